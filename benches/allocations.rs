@@ -0,0 +1,91 @@
+use std::alloc::GlobalAlloc;
+use std::alloc::Layout;
+use std::alloc::System;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use criterion::black_box;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use parser_generator::CharStream;
+use parser_generator::Parser;
+use parser_generator::SymbolProperties;
+
+/// Counts every allocation and reallocation.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const LEN: usize = 10_000;
+
+/// `'[' NUMBER { ',' NUMBER } ']'`, reserving `expected` children for the
+/// repetition if given.
+fn array_parser(expected: Option<usize>) -> Parser {
+    let mut parser = Parser::new();
+    let digits = parser.one_of_chars("0123456789", None);
+    let number = parser
+        .one_or_more(&digits, SymbolProperties::new("NUMBER", true, false));
+    let comma = parser.terminal(',', None);
+    let item = parser.sequence(vec![&comma, &number], None);
+    let props = SymbolProperties::new("ITEMS", false, true);
+    let items = parser.zero_or_more(
+        &item,
+        match expected {
+            Some(count) => props.map(|p| p.with_expected_children(count)),
+            None => props,
+        },
+    );
+    let open = parser.terminal('[', None);
+    let close = parser.terminal(']', None);
+    let array = parser.sequence(
+        vec![&open, &number, &items, &close],
+        SymbolProperties::new("ARRAY", false, false),
+    );
+    parser.root_node(&array);
+    parser
+}
+
+fn allocations(c: &mut Criterion) {
+    let numbers: Vec<String> = (0..LEN).map(|i| i.to_string()).collect();
+    let input = format!("[{}]", numbers.join(","));
+
+    for (name, expected) in [("no hint", None), ("hint", Some(LEN))] {
+        let parser = array_parser(expected);
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        parser.parse(&mut CharStream::from(&input)).unwrap();
+        let count = ALLOCATIONS.load(Ordering::Relaxed) - before;
+        println!("{} element array, {}: {} allocations", LEN, name, count);
+
+        c.bench_function(&format!("{} element array, {}", LEN, name), |b| {
+            b.iter(|| parser.parse(&mut CharStream::from(black_box(&input))))
+        });
+    }
+}
+
+criterion_group!(benches, allocations);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+use criterion::black_box;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use parser_generator::examples::json::json_parser;
+use parser_generator::CharStream;
+use parser_generator::FlatNode;
+use parser_generator::Node;
+use parser_generator::NodeData;
+
+fn document(len: usize) -> String {
+    let items: Vec<String> = (0..len)
+        .map(|i| {
+            format!(
+                "{{\"id\": {}, \"tags\": [\"a\", \"b\"], \"nested\": {{\"x\": [1, 2, 3]}}}}",
+                i
+            )
+        })
+        .collect();
+    format!("[{}]", items.join(","))
+}
+
+fn count_tree(node: &Node) -> usize {
+    match &node.data {
+        NodeData::Children(c) => 1 + c.iter().map(count_tree).sum::<usize>(),
+        NodeData::Raw(_) => 1,
+    }
+}
+
+fn count_arena(node: &FlatNode, arena: &[FlatNode]) -> usize {
+    1 + node
+        .children(arena)
+        .iter()
+        .map(|n| count_arena(n, arena))
+        .sum::<usize>()
+}
+
+fn traversal(c: &mut Criterion) {
+    let input = document(1000);
+    let json = json_parser();
+    let tree = json.parse(&mut CharStream::from(&input)).unwrap();
+    let (arena, root) =
+        json.parse_arena(&mut CharStream::from(&input)).unwrap();
+    assert_eq!(count_tree(&tree), count_arena(&arena[root], &arena));
+
+    c.bench_function("traverse boxed tree", |b| {
+        b.iter(|| count_tree(black_box(&tree)))
+    });
+    c.bench_function("traverse arena", |b| {
+        b.iter(|| count_arena(&arena[root], black_box(&arena)))
+    });
+    c.bench_function("traverse arena linearly", |b| {
+        b.iter(|| {
+            black_box(&arena)
+                .iter()
+                .filter(|n| n.raw().is_some())
+                .count()
+        })
+    });
+}
+
+criterion_group!(benches, traversal);
+criterion_main!(benches);
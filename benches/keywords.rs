@@ -0,0 +1,62 @@
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use parser_generator::CharStream;
+use parser_generator::Parser;
+
+/// 50 keywords sharing prefixes, such as `con`, `const` and `construct`.
+fn keywords() -> Vec<String> {
+    let mut keywords = Vec::new();
+    for prefix in ["con", "pro", "re", "in", "de"] {
+        for suffix in [
+            "", "st", "struct", "cat", "fig", "tinue", "sole", "duce", "vert",
+            "tract",
+        ] {
+            keywords.push(format!("{}{}", prefix, suffix));
+        }
+    }
+    // Longer keywords first, so that prefixes do not shadow them.
+    keywords.sort_by_key(|k| std::cmp::Reverse(k.len()));
+    keywords
+}
+
+fn keyword_parser() -> Parser {
+    let alternatives: Vec<String> = keywords()
+        .iter()
+        .map(|k| {
+            let chars: Vec<String> =
+                k.chars().map(|c| format!("'{}'", c)).collect();
+            format!("( {} )", chars.join(" "))
+        })
+        .collect();
+    Parser::from_bnf(&format!(
+        "ROOT(root) = KEYWORD {{ ' ' KEYWORD }};\nKEYWORD(raw) = {};",
+        alternatives.join(" | ")
+    ))
+    .unwrap()
+}
+
+fn keyword_alternatives(c: &mut Criterion) {
+    let keywords = keywords();
+    let input: Vec<&str> = (0..2000)
+        .map(|i| keywords[i % keywords.len()].as_str())
+        .collect();
+    let input = input.join(" ");
+    let parser = keyword_parser();
+    let mut compacted = keyword_parser();
+    compacted.compact();
+    assert_eq!(
+        parser.parse(&mut CharStream::from(&input)).unwrap(),
+        compacted.parse(&mut CharStream::from(&input)).unwrap()
+    );
+
+    c.bench_function("keywords as alternatives", |b| {
+        b.iter(|| parser.parse(&mut CharStream::from(&input)).unwrap())
+    });
+    c.bench_function("keywords as trie", |b| {
+        b.iter(|| compacted.parse(&mut CharStream::from(&input)).unwrap())
+    });
+}
+
+criterion_group!(benches, keyword_alternatives);
+criterion_main!(benches);
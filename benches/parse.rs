@@ -0,0 +1,83 @@
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use criterion::Throughput;
+use parser_generator::examples::json::json_parser;
+use parser_generator::CharStream;
+use parser_generator::Parser;
+
+const KEYWORDS: [&str; 12] = [
+    "if", "else", "while", "for", "return", "break", "continue", "let",
+    "const", "fn", "match", "loop",
+];
+
+fn flat_array(len: usize) -> String {
+    let items: Vec<String> = (0..len).map(|i| (i * 7919).to_string()).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn nested_arrays(depth: usize) -> String {
+    format!("{}1{}", "[".repeat(depth), "]".repeat(depth))
+}
+
+/// Whitespace separated words, most of them keywords.
+fn keyword_parser() -> Parser {
+    let keywords: Vec<String> = KEYWORDS
+        .iter()
+        .map(|k| {
+            let chars: Vec<String> =
+                k.chars().map(|c| format!("'{}'", c)).collect();
+            format!("( {} )", chars.join(" "))
+        })
+        .collect();
+    Parser::from_bnf(&format!(
+        "ROOT(root) = WORD {{ WORD }} $WHITESPACE;\n\
+         WORD(ignore) = $WHITESPACE ( KEYWORD | NAME );\n\
+         KEYWORD(raw) = {};\n\
+         NAME(raw) = 'a'..'z' {{ 'a'..'z' }};",
+        keywords.join(" | ")
+    ))
+    .unwrap()
+}
+
+fn keyword_text(len: usize) -> String {
+    let words: Vec<&str> = (0..len)
+        .map(|i| match i % 5 {
+            4 => "name",
+            _ => KEYWORDS[i % KEYWORDS.len()],
+        })
+        .collect();
+    words.join(" ")
+}
+
+/// Reports bytes per second, so that inputs of different sizes compare.
+fn bench_parse(c: &mut Criterion, name: &str, parser: &Parser, input: &str) {
+    assert!(
+        parser.parse(&mut CharStream::from(input)).is_ok(),
+        "{}",
+        name
+    );
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("parse", |b| {
+        b.iter(|| parser.parse(&mut CharStream::from(input)))
+    });
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let json = json_parser();
+    bench_parse(c, "json flat array", &json, &flat_array(10_000));
+    bench_parse(c, "json nested arrays", &json, &nested_arrays(500));
+    bench_parse(c, "keywords", &keyword_parser(), &keyword_text(10_000));
+
+    let bnf = json.to_bnf().unwrap();
+    let mut group = c.benchmark_group("bnf self-parse");
+    group.throughput(Throughput::Bytes(bnf.len() as u64));
+    group
+        .bench_function("parse", |b| b.iter(|| Parser::parse_bnf_to_ast(&bnf)));
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
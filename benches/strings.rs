@@ -0,0 +1,53 @@
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use parser_generator::examples::json::json_parser;
+use parser_generator::CharStream;
+use parser_generator::Parser;
+use parser_generator::SymbolProperties;
+
+fn string_array(len: usize) -> String {
+    let strings: Vec<String> = (0..len)
+        .map(|i| format!("\"string number {} with some padding text\"", i))
+        .collect();
+    format!("[{}]", strings.join(","))
+}
+
+/// `'"' { any except C0 controls, '"' and '\' } '"'`, repeated.
+fn excluding_parser() -> Parser {
+    let mut parser = Parser::new();
+    let quote = parser.terminal('"', None);
+    let comma = parser.terminal(',', None);
+    let char = parser.any_except_ranges(
+        &[('\u{0}', '\u{1f}'), ('"', '"'), ('\\', '\\')],
+        None,
+    );
+    let chars = parser.zero_or_more(&char, None);
+    let string = parser.sequence(vec![&quote, &chars, &quote], None);
+    let item = parser.sequence(vec![&comma, &string], None);
+    let items = parser.zero_or_more(&item, None);
+    let open = parser.terminal('[', None);
+    let close = parser.terminal(']', None);
+    let root = parser.sequence(
+        vec![&open, &string, &items, &close],
+        SymbolProperties::new("ROOT", false, false),
+    );
+    parser.root_node(&root);
+    parser
+}
+
+fn strings(c: &mut Criterion) {
+    let input = string_array(1000);
+    let json = json_parser();
+    let excluding = excluding_parser();
+
+    c.bench_function("json string array", |b| {
+        b.iter(|| json.parse(&mut CharStream::from(&input)))
+    });
+    c.bench_function("any_except_ranges string array", |b| {
+        b.iter(|| excluding.parse(&mut CharStream::from(&input)))
+    });
+}
+
+criterion_group!(benches, strings);
+criterion_main!(benches);
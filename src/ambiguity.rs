@@ -0,0 +1,572 @@
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+use crate::input::CharStream;
+use crate::input::Position;
+use crate::input::Range;
+use crate::parse::eat;
+use crate::parse::in_ranges;
+use crate::parse::match_balanced;
+use crate::parse::match_nested_comment;
+use crate::parse::match_rest_of_line;
+use crate::parse::match_take;
+use crate::parse::Node;
+use crate::parse::ParseError;
+use crate::parse::Parser;
+use crate::parse::SymbolIdent;
+use crate::parse::SymbolType;
+
+/// One way a symbol can match: where the match ends and the nodes it
+/// forwards to its parent.
+type Match = (Position, Vec<Node>);
+
+/// Called with each match of a symbol, breaks to end the search.
+type Found<'a> =
+    dyn FnMut(&mut CharStream, &mut Explore, Match) -> ControlFlow<()> + 'a;
+
+/// Like [`Found`], also given the span of the elements that are not
+/// whitespace of a tight sequence.
+type FoundSpan<'a> = dyn FnMut(
+        &mut CharStream,
+        &mut Explore,
+        Match,
+        Option<Range>,
+    ) -> ControlFlow<()>
+    + 'a;
+
+/// What the calls of one [`Parser::parse_all`] share.
+struct Explore {
+    depth: usize,
+    nodes: usize,
+    /// The symbols being matched and where they started, to cut off left
+    /// recursion.
+    active: HashSet<(usize, usize)>,
+    error: Option<ParseError>,
+}
+
+impl Parser {
+    /// Every parse tree of the whole input, at most `limit` of them.
+    ///
+    /// Where [`Parser::parse`] commits to the first alternative that matches,
+    /// this explores every alternative of a `OneOf`, both outcomes of an
+    /// `Optional` and every repetition count, so an ambiguous grammar yields
+    /// one tree per reading. The search is depth first and stops at the
+    /// `limit`th tree, but it is still exponential in the worst case and
+    /// meant for studying grammars, not for production parsing. A symbol
+    /// reached again at the same position while it is being matched, through
+    /// left recursion, does not match there.
+    ///
+    /// Builds nodes like [`Parser::parse`]: skips a shebang line, narrows
+    /// tight nodes, converts and runs the hooks of every node, failing with
+    /// the first [`ParseError::Rejected`], and fails with
+    /// [`ParseError::TooDeep`] and [`ParseError::TooManyNodes`] past
+    /// [`Parser::with_max_depth`] and [`Parser::with_max_nodes`], the latter
+    /// counting the nodes of every reading tried.
+    pub fn parse_all(
+        &self,
+        stream: &mut CharStream,
+        limit: usize,
+    ) -> Result<Vec<Node>, ParseError> {
+        let root = self.root_node.ok_or(ParseError::NoRoot)?;
+        if root.0 >= self.symbol_registry.len() {
+            return Err(ParseError::InvalidRoot(root));
+        }
+        if self.get_symbol(&root).properties.ignore {
+            panic!("Root node is set to be ignored");
+        }
+        self.skip_shebang(stream);
+        let start = stream.get_pos();
+        let mut trees = Vec::new();
+        let mut stop = start;
+        let mut explore = Explore {
+            depth: 0,
+            nodes: 0,
+            active: HashSet::new(),
+            error: None,
+        };
+        if limit > 0 {
+            let _ = self.each_match(
+                &root,
+                stream,
+                start,
+                &mut explore,
+                &mut |stream, _, (end, mut nodes)| {
+                    stream.set_pos(end);
+                    if stream.peek().is_none() {
+                        trees.push(nodes.remove(0));
+                        stop = end;
+                        if trees.len() >= limit {
+                            return ControlFlow::Break(());
+                        }
+                    }
+                    ControlFlow::Continue(())
+                },
+            );
+        }
+        if let Some(err) = stream.take_error().or(explore.error) {
+            return Err(err);
+        }
+        stream.set_pos(stop);
+        Ok(trees)
+    }
+
+    /// Calls `found` with every match of `ident` at `start`.
+    fn each_match(
+        &self,
+        ident: &SymbolIdent,
+        stream: &mut CharStream,
+        start: Position,
+        explore: &mut Explore,
+        found: &mut Found,
+    ) -> ControlFlow<()> {
+        if self.max_depth.is_some_and(|max| explore.depth >= max) {
+            explore.error = Some(ParseError::TooDeep);
+            return ControlFlow::Break(());
+        }
+        let key = (ident.0, start.index());
+        if !explore.active.insert(key) {
+            return ControlFlow::Continue(());
+        }
+        explore.depth += 1;
+        let symbol = self.get_symbol(ident);
+        let result = self.each_match_of(
+            ident,
+            stream,
+            start,
+            explore,
+            &mut |stream, explore, (end, nodes), inner| {
+                // The parent goes on outside of this symbol.
+                explore.active.remove(&key);
+                explore.depth -= 1;
+                let result = if symbol.properties.ignore {
+                    found(stream, explore, (end, nodes))
+                } else {
+                    stream.set_pos(end);
+                    let range = inner.unwrap_or_else(|| Range::new(start, end));
+                    match self.build_node(ident, stream, range, nodes, explore)
+                    {
+                        Ok(node) => found(stream, explore, (end, vec![node])),
+                        Err(e) => {
+                            explore.error = Some(e);
+                            ControlFlow::Break(())
+                        }
+                    }
+                };
+                explore.active.insert(key);
+                explore.depth += 1;
+                result
+            },
+        );
+        explore.depth -= 1;
+        explore.active.remove(&key);
+        result
+    }
+
+    /// The node of `ident` for its match spanning `range`, counted and
+    /// passed through the hooks.
+    fn build_node(
+        &self,
+        ident: &SymbolIdent,
+        stream: &mut CharStream,
+        range: Range,
+        nodes: Vec<Node>,
+        explore: &mut Explore,
+    ) -> Result<Node, ParseError> {
+        explore.nodes += 1;
+        if self.max_nodes.is_some_and(|max| explore.nodes > max) {
+            return Err(ParseError::TooManyNodes);
+        }
+        let symbol = self.get_symbol(ident);
+        let data = symbol.node_data(|| stream.slice(&range), nodes);
+        let mut node = symbol.node(range, data);
+        self.run_hooks(&mut node)?;
+        Ok(node)
+    }
+
+    fn each_match_of(
+        &self,
+        ident: &SymbolIdent,
+        stream: &mut CharStream,
+        start: Position,
+        explore: &mut Explore,
+        found: &mut FoundSpan,
+    ) -> ControlFlow<()> {
+        stream.set_pos(start);
+        let symbol = self.get_symbol(ident);
+        if let SymbolType::Sequence(s) = &symbol.symbol_type {
+            let tight = symbol.properties.tight.then_some(None);
+            return self.each_sequence(
+                s,
+                stream,
+                (start, Vec::new()),
+                tight,
+                explore,
+                found,
+            );
+        }
+        let found = &mut |stream: &mut CharStream, explore: &mut Explore, m| {
+            found(stream, explore, m, None)
+        };
+        let matches: Vec<Match> = match &symbol.symbol_type {
+            SymbolType::Sequence(_) => unreachable!(),
+            SymbolType::OneOf(s) => {
+                for id in s {
+                    self.each_match(id, stream, start, explore, found)?;
+                }
+                return ControlFlow::Continue(());
+            }
+            SymbolType::Optional(id) => {
+                self.each_match(id, stream, start, explore, found)?;
+                return found(stream, explore, (start, vec![]));
+            }
+            SymbolType::ZeroOrMore(id) => {
+                return self.each_repetition(
+                    id,
+                    stream,
+                    (start, Vec::new()),
+                    (0, None),
+                    explore,
+                    found,
+                );
+            }
+            SymbolType::OneOrMore(id) => {
+                return self.each_repetition(
+                    id,
+                    stream,
+                    (start, Vec::new()),
+                    (1, None),
+                    explore,
+                    found,
+                );
+            }
+            SymbolType::Repeat { symbol, min, max } => {
+                return self.each_repetition(
+                    symbol,
+                    stream,
+                    (start, Vec::new()),
+                    (*min, *max),
+                    explore,
+                    found,
+                );
+            }
+            SymbolType::Terminal(c) => single(stream, |n| n == *c),
+            SymbolType::AnyExcept(ranges) => {
+                single(stream, |n| !in_ranges(ranges, n))
+            }
+            SymbolType::Literal(s) => {
+                if eat(s, stream) {
+                    vec![(stream.get_pos(), vec![])]
+                } else {
+                    vec![]
+                }
+            }
+            SymbolType::LiteralSet(trie) => {
+                let mut ends: Vec<Position> =
+                    trie.matches(stream).into_iter().map(|m| m.1).collect();
+                ends.sort_by_key(|end| end.index());
+                ends.dedup();
+                ends.into_iter().map(|end| (end, vec![])).collect()
+            }
+            SymbolType::CharSet(ranges) => {
+                single(stream, |n| in_ranges(ranges, n))
+            }
+            SymbolType::Lookbehind { symbol, length } => {
+                if self.matches_behind(symbol, *length, stream) {
+                    vec![(start, vec![])]
+                } else {
+                    vec![]
+                }
+            }
+            SymbolType::And(id) => {
+                if self.matches_at(id, stream) {
+                    vec![(start, vec![])]
+                } else {
+                    vec![]
+                }
+            }
+            SymbolType::Not(id) => {
+                if self.matches_at(id, stream) {
+                    vec![]
+                } else {
+                    vec![(start, vec![])]
+                }
+            }
+            SymbolType::Take(n) => {
+                if match_take(*n, stream) {
+                    vec![(stream.get_pos(), vec![])]
+                } else {
+                    vec![]
+                }
+            }
+            SymbolType::RestOfLine => {
+                match_rest_of_line(stream);
+                vec![(stream.get_pos(), vec![])]
+            }
+            SymbolType::EndOfInput => match stream.peek() {
+                Some(_) => vec![],
+                None => vec![(start, vec![])],
+            },
+            SymbolType::Balanced { open, close } => {
+                if match_balanced(*open, *close, stream) {
+                    vec![(stream.get_pos(), vec![])]
+                } else {
+                    vec![]
+                }
+            }
+            SymbolType::NestedComment { open, close } => {
+                if match_nested_comment(open, close, stream) {
+                    vec![(stream.get_pos(), vec![])]
+                } else {
+                    vec![]
+                }
+            }
+        };
+        for m in matches {
+            found(stream, explore, m)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Calls `found` with every match of `symbols` after the partial match
+    /// `(start, before)`. `tight` is set for tight sequences, with the span
+    /// of the elements so far that are not whitespace, if any.
+    fn each_sequence(
+        &self,
+        symbols: &[SymbolIdent],
+        stream: &mut CharStream,
+        (start, before): Match,
+        tight: Option<Option<Range>>,
+        explore: &mut Explore,
+        found: &mut FoundSpan,
+    ) -> ControlFlow<()> {
+        let Some((first, rest)) = symbols.split_first() else {
+            // A span without elements is empty at the end of the sequence.
+            let inner =
+                tight.map(|inner| inner.unwrap_or(Range::new(start, start)));
+            return found(stream, explore, (start, before), inner);
+        };
+        self.each_match(
+            first,
+            stream,
+            start,
+            explore,
+            &mut |stream, explore, (end, nodes)| {
+                let mut before = before.clone();
+                before.extend(nodes);
+                let tight = match tight.clone() {
+                    Some(inner) if !self.only_whitespace(first) => {
+                        let from = inner.map_or(start, |inner| inner.0);
+                        Some(Some(Range::new(from, end)))
+                    }
+                    tight => tight,
+                };
+                self.each_sequence(
+                    rest,
+                    stream,
+                    (end, before),
+                    tight,
+                    explore,
+                    found,
+                )
+            },
+        )
+    }
+
+    /// Calls `found` with every repetition of at least `min` and at most
+    /// `max` iterations after the partial match `(start, before)`, longer
+    /// ones first. Iterations that consume nothing end the repetition, and
+    /// stand for the iterations still missing, otherwise a nullable body
+    /// would repeat forever.
+    fn each_repetition(
+        &self,
+        ident: &SymbolIdent,
+        stream: &mut CharStream,
+        (start, before): Match,
+        (min, max): (usize, Option<usize>),
+        explore: &mut Explore,
+        found: &mut Found,
+    ) -> ControlFlow<()> {
+        if max == Some(0) {
+            return found(stream, explore, (start, before));
+        }
+        self.each_match(
+            ident,
+            stream,
+            start,
+            explore,
+            &mut |stream, explore, (end, nodes)| {
+                let mut before = before.clone();
+                before.extend(nodes);
+                if end.index() == start.index() {
+                    if min > 0 {
+                        return found(stream, explore, (end, before));
+                    }
+                    return ControlFlow::Continue(());
+                }
+                let bounds = (min.saturating_sub(1), max.map(|max| max - 1));
+                self.each_repetition(
+                    ident,
+                    stream,
+                    (end, before),
+                    bounds,
+                    explore,
+                    found,
+                )
+            },
+        )?;
+        if min == 0 {
+            return found(stream, explore, (start, before));
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+fn single(
+    stream: &mut CharStream,
+    accept: impl Fn(char) -> bool,
+) -> Vec<Match> {
+    match stream.peek() {
+        Some(c) if accept(*c) => {
+            stream.next();
+            vec![(stream.get_pos(), vec![])]
+        }
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::NodeData;
+    use crate::parse::SymbolProperties;
+
+    const SPLIT_BNF: &str = r#"
+ROOT(root) = LEFT RIGHT;
+LEFT(raw) = 'x' [ 'x' ];
+RIGHT(raw) = [ 'x' ] 'x';
+"#;
+
+    fn raw_children(node: &Node) -> Vec<String> {
+        match &node.data {
+            NodeData::Children(c) => c
+                .iter()
+                .map(|n| match &n.data {
+                    NodeData::Raw(raw) => raw.clone(),
+                    NodeData::Children(_) => panic!("expected raw node"),
+                })
+                .collect(),
+            NodeData::Raw(_) => panic!("expected children"),
+        }
+    }
+
+    #[test]
+    fn two_trees() {
+        let parser = Parser::from_bnf(SPLIT_BNF).unwrap();
+
+        let trees = parser.parse_all(&mut CharStream::from("xxx"), 10).unwrap();
+
+        assert_eq!(2, trees.len());
+        assert_eq!(vec!["xx", "x"], raw_children(&trees[0]));
+        assert_eq!(vec!["x", "xx"], raw_children(&trees[1]));
+        // The committing parser takes "xx" for LEFT and never backs out.
+        assert!(parser.parse(&mut CharStream::from("xxx")).is_err());
+    }
+
+    #[test]
+    fn limit() {
+        let parser = Parser::from_bnf(SPLIT_BNF).unwrap();
+
+        let all = |input: &str, limit| {
+            parser
+                .parse_all(&mut CharStream::from(input), limit)
+                .unwrap()
+        };
+        assert_eq!(1, all("xxx", 1).len());
+        assert!(all("x", 10).is_empty());
+
+        // Exponentially many ways to split the input, the search stops early.
+        let parser = Parser::from_bnf(
+            "ROOT(root) = { A };\nA(raw) = 'x' | ( 'x' 'x' );",
+        )
+        .unwrap();
+        let input = "x".repeat(80);
+        let trees = parser.parse_all(&mut CharStream::from(&input), 5);
+        assert_eq!(5, trees.unwrap().len());
+    }
+
+    #[test]
+    fn left_recursion() {
+        // Grammar files reject this, combinators do not.
+        let mut parser = Parser::new();
+        let expr =
+            parser.one_of(vec![], SymbolProperties::new("EXPR", true, false));
+        let plus = parser.terminal('+', None);
+        let x = parser.terminal('x', None);
+        let sum = parser.sequence(vec![&expr, &plus, &x], None);
+        parser.add_ident(&expr, &sum);
+        parser.add_ident(&expr, &x);
+        parser.root_node(&expr);
+
+        let all = |input: &str| {
+            parser.parse_all(&mut CharStream::from(input), 10).unwrap()
+        };
+        assert_eq!(1, all("x").len());
+        assert!(all("x+x").is_empty());
+    }
+
+    #[test]
+    fn like_parse() {
+        let parser = Parser::from_bnf(SPLIT_BNF).unwrap().with_shebang_skip();
+        let trees =
+            parser.parse_all(&mut CharStream::from("#!/bin/split\nxxx"), 10);
+        assert_eq!(2, trees.unwrap().len());
+
+        let parser = Parser::from_bnf(SPLIT_BNF).unwrap().with_max_depth(2);
+        assert!(matches!(
+            parser.parse_all(&mut CharStream::from("xxx"), 10),
+            Err(ParseError::TooDeep)
+        ));
+        assert!(matches!(
+            Parser::new().parse_all(&mut CharStream::from("xxx"), 10),
+            Err(ParseError::NoRoot)
+        ));
+    }
+
+    #[test]
+    fn nodes_like_parse() {
+        let mut parser = Parser::from_bnf(SPLIT_BNF).unwrap();
+        parser.on_complete(
+            "LEFT",
+            Box::new(|node| match &node.data {
+                NodeData::Raw(raw) if raw == "xx" => Err("Too long".into()),
+                _ => Ok(()),
+            }),
+        );
+        assert!(parser.parse(&mut CharStream::from("xxx")).is_err());
+        assert!(matches!(
+            parser.parse_all(&mut CharStream::from("xxx"), 10),
+            Err(ParseError::Rejected { .. })
+        ));
+
+        let parser = Parser::from_bnf(SPLIT_BNF).unwrap().with_max_nodes(2);
+        assert!(matches!(
+            parser.parse_all(&mut CharStream::from("xxx"), 10),
+            Err(ParseError::TooManyNodes)
+        ));
+
+        let mut parser = Parser::from_bnf(
+            "ROOT(root) = NUMBER NAME;\nNUMBER(raw) = '1' [ '2' ];\n\
+             NAME(raw,tight) = [ $WHITESPACE ] 'a' [ $WHITESPACE ];",
+        )
+        .unwrap();
+        parser.parse_as::<u32>("NUMBER");
+        let trees = parser
+            .parse_all(&mut CharStream::from("12 a "), 10)
+            .unwrap();
+        assert_eq!(1, trees.len());
+        let number = trees[0].field("NUMBER").unwrap();
+        assert_eq!(Some(&12), number.value::<u32>());
+        let name = trees[0].field("NAME").unwrap();
+        assert_eq!(NodeData::Raw("a".to_owned()), name.data);
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::VecDeque;
+
+use crate::input::CharStream;
+use crate::input::Range;
+use crate::parse::Node;
+use crate::parse::NodeData;
+use crate::parse::ParseError;
+use crate::parse::Parser;
+
+/// A [`Node`] stored in a flat vector. Its children are the contiguous
+/// entries `children` of the same vector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlatNode {
+    pub node_type: String,
+    pub loc: Range,
+    pub children: std::ops::Range<usize>,
+    raw: Option<String>,
+}
+
+impl FlatNode {
+    /// The matched text of a raw node.
+    pub fn raw(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    pub fn children<'a>(&self, arena: &'a [FlatNode]) -> &'a [FlatNode] {
+        &arena[self.children.clone()]
+    }
+
+    /// The first direct child of type `node_type`, like [`Node::field`].
+    pub fn field<'a>(
+        &self,
+        arena: &'a [FlatNode],
+        node_type: &str,
+    ) -> Option<&'a FlatNode> {
+        self.children(arena)
+            .iter()
+            .find(|n| n.node_type == node_type)
+    }
+}
+
+/// Lays `root` out breadth first, so that the children of every node are
+/// next to each other. The root ends up at index 0.
+fn flatten(root: Node) -> Vec<FlatNode> {
+    let mut arena = Vec::new();
+    let mut queue = VecDeque::from([root]);
+    let mut enqueued = 1;
+    while let Some(node) = queue.pop_front() {
+        let (children, raw) = match node.data {
+            NodeData::Children(c) => {
+                let children = enqueued..enqueued + c.len();
+                enqueued += c.len();
+                queue.extend(c);
+                (children, None)
+            }
+            NodeData::Raw(raw) => (enqueued..enqueued, Some(raw)),
+        };
+        arena.push(FlatNode {
+            node_type: node.node_type,
+            loc: node.loc,
+            children,
+            raw,
+        });
+    }
+    arena
+}
+
+impl Parser {
+    /// Parses like [`Parser::parse`] and returns the tree as a flat vector
+    /// of [`FlatNode`]s together with the index of the root.
+    ///
+    /// This saves no allocations over [`Parser::parse`]: the parse still
+    /// builds the [`Node`] tree, which is then laid out in the arena, moving
+    /// node types and raw text over. The arena pays off for trees that are
+    /// walked often.
+    pub fn parse_arena(
+        &self,
+        stream: &mut CharStream,
+    ) -> Result<(Vec<FlatNode>, usize), ParseError> {
+        let root = self.parse(stream)?;
+        Ok((flatten(root), 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+
+    #[test]
+    fn same_tree() {
+        let parser = json_parser();
+        let input = r#"{"a": [1, {"b": null}], "c": "d"}"#;
+
+        let tree = parser.parse(&mut CharStream::from(input)).unwrap();
+        let (arena, root) =
+            parser.parse_arena(&mut CharStream::from(input)).unwrap();
+
+        fn same(node: &Node, flat: &FlatNode, arena: &[FlatNode]) -> bool {
+            let children_match = match &node.data {
+                NodeData::Children(c) => {
+                    let flat_children = flat.children(arena);
+                    flat.raw().is_none()
+                        && c.len() == flat_children.len()
+                        && c.iter()
+                            .zip(flat_children)
+                            .all(|(n, f)| same(n, f, arena))
+                }
+                NodeData::Raw(raw) => flat.raw() == Some(raw.as_str()),
+            };
+            node.node_type == flat.node_type
+                && node.loc == flat.loc
+                && children_match
+        }
+        assert!(same(&tree, &arena[root], &arena));
+
+        let object = arena[root].field(&arena, "OBJECT").unwrap();
+        assert_eq!(2, object.children(&arena).len());
+    }
+}
@@ -14,6 +14,10 @@ pub fn bnf_parser() -> Parser {
     let SEP = parser.terminal(' ', None);
     let PIPE = parser.terminal('|', None);
     let COMMA = parser.terminal(',', None);
+    let COLON = parser.terminal(':', None);
+    let QUOTE = parser.terminal('"', None);
+    let ATTR_CHAR = parser.any_except(&['"'], None);
+    let ATTR_CHARS = parser.zero_or_more(&ATTR_CHAR, None);
     let CHAR = parser.any_except(&[], None);
     let OPT_BEGIN = parser.terminal('[', None);
     let OPT_END = parser.terminal(']', None);
@@ -26,45 +30,207 @@ pub fn bnf_parser() -> Parser {
         " \n\r\t",
         SymbolProperties::new("WHITESPACE_CHAR", false, true),
     );
-    let IDENT_WHITESPACE = parser.sequence_chars(
-        "$WHITESPACE",
+    let WHITESPACE_KEYWORD = parser.sequence_chars("$WHITESPACE", None);
+    let PLUS = parser.terminal('+', None);
+    let PLUS_OPT = parser.optional(&PLUS, None);
+    let IDENT_WHITESPACE = parser.sequence(
+        vec![&WHITESPACE_KEYWORD, &PLUS_OPT],
         SymbolProperties::new("WHITESPACE_ID", true, false),
     );
-    let ANY = parser.terminal('*', SymbolProperties::new("ANY", true, false));
+    let EOL_TEXT = parser.sequence_chars(
+        "$EOL_TEXT",
+        SymbolProperties::new("EOL_TEXT", true, false),
+    );
+    let DOT = parser.terminal('.', None);
+    let DIGIT = parser.one_of_chars("0123456789", None);
+    let COUNT =
+        parser.one_or_more(&DIGIT, SymbolProperties::new("COUNT", true, false));
+    let TAKE = parser.sequence(
+        vec![&DOT, &MANY_BEGIN, &COUNT, &MANY_END],
+        SymbolProperties::new("TAKE", false, false),
+    );
+    let HEX_CHAR = parser.one_of_chars("0123456789ABCDEFabcdef", None);
+    let HEX_CHARS = parser.one_or_more(&HEX_CHAR, None);
+    let LC_U = parser.terminal('u', None);
+    let CODEPOINT_U = parser.sequence_chars("U+", None);
+    let CODEPOINT_X = parser.sequence_chars("\\x", None);
+    let CODEPOINT_PREFIX =
+        parser.one_of(vec![&CODEPOINT_U, &CODEPOINT_X], None);
 
     let WHITESPACE = parser.zero_or_more(
         &WHITESPACE_CHAR,
         SymbolProperties::new("WHITESPACE", false, true),
     );
 
+    // Whitespace that may hold `#` or `//` comments running to the end of
+    // the line, which are dropped.
+    let HASH = parser.terminal('#', None);
+    let DOUBLE_SLASH = parser.sequence_chars("//", None);
+    let LINE_COMMENT_START = parser.one_of(vec![&HASH, &DOUBLE_SLASH], None);
+    let LINE_COMMENT_TEXT = parser.rest_of_line(None);
+    let LINE_COMMENT =
+        parser.sequence(vec![&LINE_COMMENT_START, &LINE_COMMENT_TEXT], None);
+    let GAP_ITEM = parser.one_of(vec![&WHITESPACE_CHAR, &LINE_COMMENT], None);
+    let GAP = parser
+        .zero_or_more(&GAP_ITEM, SymbolProperties::new("GAP", false, true));
+    // The rest of a statement's line, which may end in a comment.
+    let INLINE_SPACE = parser.one_of_chars(" \t", None);
+    let INLINE_SPACES = parser.zero_or_more(&INLINE_SPACE, None);
+    let LINE_COMMENT_OPT = parser.optional(&LINE_COMMENT, None);
+    let LINE_END = parser.sequence(
+        vec![&INLINE_SPACES, &LINE_COMMENT_OPT],
+        SymbolProperties::new("LINE_END", false, true),
+    );
+
     let IDENTIFIER = parser.one_or_more(
         &IDENT_CHAR,
         SymbolProperties::new("IDENTIFIER", true, false),
     );
-    let TERMINAL = parser.sequence(
-        vec![&TERMINAL_DELIM, &CHAR, &TERMINAL_DELIM],
+    // `'\n'`, `'\t'`, `'\r'`, `'\\'`, `'\''` or `'\u{1F600}'`. A lone
+    // `'\'` still is a backslash.
+    let BACKSLASH = parser.terminal('\\', None);
+    let ESCAPED_CHAR = parser.one_of_chars("ntr\\'", None);
+    let UNICODE_ESCAPE =
+        parser.sequence(vec![&LC_U, &MANY_BEGIN, &HEX_CHARS, &MANY_END], None);
+    let ESCAPE_CODE = parser.one_of(vec![&ESCAPED_CHAR, &UNICODE_ESCAPE], None);
+    let ESCAPE = parser.sequence(vec![&BACKSLASH, &ESCAPE_CODE], None);
+    let ESCAPED_TERMINAL =
+        parser.sequence(vec![&TERMINAL_DELIM, &ESCAPE, &TERMINAL_DELIM], None);
+    let PLAIN_TERMINAL =
+        parser.sequence(vec![&TERMINAL_DELIM, &CHAR, &TERMINAL_DELIM], None);
+    let TERMINAL = parser.one_of(
+        vec![&ESCAPED_TERMINAL, &PLAIN_TERMINAL],
         SymbolProperties::new("TERMINAL", true, false),
     );
+    let STRING_TERMINAL = parser.sequence(
+        vec![&QUOTE, &ATTR_CHARS, &QUOTE],
+        SymbolProperties::new("STRING_TERMINAL", true, false),
+    );
+    let CODEPOINT = parser.sequence(
+        vec![&CODEPOINT_PREFIX, &HEX_CHARS],
+        SymbolProperties::new("CODEPOINT", true, false),
+    );
+    // `'a'..'z'`, or `'a' - 'z'` with optional whitespace.
+    let RANGE_DOTS = parser.sequence_chars("..", None);
+    let DASH = parser.terminal('-', None);
+    let RANGE_DASH =
+        parser.sequence(vec![&WHITESPACE, &DASH, &WHITESPACE], None);
+    let RANGE_SEP = parser.one_of(vec![&RANGE_DOTS, &RANGE_DASH], None);
+    let RANGE_BOUND = parser.one_of(vec![&TERMINAL, &CODEPOINT], None);
+    let RANGE = parser.sequence(
+        vec![&RANGE_BOUND, &RANGE_SEP, &RANGE_BOUND],
+        SymbolProperties::new("RANGE", false, false),
+    );
+
+    // `*`, or `*('"', '\', U+0..U+1F)` excluding the listed chars.
+    let STAR = parser.terminal('*', None);
+    let EXCLUDED = parser.one_of(vec![&RANGE, &TERMINAL, &CODEPOINT], None);
+    let EXCLUDED_SEP = parser.sequence(vec![&GAP, &COMMA, &GAP], None);
+    let EXCLUDED_ITEM = parser.sequence(vec![&EXCLUDED_SEP, &EXCLUDED], None);
+    let EXCLUDED_ITEMS = parser.zero_or_more(&EXCLUDED_ITEM, None);
+    let EXCLUSION = parser.sequence(
+        vec![&PAR_BEGIN, &GAP, &EXCLUDED, &EXCLUDED_ITEMS, &GAP, &PAR_END],
+        None,
+    );
+    let EXCLUSION_OPT = parser.optional(&EXCLUSION, None);
+    let ANY = parser.sequence(
+        vec![&STAR, &EXCLUSION_OPT],
+        SymbolProperties::new("ANY", false, false),
+    );
+
+    // LABEL
+    let EXPR_LABELED = parser.one_of(
+        vec![
+            &RANGE,
+            &TERMINAL,
+            &STRING_TERMINAL,
+            &CODEPOINT,
+            &IDENTIFIER,
+            &ANY,
+            &EOL_TEXT,
+            &TAKE,
+            &IDENT_WHITESPACE,
+        ],
+        SymbolProperties::new("EXPR_LABELED", false, true),
+    );
+    let LABEL = parser
+        .one_or_more(&LC_CHAR, SymbolProperties::new("LABEL", true, false));
+    let LABELED = parser.sequence(
+        vec![&LABEL, &COLON, &EXPR_LABELED],
+        SymbolProperties::new("LABELED", false, false),
+    );
 
     // EXPRESSION
     let EXPRESSION = parser.one_of(
-        vec![&TERMINAL, &IDENTIFIER, &ANY, &IDENT_WHITESPACE],
+        vec![
+            &LABELED,
+            &RANGE,
+            &TERMINAL,
+            &STRING_TERMINAL,
+            &CODEPOINT,
+            &IDENTIFIER,
+            &ANY,
+            &EOL_TEXT,
+            &TAKE,
+            &IDENT_WHITESPACE,
+        ],
         SymbolProperties::new("EXPR", false, true),
     );
     let EXPR_SEQ = parser.one_of(
-        vec![&TERMINAL, &IDENTIFIER, &ANY, &IDENT_WHITESPACE],
+        vec![
+            &LABELED,
+            &RANGE,
+            &TERMINAL,
+            &STRING_TERMINAL,
+            &CODEPOINT,
+            &IDENTIFIER,
+            &ANY,
+            &EOL_TEXT,
+            &TAKE,
+            &IDENT_WHITESPACE,
+        ],
         SymbolProperties::new("EXPR_SEQ", false, true),
     );
     let EXPR_ONE_OF = parser.one_of(
-        vec![&TERMINAL, &IDENTIFIER, &ANY, &IDENT_WHITESPACE],
+        vec![
+            &LABELED,
+            &RANGE,
+            &TERMINAL,
+            &STRING_TERMINAL,
+            &CODEPOINT,
+            &IDENTIFIER,
+            &ANY,
+            &EOL_TEXT,
+            &TAKE,
+            &IDENT_WHITESPACE,
+        ],
         SymbolProperties::new("EXPR_ONE_OF", false, true),
     );
     let EXPR_OPT = parser.one_of(
-        vec![&TERMINAL, &IDENTIFIER, &ANY, &IDENT_WHITESPACE],
+        vec![
+            &LABELED,
+            &RANGE,
+            &TERMINAL,
+            &STRING_TERMINAL,
+            &CODEPOINT,
+            &IDENTIFIER,
+            &ANY,
+            &EOL_TEXT,
+            &TAKE,
+            &IDENT_WHITESPACE,
+        ],
         SymbolProperties::new("EXPR_OPT", false, true),
     );
     let EXPR_MANY = parser.one_of(
-        vec![&TERMINAL, &IDENTIFIER, &IDENT_WHITESPACE],
+        vec![
+            &LABELED,
+            &RANGE,
+            &TERMINAL,
+            &STRING_TERMINAL,
+            &CODEPOINT,
+            &IDENTIFIER,
+            &IDENT_WHITESPACE,
+        ],
         SymbolProperties::new("EXPR_MANY", false, true),
     );
 
@@ -82,8 +248,8 @@ pub fn bnf_parser() -> Parser {
         SymbolProperties::new("SEQUENCE", false, false),
     );
     let SEQ_PAR = parser.sequence(
-        vec![&PAR_BEGIN, &WHITESPACE, &SEQ, &WHITESPACE, &PAR_END],
-        SymbolProperties::new("SEQ_PAR", false, true),
+        vec![&PAR_BEGIN, &GAP, &SEQ, &GAP, &PAR_END],
+        SymbolProperties::new("GROUP", false, false),
     );
 
     // ONE OF
@@ -100,130 +266,194 @@ pub fn bnf_parser() -> Parser {
         SymbolProperties::new("ONE_OF", false, false),
     );
     let ONE_OF_PAR = parser.sequence(
-        vec![&PAR_BEGIN, &WHITESPACE, &ONE_OF, &WHITESPACE, &PAR_END],
-        SymbolProperties::new("ONE_OF_PAR", false, true),
+        vec![&PAR_BEGIN, &GAP, &ONE_OF, &GAP, &PAR_END],
+        SymbolProperties::new("GROUP", false, false),
     );
 
     // OPTIONAL
     let OPT = parser.sequence(
-        vec![&OPT_BEGIN, &WHITESPACE, &EXPR_OPT, &WHITESPACE, &OPT_END],
+        vec![&OPT_BEGIN, &GAP, &EXPR_OPT, &GAP, &OPT_END],
         SymbolProperties::new("OPTIONAL", false, false),
     );
 
-    // MANY
+    // MANY, optionally bounded as in `{ X }<4>`, `{ X }<2,4>` or `{ X }<2,>`
+    let BOUNDS_BEGIN = parser.terminal('<', None);
+    let BOUNDS_END = parser.terminal('>', None);
+    let DIGITS = parser.one_or_more(&DIGIT, None);
+    let DIGITS_OPT = parser.optional(&DIGITS, None);
+    let BOUNDS_MAX = parser.sequence(vec![&COMMA, &DIGITS_OPT], None);
+    let BOUNDS_MAX_OPT = parser.optional(&BOUNDS_MAX, None);
+    let BOUNDS = parser.sequence(
+        vec![&BOUNDS_BEGIN, &DIGITS, &BOUNDS_MAX_OPT, &BOUNDS_END],
+        SymbolProperties::new("BOUNDS", true, false),
+    );
+    let BOUNDS_OPT = parser.optional(&BOUNDS, None);
     let MANY = parser.sequence(
-        vec![&MANY_BEGIN, &WHITESPACE, &EXPR_MANY, &WHITESPACE, &MANY_END],
+        vec![&MANY_BEGIN, &GAP, &EXPR_MANY, &GAP, &MANY_END, &BOUNDS_OPT],
         SymbolProperties::new("MANY", false, false),
     );
+
+    // PREDICATES, `&X` to require X and `!X` to rule it out without
+    // consuming it
+    let AMPERSAND = parser.terminal('&', None);
+    let BANG = parser.terminal('!', None);
+    let EXPR_PREDICATE = parser.one_of(
+        vec![
+            &RANGE,
+            &TERMINAL,
+            &STRING_TERMINAL,
+            &CODEPOINT,
+            &IDENTIFIER,
+            &ANY,
+            &EOL_TEXT,
+            &TAKE,
+            &IDENT_WHITESPACE,
+            &SEQ_PAR,
+            &ONE_OF_PAR,
+            &OPT,
+            &MANY,
+        ],
+        SymbolProperties::new("EXPR_PREDICATE", false, true),
+    );
+    let AND = parser.sequence(
+        vec![&AMPERSAND, &EXPR_PREDICATE],
+        SymbolProperties::new("AND", false, false),
+    );
+    let NOT = parser.sequence(
+        vec![&BANG, &EXPR_PREDICATE],
+        SymbolProperties::new("NOT", false, false),
+    );
     // STATEMENT
     let STMT_INFO_FLAG = parser
         .one_or_more(&LC_CHAR, SymbolProperties::new("FLAG", true, false));
+    let STMT_INFO_VALUE = parser.sequence(
+        vec![&QUOTE, &ATTR_CHARS, &QUOTE],
+        SymbolProperties::new("ATTR_VALUE", true, false),
+    );
+    let STMT_INFO_ATTR = parser.sequence(
+        vec![&STMT_INFO_FLAG, &EQUALS, &STMT_INFO_VALUE],
+        SymbolProperties::new("ATTRIBUTE", false, false),
+    );
+    let STMT_INFO_ITEM =
+        parser.one_of(vec![&STMT_INFO_ATTR, &STMT_INFO_FLAG], None);
     let STMT_INFO_FLAG_ITEM =
-        parser.sequence(vec![&COMMA, &STMT_INFO_FLAG], None);
+        parser.sequence(vec![&COMMA, &STMT_INFO_ITEM], None);
     let STMT_INFO_FLAG_ITEM_OPT =
         parser.zero_or_more(&STMT_INFO_FLAG_ITEM, None);
     let STMT_INFO = parser.sequence(
         vec![
             &PAR_BEGIN,
-            &STMT_INFO_FLAG,
+            &STMT_INFO_ITEM,
             &STMT_INFO_FLAG_ITEM_OPT,
             &PAR_END,
         ],
         SymbolProperties::new("STMT_INFO", false, false),
     );
     let STMT_INFO_OPT = parser.optional(&STMT_INFO, None);
+    let SLASHES = parser.sequence_chars("///", None);
+    let COMMENT_START = parser.one_of(vec![&HASH, &SLASHES], None);
+    let COMMENT_TEXT =
+        parser.rest_of_line(SymbolProperties::new("COMMENT", true, false));
+    let COMMENT =
+        parser.sequence(vec![&WHITESPACE, &COMMENT_START, &COMMENT_TEXT], None);
+    let DOC = parser
+        .one_or_more(&COMMENT, SymbolProperties::new("DOC", false, false));
+    let DOC_OPT = parser.optional(&DOC, None);
+    // `#` and `///` comments right above a statement document it, other
+    // comments are skipped.
     let STATEMENT = parser.sequence(
         vec![
-            &WHITESPACE,
+            &DOC_OPT,
+            &GAP,
             &IDENTIFIER,
             &STMT_INFO_OPT,
-            &WHITESPACE,
+            &GAP,
             &EQUALS,
-            &WHITESPACE,
+            &GAP,
             &EXPRESSION,
-            &WHITESPACE,
+            &GAP,
             &STATEMENT_TERM,
-            &WHITESPACE,
+            &LINE_END,
         ],
         SymbolProperties::new("STATEMENT", false, false),
     );
 
-    let ROOT = parser
-        .one_or_more(&STATEMENT, SymbolProperties::new("ROOT", false, false));
+    let STATEMENTS = parser.one_or_more(&STATEMENT, None);
+    let ROOT = parser.sequence(
+        vec![&STATEMENTS, &GAP],
+        SymbolProperties::new("ROOT", false, false),
+    );
 
     let val = parser.get_symbol_mut(&EXPRESSION);
     match &mut val.symbol_type {
-        crate::parse::SymbolType::Sequence(_) => todo!(),
         crate::parse::SymbolType::OneOf(a) => {
+            a.insert(0, AND);
+            a.insert(0, NOT);
+            a.insert(0, MANY);
             a.insert(0, OPT);
             a.insert(0, ONE_OF);
             a.insert(0, SEQ);
         }
-        crate::parse::SymbolType::Optional(_) => todo!(),
-        crate::parse::SymbolType::OneOrMore(_) => todo!(),
-        crate::parse::SymbolType::ZeroOrMore(_) => todo!(),
-        crate::parse::SymbolType::Terminal(_) => todo!(),
-        crate::parse::SymbolType::AnyExcept(_) => todo!(),
+        _ => todo!(),
     }
 
     let val = parser.get_symbol_mut(&EXPR_SEQ);
     match &mut val.symbol_type {
-        crate::parse::SymbolType::Sequence(_) => todo!(),
         crate::parse::SymbolType::OneOf(a) => {
+            a.insert(0, AND);
+            a.insert(0, NOT);
             a.insert(0, MANY);
             a.insert(0, OPT);
             a.insert(0, ONE_OF_PAR);
         }
-        crate::parse::SymbolType::Optional(_) => todo!(),
-        crate::parse::SymbolType::OneOrMore(_) => todo!(),
-        crate::parse::SymbolType::ZeroOrMore(_) => todo!(),
-        crate::parse::SymbolType::Terminal(_) => todo!(),
-        crate::parse::SymbolType::AnyExcept(_) => todo!(),
+        _ => todo!(),
     }
 
     let val = parser.get_symbol_mut(&EXPR_ONE_OF);
     match &mut val.symbol_type {
-        crate::parse::SymbolType::Sequence(_) => todo!(),
         crate::parse::SymbolType::OneOf(a) => {
+            a.insert(0, AND);
+            a.insert(0, NOT);
             a.insert(0, MANY);
             a.insert(0, OPT);
             a.insert(0, SEQ_PAR);
         }
-        crate::parse::SymbolType::Optional(_) => todo!(),
-        crate::parse::SymbolType::OneOrMore(_) => todo!(),
-        crate::parse::SymbolType::ZeroOrMore(_) => todo!(),
-        crate::parse::SymbolType::Terminal(_) => todo!(),
-        crate::parse::SymbolType::AnyExcept(_) => todo!(),
+        _ => todo!(),
     }
 
     let val = parser.get_symbol_mut(&EXPR_OPT);
     match &mut val.symbol_type {
-        crate::parse::SymbolType::Sequence(_) => todo!(),
         crate::parse::SymbolType::OneOf(a) => {
+            a.insert(0, AND);
+            a.insert(0, NOT);
             a.insert(0, MANY);
             a.insert(0, ONE_OF);
             a.insert(0, SEQ);
         }
-        crate::parse::SymbolType::Optional(_) => todo!(),
-        crate::parse::SymbolType::OneOrMore(_) => todo!(),
-        crate::parse::SymbolType::ZeroOrMore(_) => todo!(),
-        crate::parse::SymbolType::Terminal(_) => todo!(),
-        crate::parse::SymbolType::AnyExcept(_) => todo!(),
+        _ => todo!(),
     }
 
     let val = parser.get_symbol_mut(&EXPR_MANY);
     match &mut val.symbol_type {
-        crate::parse::SymbolType::Sequence(_) => todo!(),
         crate::parse::SymbolType::OneOf(a) => {
+            a.insert(0, AND);
+            a.insert(0, NOT);
             a.insert(0, OPT);
             a.insert(0, ONE_OF);
             a.insert(0, SEQ);
         }
-        crate::parse::SymbolType::Optional(_) => todo!(),
-        crate::parse::SymbolType::OneOrMore(_) => todo!(),
-        crate::parse::SymbolType::ZeroOrMore(_) => todo!(),
-        crate::parse::SymbolType::Terminal(_) => todo!(),
-        crate::parse::SymbolType::AnyExcept(_) => todo!(),
+        _ => todo!(),
+    }
+
+    let val = parser.get_symbol_mut(&EXPR_LABELED);
+    match &mut val.symbol_type {
+        crate::parse::SymbolType::OneOf(a) => {
+            a.insert(0, MANY);
+            a.insert(0, OPT);
+            a.insert(0, ONE_OF_PAR);
+            a.insert(0, SEQ_PAR);
+        }
+        _ => todo!(),
     }
     parser.root_node(&ROOT);
     parser
@@ -1,15 +1,159 @@
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
 
+use crate::input::Position;
 use crate::parse::Node;
 use crate::parse::NodeData;
+use crate::parse::ParseError;
 use crate::parse::Parser;
 use crate::parse::SymbolIdent;
 use crate::parse::SymbolProperties;
+use crate::parse::SymbolType;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GrammarError {
+    /// The grammar is not valid BNF.
+    Syntax(ParseError),
+    /// The parser has no root symbol.
+    NoRoot,
+    /// The symbol cannot be written in the BNF dialect, see
+    /// [`Parser::to_bnf`].
+    Unrepresentable(SymbolIdent),
+    /// Building the grammar needed more than the given number of symbols,
+    /// see [`ParserBuilder::with_max_symbols`].
+    TooLarge(usize),
+    /// No rule of the given name exists.
+    UnknownRule(String),
+    /// The rules can reach themselves without consuming input, each the
+    /// first one of the next, see [`Parser::validate`].
+    LeftRecursion(Vec<String>),
+    /// The rule `rule` refers to `name`, which is not defined. `loc` is
+    /// where the reference starts in the grammar.
+    UndeclaredRule {
+        name: String,
+        rule: String,
+        loc: Position,
+    },
+    /// The expression at `loc` in the rule `rule` cannot be built, e.g. a
+    /// range ending before it starts.
+    InvalidExpression {
+        rule: String,
+        loc: Position,
+        message: String,
+    },
+    /// The syntax tree has a node of a type the builder does not know, so
+    /// it was not made by [`Parser::parse_bnf_to_ast`].
+    UnknownNode(String),
+    /// The rule `name` is defined twice. The locations are where the rule
+    /// names start in the grammar.
+    DuplicateRule {
+        name: String,
+        first_loc: Position,
+        second_loc: Position,
+    },
+}
+
+impl Display for GrammarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GrammarError::Syntax(err) => write!(f, "Invalid grammar: {}", err),
+            GrammarError::NoRoot => write!(f, "No root node defined"),
+            GrammarError::Unrepresentable(ident) => {
+                write!(f, "Symbol {} cannot be written as BNF", ident.0)
+            }
+            GrammarError::TooLarge(max) => {
+                write!(f, "Grammar needs more than {} symbols", max)
+            }
+            GrammarError::UnknownRule(name) => {
+                write!(f, "No rule named {}", name)
+            }
+            GrammarError::LeftRecursion(cycle) => {
+                write!(f, "Left recursion {}", cycle.join(" -> "))
+            }
+            GrammarError::UndeclaredRule { name, rule, loc } => write!(
+                f,
+                "Rule {} refers to undeclared rule {} at {}",
+                rule, name, loc
+            ),
+            GrammarError::InvalidExpression { rule, loc, message } => {
+                write!(f, "{} in rule {} at {}", message, rule, loc)
+            }
+            GrammarError::UnknownNode(node_type) => {
+                write!(f, "Unknown node type {}", node_type)
+            }
+            GrammarError::DuplicateRule {
+                name,
+                first_loc,
+                second_loc,
+            } => write!(
+                f,
+                "Rule {} defined at {} is defined again at {}",
+                name, first_loc, second_loc
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
 
 #[derive(Debug)]
 pub struct ParserBuilder<'a> {
     stmt_registry: HashMap<String, StmtInfo<'a>>,
     parser: Parser,
+    /// Whether the statement being built keeps parenthesized groups as
+    /// `GROUP` nodes.
+    group: bool,
+    /// Name of the grammar file, recorded in every rule's source location.
+    source: String,
+    /// Upper bound on the size of the symbol registry.
+    max_symbols: Option<usize>,
+    /// Whether terminals match regardless of ASCII case.
+    case_insensitive: bool,
+    /// Whether the terminals of the statement being built match regardless
+    /// of case, as its `ci` flag asks.
+    ci: bool,
+    /// Names of the rules being built, innermost last.
+    rules: Vec<String>,
+    /// For each rule being built, the symbol references to it resolve to
+    /// until its body is built, if the body has not taken it over yet.
+    placeholders: Vec<Option<SymbolIdent>>,
+    /// Labeled symbols and the symbols they label, see
+    /// [`ParserBuilder::rename_labeled`].
+    labels: Vec<(SymbolIdent, SymbolIdent)>,
+    /// Placeholders that only forward to the body of another rule, see
+    /// [`ParserBuilder::fill_placeholder`], and the names of their rules.
+    aliases: HashMap<SymbolIdent, String>,
+}
+
+/// A step of [`ParserBuilder::run`].
+enum Task<'n> {
+    /// Builds the expression `node`, named `name` if given.
+    Expr {
+        node: &'n Node,
+        name: Option<&'n str>,
+        raw: bool,
+        ignore: bool,
+    },
+    /// Adds the symbol built last to the sequence or alternative `symbol`,
+    /// then builds the remaining `children` one after another.
+    Append {
+        symbol: SymbolIdent,
+        children: &'n [Node],
+    },
+    /// Wraps the symbol built last as the expression `node` requires.
+    Wrap {
+        node: &'n Node,
+        name: Option<&'n str>,
+        raw: bool,
+        ignore: bool,
+    },
+    /// Finishes the rule `node` with the symbol built last as its body.
+    Statement {
+        node: &'n Node,
+        outer_group: bool,
+        outer_ci: bool,
+    },
 }
 
 #[derive(Debug)]
@@ -33,27 +177,183 @@ impl<'a> ParserBuilder<'a> {
         ParserBuilder {
             stmt_registry: HashMap::new(),
             parser: Parser::new(),
+            group: false,
+            source: "<bnf>".to_owned(),
+            max_symbols: None,
+            case_insensitive: false,
+            ci: false,
+            rules: Vec::new(),
+            placeholders: Vec::new(),
+            labels: Vec::new(),
+            aliases: HashMap::new(),
+        }
+    }
+
+    /// Builds every terminal and char range to match the ASCII letters it
+    /// holds in both cases, as [`char::eq_ignore_ascii_case`] compares them.
+    /// Other chars only match themselves. Raw nodes still hold the input as
+    /// it was written. The `ci` flag does
+    /// the same for a single rule.
+    pub fn case_insensitive(mut self) -> Self {
+        self.case_insensitive = true;
+        self
+    }
+
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = source.to_owned();
+        self
+    }
+
+    /// A builder adding to `parser`, whose named symbols can be referred
+    /// to by name.
+    pub(crate) fn extending(parser: Parser) -> Self {
+        let mut builder = ParserBuilder::new();
+        for (idx, symbol) in parser.symbol_registry.iter().enumerate() {
+            if let Some(name) = &symbol.properties.node_name {
+                builder
+                    .stmt_registry
+                    .entry(name.clone())
+                    .or_insert(StmtInfo::AlreadyBuild(parser.ident(idx)));
+            }
         }
+        builder.parser = parser;
+        builder
+    }
+
+    /// Replaces what the rule `name` matches with `expr`. Its properties
+    /// and every reference to it stay the same.
+    pub(crate) fn override_rule(
+        &mut self,
+        name: &str,
+        expr: &Node,
+    ) -> Result<(), GrammarError> {
+        let ident = match self.stmt_registry.get(name) {
+            Some(StmtInfo::AlreadyBuild(ident)) => *ident,
+            _ => return Err(GrammarError::UnknownRule(name.to_owned())),
+        };
+        let replacement = self.build_expr(expr, None, false, true)?;
+        let symbol_type =
+            self.parser.get_symbol(&replacement).symbol_type.clone();
+        self.parser.get_symbol_mut(&ident).symbol_type = symbol_type;
+        Ok(())
+    }
+
+    pub(crate) fn into_parser(self) -> Parser {
+        self.parser
+    }
+
+    /// Aborts [`ParserBuilder::build`] with [`GrammarError::TooLarge`] once
+    /// the grammar needs more than `max` symbols.
+    pub fn with_max_symbols(mut self, max: usize) -> Self {
+        self.max_symbols = Some(max);
+        self
     }
 
     fn build_statement(
         &mut self,
-        node: &Node,
+        node: &'a Node,
         raw: bool,
         ignore: bool,
-    ) -> SymbolIdent {
+    ) -> Result<SymbolIdent, GrammarError> {
+        let mut tasks = Vec::new();
+        self.start_statement(&mut tasks, node, raw, ignore);
+        self.run(tasks)
+    }
+
+    /// Queues the tasks building the rule `node`.
+    fn start_statement<'n>(
+        &mut self,
+        tasks: &mut Vec<Task<'n>>,
+        node: &'n Node,
+        raw: bool,
+        ignore: bool,
+    ) {
         assert_eq!(node.node_type, "STATEMENT");
-        let identifier = get_stmt_identifier(node);
-        self.build_expr(get_stmt_expr(node), Some(identifier), raw, ignore)
+        let name = get_stmt_identifier(node);
+        self.rules.push(name.to_owned());
+        // Registered up front, so that rules referring back to this one
+        // while its body is built do not start building it again.
+        let placeholder = self.parser.sequence(vec![], None);
+        self.stmt_registry
+            .insert(name.to_owned(), StmtInfo::AlreadyBuild(placeholder));
+        self.placeholders.push(Some(placeholder));
+        let group = get_stmt_info(node).contains(&"group");
+        let outer_group = std::mem::replace(&mut self.group, group);
+        let ci = get_stmt_info(node).contains(&"ci");
+        let outer_ci = std::mem::replace(&mut self.ci, ci);
+        tasks.push(Task::Statement {
+            node,
+            outer_group,
+            outer_ci,
+        });
+        tasks.push(Task::Expr {
+            node: get_stmt_expr(node),
+            name: Some(get_stmt_identifier(node)),
+            raw,
+            ignore,
+        });
     }
 
-    pub fn build(mut self, root_node: &'a Node) -> Parser {
+    /// Applies the flags of the rule `node` to `body`, built from its
+    /// expression, and returns the symbol of the rule.
+    fn finish_statement(
+        &mut self,
+        node: &Node,
+        body: &SymbolIdent,
+        outer_group: bool,
+        outer_ci: bool,
+    ) -> SymbolIdent {
+        self.group = outer_group;
+        self.ci = outer_ci;
+        self.rules.pop();
+        let symbol = match self.placeholders.pop().flatten() {
+            Some(placeholder) => self.fill_placeholder(node, placeholder, body),
+            None => *body,
+        };
+        let symbol = &symbol;
+
+        let line = get_stmt_children(node)[0].loc.0.line();
+        let properties = &mut self.parser.get_symbol_mut(symbol).properties;
+        if properties.source_location.is_none() {
+            properties.source_location = Some((self.source.clone(), line));
+        }
+        if get_stmt_info(node).contains(&"memo") {
+            properties.memoize = true;
+        }
+        if get_stmt_info(node).contains(&"list") {
+            properties.is_list = true;
+        }
+        if get_stmt_info(node).contains(&"tight") {
+            properties.tight = true;
+        }
+        if let Some(message) = get_stmt_attribute(node, "error") {
+            properties.error_message = Some(message.to_owned());
+        }
+        if let Some(doc) = get_stmt_doc(node) {
+            properties.doc = Some(doc);
+        }
+        *symbol
+    }
+
+    pub fn build(
+        mut self,
+        root_node: &'a Node,
+    ) -> Result<Parser, GrammarError> {
         assert_eq!(root_node.node_type, "ROOT");
         let stmts = get_children_of_node(root_node);
         let mut root_node: Option<StmtInfo> = None;
         for stmt in stmts {
             let identifier = get_stmt_identifier(stmt);
             let stmt_info = get_stmt_info(stmt);
+            if let Some(StmtInfo::NotBuiltYet { node, .. }) =
+                self.stmt_registry.get(identifier)
+            {
+                return Err(GrammarError::DuplicateRule {
+                    name: identifier.to_owned(),
+                    first_loc: get_stmt_children(node)[0].loc.0,
+                    second_loc: get_children_of_node(stmt)[0].loc.0,
+                });
+            }
             if stmt_info.contains(&"root") {
                 root_node = Some(StmtInfo::NotBuiltYet {
                     node: stmt,
@@ -73,13 +373,16 @@ impl<'a> ParserBuilder<'a> {
         match root_node {
             Some(root_node) => {
                 if let StmtInfo::NotBuiltYet { node, raw, ignore } = root_node {
-                    let root_ident = self.build_statement(node, raw, ignore);
+                    let root_ident = self.build_statement(node, raw, ignore)?;
                     self.parser.root_node(&root_ident);
                 }
             }
-            None => panic!("No Root Node given"),
+            None => return Err(GrammarError::NoRoot),
         }
+        self.rename_labeled();
         self.parser
+            .validate_named(|id| self.aliases.get(id).cloned())?;
+        Ok(self.parser)
     }
 
     fn build_expr(
@@ -88,171 +391,595 @@ impl<'a> ParserBuilder<'a> {
         name: Option<&str>,
         raw: bool,
         ignore: bool,
-    ) -> SymbolIdent {
-        match node.node_type.as_str() {
-            "SEQUENCE" => self.build_sequence(node, name, raw, ignore),
-            "TERMINAL" => self.build_terminal(node, name, raw, ignore),
-            "IDENTIFIER" => self.build_identifier(get_raw_value_of_node(node)),
-            "ONE_OF" => self.build_one_of(node, name, raw, ignore),
-            "OPTIONAL" => self.build_optional(node, name, raw, ignore),
-            "MANY" => self.build_many(node, name, raw, ignore),
-            "ANY" => self.build_any(node, name, raw, ignore),
+    ) -> Result<SymbolIdent, GrammarError> {
+        self.run(vec![Task::Expr {
+            node,
+            name,
+            raw,
+            ignore,
+        }])
+    }
+
+    /// Works off `tasks`, last one first, and returns the symbol the bottom
+    /// one built. The explicit stack keeps deeply nested expressions and long
+    /// chains of rules from overflowing the call stack.
+    fn run<'n>(
+        &mut self,
+        mut tasks: Vec<Task<'n>>,
+    ) -> Result<SymbolIdent, GrammarError>
+    where
+        'a: 'n,
+    {
+        // The symbol built last, for the task below to pick up.
+        let mut built = None;
+        while let Some(task) = tasks.pop() {
+            let symbol = match task {
+                Task::Expr {
+                    node,
+                    name,
+                    raw,
+                    ignore,
+                } => match self
+                    .start_expr(&mut tasks, node, name, raw, ignore)?
+                {
+                    Some(symbol) => symbol,
+                    None => continue,
+                },
+                Task::Append { symbol, children } => {
+                    if let Some(child) = built.take() {
+                        self.parser.add_ident(&symbol, &child);
+                    }
+                    match children.split_first() {
+                        Some((child, rest)) => {
+                            tasks.push(Task::Append {
+                                symbol,
+                                children: rest,
+                            });
+                            tasks.push(Task::Expr {
+                                node: child,
+                                name: None,
+                                raw: false,
+                                ignore: true,
+                            });
+                            continue;
+                        }
+                        None => symbol,
+                    }
+                }
+                Task::Wrap {
+                    node,
+                    name,
+                    raw,
+                    ignore,
+                } => {
+                    let child = built.take().expect("Wrapped symbol is built");
+                    match node.node_type.as_str() {
+                        "MANY" => {
+                            self.build_many(node, &child, name, raw, ignore)?
+                        }
+                        "OPTIONAL" => {
+                            self.build_optional(&child, name, raw, ignore)
+                        }
+                        "GROUP" => self.build_group(&child),
+                        "AND" => self.build_and(&child, name, raw, ignore),
+                        "NOT" => self.build_not(&child, name, raw, ignore),
+                        "LABELED" => {
+                            self.build_labeled(node, &child, name, raw, ignore)
+                        }
+                        a => panic!("Cannot wrap Node Type {}", a),
+                    }
+                }
+                Task::Statement {
+                    node,
+                    outer_group,
+                    outer_ci,
+                } => {
+                    let body = built.take().expect("Rule body is built");
+                    self.finish_statement(node, &body, outer_group, outer_ci)
+                }
+            };
+            built = match self.max_symbols {
+                Some(max) if self.parser.symbol_count() > max => {
+                    return Err(GrammarError::TooLarge(max))
+                }
+                _ => Some(symbol),
+            };
+        }
+        Ok(built.expect("A symbol is built"))
+    }
+
+    /// The symbol of `node` if it can be built right away, otherwise queues
+    /// the tasks building it.
+    fn start_expr<'n>(
+        &mut self,
+        tasks: &mut Vec<Task<'n>>,
+        node: &'n Node,
+        name: Option<&'n str>,
+        raw: bool,
+        ignore: bool,
+    ) -> Result<Option<SymbolIdent>, GrammarError>
+    where
+        'a: 'n,
+    {
+        let symbol = match node.node_type.as_str() {
+            "SEQUENCE" | "ONE_OF" => {
+                let symbol = if node.node_type == "SEQUENCE" {
+                    self.build_sequence(name, raw, ignore)
+                } else {
+                    self.build_one_of(name, raw, ignore)
+                };
+                tasks.push(Task::Append {
+                    symbol,
+                    children: get_children_of_node(node),
+                });
+                return Ok(None);
+            }
+            "OPTIONAL" | "MANY" | "GROUP" | "LABELED" | "AND" | "NOT" => {
+                let children = get_children_of_node(node);
+                // A label comes before the expression, bounds after it.
+                let labeled = node.node_type == "LABELED";
+                let child = &children[usize::from(labeled)];
+                tasks.push(Task::Wrap {
+                    node,
+                    name,
+                    raw,
+                    ignore,
+                });
+                // A group is the expression inside, so that gets the name.
+                tasks.push(if node.node_type == "GROUP" {
+                    Task::Expr {
+                        node: child,
+                        name,
+                        raw,
+                        ignore,
+                    }
+                } else {
+                    Task::Expr {
+                        node: child,
+                        name: None,
+                        raw: false,
+                        ignore: true,
+                    }
+                });
+                return Ok(None);
+            }
+            "IDENTIFIER" => {
+                let identifier = get_raw_value_of_node(node);
+                let stmt_info = match self.stmt_registry.get(identifier) {
+                    Some(stmt_info) => stmt_info,
+                    None => {
+                        return Err(GrammarError::UndeclaredRule {
+                            name: identifier.to_owned(),
+                            rule: self.rule_name(),
+                            loc: node.loc.0,
+                        })
+                    }
+                };
+                match *stmt_info {
+                    StmtInfo::NotBuiltYet { node, raw, ignore } => {
+                        self.start_statement(tasks, node, raw, ignore);
+                        return Ok(None);
+                    }
+                    StmtInfo::AlreadyBuild(identifier) => identifier,
+                }
+            }
+            "TERMINAL" => self.build_terminal(node, name, raw, ignore)?,
+            "CODEPOINT" => self.build_codepoint(node, name, raw, ignore)?,
+            "STRING_TERMINAL" => self.build_string_terminal(node, name)?,
+            "RANGE" => self.build_range(node, name, raw, ignore)?,
+            "ANY" => self.build_any(node, name, raw, ignore)?,
+            "EOL_TEXT" => self.build_eol_text(node, name, raw, ignore),
+            "TAKE" => self.build_take(node, name, raw, ignore)?,
             "WHITESPACE_ID" => {
                 let possible_chars = self.parser.one_of_chars(
                     " \n\r\t",
                     SymbolProperties::new("WHITESPACE_CHAR", false, true),
                 );
-                self.parser.zero_or_more(
-                    &possible_chars,
-                    SymbolProperties::new("WHITESPACE", false, true),
-                )
+                let props = SymbolProperties::new("WHITESPACE", false, true);
+                // `$WHITESPACE+` requires at least one whitespace char.
+                if get_raw_value_of_node(node).ends_with('+') {
+                    self.parser.one_or_more(&possible_chars, props)
+                } else {
+                    self.parser.zero_or_more(&possible_chars, props)
+                }
             }
-            a => panic!("Unknown Node Type {}", a),
-        }
+            a => return Err(GrammarError::UnknownNode(a.to_owned())),
+        };
+        Ok(Some(symbol))
     }
 
-    fn build_identifier(&mut self, identifier: &str) -> SymbolIdent {
-        let stmt_info =
-            self.stmt_registry.get(identifier).unwrap_or_else(|| {
-                panic!("Usage of undeclared identifier {}", identifier)
-            });
-        match *stmt_info {
-            StmtInfo::NotBuiltYet { node, raw, ignore } => {
-                self.build_statement(node, raw, ignore)
-            }
-            StmtInfo::AlreadyBuild(identifier) => identifier,
+    /// The name of the rule being built.
+    fn rule_name(&self) -> String {
+        self.rules.last().cloned().unwrap_or_default()
+    }
+
+    /// Fails with [`GrammarError::InvalidExpression`] at `node`.
+    fn invalid(&self, node: &Node, message: String) -> GrammarError {
+        GrammarError::InvalidExpression {
+            rule: self.rule_name(),
+            loc: node.loc.0,
+            message,
         }
     }
 
+    /// The char of a `TERMINAL` or `CODEPOINT` node.
+    fn char_of(&self, node: &Node) -> Result<char, GrammarError> {
+        get_char_of_node(node).ok_or_else(|| {
+            let raw = get_raw_value_of_node(node);
+            self.invalid(node, format!("Invalid codepoint {}", raw))
+        })
+    }
+
     fn build_terminal(
         &mut self,
         node: &Node,
         name: Option<&str>,
         _raw: bool,
         _ignore: bool,
-    ) -> SymbolIdent {
+    ) -> Result<SymbolIdent, GrammarError> {
         assert_eq!(node.node_type, "TERMINAL");
-        let char = get_raw_value_of_node(node);
+        Ok(self.terminal(self.char_of(node)?, name))
+    }
+
+    fn build_codepoint(
+        &mut self,
+        node: &Node,
+        name: Option<&str>,
+        _raw: bool,
+        _ignore: bool,
+    ) -> Result<SymbolIdent, GrammarError> {
+        assert_eq!(node.node_type, "CODEPOINT");
+        Ok(self.terminal(self.char_of(node)?, name))
+    }
+
+    /// `"text"`, lowered to a sequence of its chars.
+    fn build_string_terminal(
+        &mut self,
+        node: &Node,
+        name: Option<&str>,
+    ) -> Result<SymbolIdent, GrammarError> {
+        assert_eq!(node.node_type, "STRING_TERMINAL");
+        let quoted = get_raw_value_of_node(node);
+        let text = &quoted[1..quoted.len() - 1];
+        if text.is_empty() {
+            return Err(self.invalid(node, "Empty string".to_owned()));
+        }
+        let props =
+            name.and_then(|name| SymbolProperties::new(name, true, false));
+        if !self.ignores_case() {
+            return Ok(self.parser.sequence_chars(text, props));
+        }
+        let chars: Vec<SymbolIdent> =
+            text.chars().map(|c| self.terminal(c, None)).collect();
+        Ok(self.parser.sequence(chars.iter().collect(), props))
+    }
+
+    /// `'a'..'z'`, lowered to a char set, see [`Parser::range`].
+    fn build_range(
+        &mut self,
+        node: &Node,
+        name: Option<&str>,
+        _raw: bool,
+        _ignore: bool,
+    ) -> Result<SymbolIdent, GrammarError> {
+        let (from, to) = self.range_of(node)?;
+        let mut inside = vec![(from, to)];
+        if self.ignores_case() {
+            // The ASCII letters inside the range, in the other case.
+            for (first, last) in [('a', 'z'), ('A', 'Z')] {
+                let (start, end) = (from.max(first), to.min(last));
+                if start <= end {
+                    let swap = |c: char| (c as u8 ^ 0x20) as char;
+                    inside.push((swap(start), swap(end)));
+                }
+            }
+        }
+        let props =
+            name.and_then(|name| SymbolProperties::new(name, true, false));
+        Ok(self.parser.one_of_ranges(&inside, props))
+    }
+
+    /// Whether terminals of the statement being built match regardless of
+    /// case, see [`ParserBuilder::case_insensitive`].
+    fn ignores_case(&self) -> bool {
+        self.case_insensitive || self.ci
+    }
+
+    /// The bounds of a `RANGE` node, or both the char of a `TERMINAL` or
+    /// `CODEPOINT` node.
+    fn range_of(&self, node: &Node) -> Result<(char, char), GrammarError> {
+        if node.node_type != "RANGE" {
+            let c = self.char_of(node)?;
+            return Ok((c, c));
+        }
+        let children = get_children_of_node(node);
+        let (from, to) =
+            (self.char_of(&children[0])?, self.char_of(&children[1])?);
+        if from > to {
+            return Err(self
+                .invalid(node, format!("Invalid range {:?}..{:?}", from, to)));
+        }
+        Ok((from, to))
+    }
+
+    fn terminal(&mut self, char: char, name: Option<&str>) -> SymbolIdent {
+        if self.ignores_case() {
+            let cases = cases(char);
+            if cases.len() > 1 {
+                let props = name
+                    .and_then(|name| SymbolProperties::new(name, true, false));
+                return self.parser.one_of_chars(&cases, props);
+            }
+        }
         match name {
-            Some(name) => self.parser.terminal(
-                char.chars().nth(1).unwrap(),
-                SymbolProperties::new(name, true, false),
-            ),
-            None => self.parser.terminal(char.chars().nth(1).unwrap(), None),
+            Some(name) => self
+                .parser
+                .terminal(char, SymbolProperties::new(name, true, false)),
+            None => self.parser.terminal(char, None),
         }
     }
 
+    /// `*`, any char, or `*('a', ...)`, any char but the listed ones.
     fn build_any(
         &mut self,
         node: &Node,
         name: Option<&str>,
         raw: bool,
         ignore: bool,
-    ) -> SymbolIdent {
+    ) -> Result<SymbolIdent, GrammarError> {
         assert_eq!(node.node_type, "ANY");
-        match name {
-            Some(name) => self.parser.any_except(
-                &['"', '\\'],
-                SymbolProperties::new(name, raw, ignore),
-            ),
-            None => self.parser.any_except(&['"', '\\'], None),
-        }
+        let excluded = get_children_of_node(node)
+            .iter()
+            .map(|child| self.range_of(child))
+            .collect::<Result<Vec<_>, _>>()?;
+        let props =
+            name.and_then(|name| SymbolProperties::new(name, raw, ignore));
+        Ok(self.parser.any_except_ranges(&excluded, props))
     }
-    fn build_many(
+    fn build_eol_text(
         &mut self,
         node: &Node,
         name: Option<&str>,
         raw: bool,
         ignore: bool,
     ) -> SymbolIdent {
-        assert_eq!(node.node_type, "MANY");
-        let children = get_children_of_node(node);
-        assert_eq!(children.len(), 1);
-        let child = &children[0];
-
-        let id = self.build_expr(child, None, false, true);
+        assert_eq!(node.node_type, "EOL_TEXT");
         match name {
             Some(name) => self
                 .parser
-                .zero_or_more(&id, SymbolProperties::new(name, raw, ignore)),
-            None => self.parser.zero_or_more(&id, None),
+                .rest_of_line(SymbolProperties::new(name, raw, ignore)),
+            None => self.parser.rest_of_line(None),
         }
     }
-    fn build_optional(
+    /// `.{n}`, any `n` chars.
+    fn build_take(
         &mut self,
         node: &Node,
         name: Option<&str>,
         raw: bool,
         ignore: bool,
+    ) -> Result<SymbolIdent, GrammarError> {
+        assert_eq!(node.node_type, "TAKE");
+        let count = get_raw_value_of_node(&get_children_of_node(node)[0]);
+        let n = count.parse().map_err(|_| {
+            self.invalid(node, format!("Invalid count {}", count))
+        })?;
+        Ok(match name {
+            Some(name) => self
+                .parser
+                .take(n, SymbolProperties::new(name, raw, ignore)),
+            None => self.parser.take(n, None),
+        })
+    }
+    /// `{ X }`, or `{ X }<min,max>` with optional bounds.
+    fn build_many(
+        &mut self,
+        node: &Node,
+        id: &SymbolIdent,
+        name: Option<&str>,
+        raw: bool,
+        ignore: bool,
+    ) -> Result<SymbolIdent, GrammarError> {
+        let props =
+            name.and_then(|name| SymbolProperties::new(name, raw, ignore));
+        let bounds = match get_children_of_node(node).get(1) {
+            Some(bounds) => bounds,
+            None => return Ok(self.parser.zero_or_more(id, props)),
+        };
+        let raw_bounds = get_raw_value_of_node(bounds);
+        let inner = &raw_bounds[1..raw_bounds.len() - 1];
+        let (min, max) = match inner.split_once(',') {
+            None => (inner, Some(inner)),
+            Some((min, "")) => (min, None),
+            Some((min, max)) => (min, Some(max)),
+        };
+        let count = |count: &str| {
+            count.parse::<usize>().map_err(|_| {
+                self.invalid(bounds, format!("Invalid count {}", count))
+            })
+        };
+        let min = count(min)?;
+        let max = max.map(count).transpose()?;
+        if max.is_some_and(|max| max < min) {
+            return Err(
+                self.invalid(bounds, format!("Invalid bounds {}", raw_bounds))
+            );
+        }
+        Ok(self.parser.repeat(id, min, max, props))
+    }
+    fn build_optional(
+        &mut self,
+        id: &SymbolIdent,
+        name: Option<&str>,
+        raw: bool,
+        ignore: bool,
     ) -> SymbolIdent {
-        assert_eq!(node.node_type, "OPTIONAL");
-        let children = get_children_of_node(node);
-        assert_eq!(children.len(), 1);
-        let child = &children[0];
-
-        let id = self.build_expr(child, None, false, true);
         match name {
             Some(name) => self
                 .parser
-                .optional(&id, SymbolProperties::new(name, raw, ignore)),
-            None => self.parser.optional(&id, None),
+                .optional(id, SymbolProperties::new(name, raw, ignore)),
+            None => self.parser.optional(id, None),
         }
     }
 
-    fn build_one_of(
+    /// `&X` requires `X` without consuming it.
+    fn build_and(
+        &mut self,
+        id: &SymbolIdent,
+        name: Option<&str>,
+        raw: bool,
+        ignore: bool,
+    ) -> SymbolIdent {
+        let props =
+            name.and_then(|name| SymbolProperties::new(name, raw, ignore));
+        self.parser.and(id, props)
+    }
+
+    /// `!X` rules out `X` without consuming anything.
+    fn build_not(
+        &mut self,
+        id: &SymbolIdent,
+        name: Option<&str>,
+        raw: bool,
+        ignore: bool,
+    ) -> SymbolIdent {
+        let props =
+            name.and_then(|name| SymbolProperties::new(name, raw, ignore));
+        self.parser.not(id, props)
+    }
+
+    fn build_group(&mut self, id: &SymbolIdent) -> SymbolIdent {
+        if self.group {
+            self.parser.sequence(
+                vec![id],
+                SymbolProperties::new("GROUP", false, false),
+            )
+        } else {
+            *id
+        }
+    }
+
+    /// A `label:RULE` is `RULE` under the name `label`, see
+    /// [`ParserBuilder::rename_labeled`]. Any other `label:expr` captures the
+    /// text matched by `expr` in a raw node of type `label`.
+    fn build_labeled(
         &mut self,
         node: &Node,
+        id: &SymbolIdent,
         name: Option<&str>,
         raw: bool,
         ignore: bool,
     ) -> SymbolIdent {
-        assert_eq!(node.node_type, "ONE_OF");
-        let children = get_children_of_node(node);
-        let symbol = match name {
-            Some(name) => {
-                let seq_id = self
-                    .parser
-                    .one_of(vec![], SymbolProperties::new(name, raw, ignore));
-                self.stmt_registry
-                    .insert(name.to_owned(), StmtInfo::AlreadyBuild(seq_id));
-                seq_id
+        assert_eq!(node.node_type, "LABELED");
+        let label = get_raw_value_of_node(&get_children_of_node(node)[0]);
+
+        let labeled = self
+            .parser
+            .sequence(vec![id], SymbolProperties::new(label, true, false));
+        self.labels.push((labeled, *id));
+        match name {
+            Some(name) => self.parser.sequence(
+                vec![&labeled],
+                SymbolProperties::new(name, raw, ignore),
+            ),
+            None => labeled,
+        }
+    }
+
+    /// Turns each labeled rule into a copy of the rule, named by the label
+    /// but otherwise the same, raw flag included. Rules referenced before
+    /// their body is built are placeholders, so this waits until all are
+    /// built.
+    fn rename_labeled(&mut self) {
+        for (labeled, id) in std::mem::take(&mut self.labels) {
+            let rule = self.parser.get_symbol(&id).clone();
+            if rule.properties.node_name.is_none() || rule.properties.ignore {
+                continue;
             }
-            None => self.parser.one_of(vec![], None),
-        };
-        for child in children {
-            let id = self.build_expr(child, None, false, true);
-            self.parser.add_ident(&symbol, &id)
-            // Build the expression.
+            let symbol = self.parser.get_symbol_mut(&labeled);
+            symbol.properties = SymbolProperties {
+                node_name: symbol.properties.node_name.take(),
+                ..rule.properties
+            };
+            symbol.symbol_type = rule.symbol_type;
         }
-        symbol
     }
-    fn build_sequence(
+
+    /// Makes `placeholder` the symbol of the rule `node`, built as `body`.
+    /// A newly built body is always the last symbol and takes its place, so
+    /// no extra symbol remains. For `A = B;` the rule is `B` and the
+    /// placeholder only forwards to it.
+    fn fill_placeholder(
         &mut self,
         node: &Node,
+        placeholder: SymbolIdent,
+        body: &SymbolIdent,
+    ) -> SymbolIdent {
+        if get_stmt_expr(node).node_type != "IDENTIFIER" {
+            assert_eq!(body.0 + 1, self.parser.symbol_count());
+            let symbol = self.parser.symbol_registry.pop().unwrap();
+            *self.parser.get_symbol_mut(&placeholder) = symbol;
+            return placeholder;
+        }
+        self.parser.get_symbol_mut(&placeholder).symbol_type =
+            SymbolType::Sequence(vec![*body]);
+        let name = get_stmt_identifier(node);
+        self.aliases.insert(placeholder, name.to_owned());
+        self.stmt_registry
+            .insert(name.to_owned(), StmtInfo::AlreadyBuild(*body));
+        *body
+    }
+
+    /// An alternative still without alternatives. As the body of a rule it
+    /// takes over the rule's placeholder, so the alternatives can refer to
+    /// it.
+    fn build_one_of(
+        &mut self,
         name: Option<&str>,
         raw: bool,
         ignore: bool,
     ) -> SymbolIdent {
-        assert_eq!(node.node_type, "SEQUENCE");
-        let children = get_children_of_node(node);
-        let symbol = match name {
-            Some(name) => {
-                let seq_id = self
-                    .parser
-                    .sequence(vec![], SymbolProperties::new(name, raw, ignore));
-                self.stmt_registry
-                    .insert(name.to_owned(), StmtInfo::AlreadyBuild(seq_id));
-                seq_id
-            }
-            None => self.parser.sequence(vec![], None),
+        let ident = self.build_sequence(name, raw, ignore);
+        self.parser.get_symbol_mut(&ident).symbol_type =
+            SymbolType::OneOf(vec![]);
+        ident
+    }
+
+    /// A sequence still without elements, see
+    /// [`ParserBuilder::build_one_of`].
+    fn build_sequence(
+        &mut self,
+        name: Option<&str>,
+        raw: bool,
+        ignore: bool,
+    ) -> SymbolIdent {
+        let props =
+            name.and_then(|name| SymbolProperties::new(name, raw, ignore));
+        let placeholder = match props {
+            Some(_) => self.placeholders.last_mut().and_then(Option::take),
+            None => None,
         };
-        for child in children {
-            let id = self.build_expr(child, None, false, true);
-            self.parser.add_ident(&symbol, &id)
-            // Build the expression.
+        match placeholder {
+            Some(placeholder) => {
+                let symbol = self.parser.get_symbol_mut(&placeholder);
+                symbol.symbol_type = SymbolType::Sequence(vec![]);
+                symbol.properties = props.unwrap_or_default();
+                placeholder
+            }
+            None => self.parser.sequence(vec![], props),
+        }
+    }
+}
+
+/// `c` and, for an ASCII letter, its other case.
+fn cases(c: char) -> String {
+    let mut cases = String::from(c);
+    for other in [c.to_ascii_lowercase(), c.to_ascii_uppercase()] {
+        if !cases.contains(other) {
+            cases.push(other);
         }
-        symbol
     }
+    cases
 }
 
 pub fn get_children_of_node(node: &Node) -> &[Node] {
@@ -269,38 +996,767 @@ pub fn get_raw_value_of_node(node: &Node) -> &str {
     }
 }
 
-fn get_stmt_identifier(node: &Node) -> &str {
+/// The char of a `TERMINAL` or `CODEPOINT` node, `None` for codepoints that
+/// are no char.
+fn get_char_of_node(node: &Node) -> Option<char> {
+    let raw = get_raw_value_of_node(node);
+    match node.node_type.as_str() {
+        "TERMINAL" => match &raw[1..raw.len() - 1] {
+            "\\n" => Some('\n'),
+            "\\t" => Some('\t'),
+            "\\r" => Some('\r'),
+            "\\\\" => Some('\\'),
+            "\\'" => Some('\''),
+            // `\u{XXXX}`
+            escape if escape.starts_with("\\u{") => {
+                u32::from_str_radix(&escape[3..escape.len() - 1], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+            }
+            c => c.chars().next(),
+        },
+        // Either `U+XXXX` or `\xXX`, both with a two char prefix.
+        "CODEPOINT" => u32::from_str_radix(&raw[2..], 16)
+            .ok()
+            .and_then(char::from_u32),
+        other => panic!("{} is not a char", other),
+    }
+}
+
+/// The children of a statement after its doc comment.
+fn get_stmt_children(node: &Node) -> &[Node] {
     assert_eq!(node.node_type, "STATEMENT");
     let children = get_children_of_node(node);
-    get_raw_value_of_node(&children[0])
+    match children.first() {
+        Some(doc) if doc.node_type == "DOC" => &children[1..],
+        _ => children,
+    }
 }
 
-fn get_stmt_info(node: &Node) -> Vec<&str> {
+fn get_stmt_identifier(node: &Node) -> &str {
+    get_raw_value_of_node(&get_stmt_children(node)[0])
+}
+
+/// The comment lines above a statement, trimmed and joined by newlines.
+fn get_stmt_doc(node: &Node) -> Option<String> {
     assert_eq!(node.node_type, "STATEMENT");
-    let info_node = match &node.data {
-        NodeData::Children(c) => {
-            if c.len() == 2 {
-                return Vec::new();
-            }
-            &c[1]
-        }
-        NodeData::Raw(_) => panic!("Statement node is raw"),
-    };
+    let doc = get_children_of_node(node)
+        .first()
+        .filter(|doc| doc.node_type == "DOC")?;
+    let lines: Vec<&str> = get_children_of_node(doc)
+        .iter()
+        .map(|comment| get_raw_value_of_node(comment).trim())
+        .collect();
+    Some(lines.join("\n"))
+}
+
+fn get_stmt_info_items(node: &Node) -> &[Node] {
+    let children = get_stmt_children(node);
+    if children.len() == 2 {
+        return &[];
+    }
+    let info_node = &children[1];
     assert_eq!(info_node.node_type, "STMT_INFO");
-    let children = get_children_of_node(info_node);
-    children.iter().map(get_raw_value_of_node).collect()
+    get_children_of_node(info_node)
 }
 
-fn get_stmt_expr(node: &Node) -> &Node {
-    assert_eq!(node.node_type, "STATEMENT");
-    match &node.data {
-        NodeData::Children(c) => {
-            if c.len() == 2 {
-                &c[1]
-            } else {
-                &c[2]
+fn get_stmt_info(node: &Node) -> Vec<&str> {
+    get_stmt_info_items(node)
+        .iter()
+        .filter(|item| item.node_type == "FLAG")
+        .map(get_raw_value_of_node)
+        .collect()
+}
+
+/// The value of a `key="value"` attribute of a statement.
+fn get_stmt_attribute<'n>(node: &'n Node, key: &str) -> Option<&'n str> {
+    get_stmt_info_items(node)
+        .iter()
+        .filter(|item| item.node_type == "ATTRIBUTE")
+        .map(get_children_of_node)
+        .find(|attr| get_raw_value_of_node(&attr[0]) == key)
+        .map(|attr| {
+            let quoted = get_raw_value_of_node(&attr[1]);
+            &quoted[1..quoted.len() - 1]
+        })
+}
+
+pub(crate) fn get_stmt_expr(node: &Node) -> &Node {
+    let children = get_stmt_children(node);
+    if children.len() == 2 {
+        &children[1]
+    } else {
+        &children[2]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::CharStream;
+    use crate::parse::Node;
+    use crate::parse::NodeData;
+    use crate::parse::ParseResult;
+    use crate::parse::Parser;
+
+    fn accepts(parser: &Parser, input: &str) -> bool {
+        matches!(
+            parser.parse(&mut CharStream::from(input)),
+            ParseResult::Ok(_)
+        )
+    }
+
+    fn parse(parser: &Parser, input: &str) -> Node {
+        match parser.parse(&mut CharStream::from(input)) {
+            ParseResult::Ok(node) => node,
+            ParseResult::Err(err) => panic!("{}", err),
+        }
+    }
+
+    #[test]
+    fn bnf_ast() {
+        let ast = Parser::parse_bnf_to_ast("A(root) = 'a' B;\nB(raw) = 'b';")
+            .unwrap();
+
+        assert_eq!("ROOT", ast.node_type);
+        let statements = match &ast.data {
+            NodeData::Children(c) => c,
+            NodeData::Raw(_) => panic!("ROOT is raw"),
+        };
+        assert_eq!(2, statements.len());
+        assert!(statements.iter().all(|s| s.node_type == "STATEMENT"));
+        assert_eq!(
+            "IDENTIFIER",
+            get_children_of_node(&statements[1])[0].node_type
+        );
+        assert_eq!("B", get_stmt_identifier(&statements[1]));
+        assert_eq!(vec!["raw"], get_stmt_info(&statements[1]));
+        assert_eq!("SEQUENCE", get_stmt_expr(&statements[0]).node_type);
+
+        assert!(matches!(
+            Parser::parse_bnf_to_ast("A(root) = 'a'"),
+            Err(GrammarError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn codepoint_terminal() {
+        let parser =
+            Parser::from_bnf("ROOT(root) = 'a' U+0009 \\x62;").unwrap();
+
+        assert!(accepts(&parser, "a\tb"));
+        assert!(!accepts(&parser, "a b"));
+    }
+
+    const ARITH_BNF: &str = r#"
+EXPR(root) = TERM { OP TERM };
+OP(raw) = '+' | '*';
+NUMBER(raw) = DIGIT { DIGIT };
+DIGIT(ignore) = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9';
+"#;
+
+    fn node_types(node: &Node) -> String {
+        match &node.data {
+            NodeData::Children(c) => format!(
+                "{}[{}]",
+                node.node_type,
+                c.iter().map(node_types).collect::<Vec<_>>().join(" ")
+            ),
+            NodeData::Raw(_) => node.node_type.clone(),
+        }
+    }
+
+    #[test]
+    fn group_flag() {
+        let grouped = Parser::from_bnf(&format!(
+            "{}TERM(group) = NUMBER | ( '(' EXPR ')' );",
+            ARITH_BNF
+        ))
+        .unwrap();
+        let flat = Parser::from_bnf(&format!(
+            "{}TERM = NUMBER | ( '(' EXPR ')' );",
+            ARITH_BNF
+        ))
+        .unwrap();
+
+        let grouped = parse(&grouped, "(1+2)*3");
+        let flat = parse(&flat, "(1+2)*3");
+
+        assert_eq!(
+            "EXPR[TERM[GROUP[EXPR[TERM[NUMBER] OP TERM[NUMBER]]]] OP TERM[NUMBER]]",
+            node_types(&grouped)
+        );
+        assert_eq!(
+            "EXPR[TERM[EXPR[TERM[NUMBER] OP TERM[NUMBER]]] OP TERM[NUMBER]]",
+            node_types(&flat)
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let parser = Parser::from_bnf(
+            r#"
+DATE(root) = year:(DIGIT DIGIT DIGIT DIGIT) '-' month:(DIGIT DIGIT) '-' day:(DIGIT DIGIT);
+DIGIT(ignore) = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9';
+"#,
+        ).unwrap();
+
+        let date = parse(&parser, "2024-02-29");
+
+        let year = date.field("year").unwrap();
+        assert_eq!(NodeData::Raw("2024".to_owned()), year.data);
+        assert_eq!(
+            NodeData::Raw("29".to_owned()),
+            date.field("day").unwrap().data
+        );
+        assert!(date.field("hour").is_none());
+    }
+
+    #[test]
+    fn labeled_rules() {
+        let parser = Parser::from_bnf(
+            r#"
+SUM(root) = lhs:NUMBER '+' rhs:PRODUCT;
+PRODUCT = NUMBER { '*' NUMBER };
+NUMBER(raw) = DIGIT { DIGIT };
+DIGIT(ignore) = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9';
+"#,
+        )
+        .unwrap();
+
+        let sum = parse(&parser, "1+23*4");
+
+        let lhs = sum.field("lhs").unwrap();
+        assert_eq!(NodeData::Raw("1".to_owned()), lhs.data);
+        let rhs = sum.field("rhs").unwrap();
+        assert_eq!("rhs[NUMBER NUMBER]", node_types(rhs));
+    }
+
+    #[test]
+    fn required_whitespace() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = 'r' 'e' 't' 'u' 'r' 'n' $WHITESPACE+ 'x' $WHITESPACE;",
+        ).unwrap();
+
+        assert!(accepts(&parser, "return x"));
+        assert!(accepts(&parser, "return\n\tx "));
+        assert!(!accepts(&parser, "returnx"));
+    }
+
+    #[test]
+    fn codepoint_surrogate() {
+        let err = Parser::from_bnf("ROOT(root) = 'a' U+D800;").unwrap_err();
+        assert_eq!(
+            "Invalid codepoint U+D800 in rule ROOT at 1:18",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn build_errors() {
+        match Parser::from_bnf("ROOT(root) = ITEM;\nITEM = 'a' NAME;") {
+            Err(GrammarError::UndeclaredRule { name, rule, loc }) => {
+                assert_eq!("NAME", name);
+                assert_eq!("ITEM", rule);
+                assert_eq!((2, 12), (loc.line(), loc.col()));
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(matches!(
+            Parser::from_bnf("ROOT = 'z'..'a';"),
+            Err(GrammarError::NoRoot)
+        ));
+        assert!(matches!(
+            Parser::from_bnf("ROOT(root) = 'z'..'a';"),
+            Err(GrammarError::InvalidExpression { .. })
+        ));
+        assert!(matches!(
+            Parser::from_bnf("ROOT(root) = 'a'"),
+            Err(GrammarError::Syntax(ParseError::Syntax { .. }))
+        ));
+    }
+
+    #[test]
+    fn source_location() {
+        let parser = Parser::from_bnf_source(
+            "digits.bnf",
+            "\nROOT(root) = DIGIT { DIGIT };\n\nDIGIT(raw) = '0' | '1';\n",
+        )
+        .unwrap();
+
+        let root = parser.get_symbol(&parser.find_symbol("ROOT").unwrap());
+        let digit = parser.get_symbol(&parser.find_symbol("DIGIT").unwrap());
+        assert_eq!(Some(("digits.bnf", 2)), root.source_location());
+        assert_eq!(Some(("digits.bnf", 4)), digit.source_location());
+        assert!(root.repr(&parser).ends_with("@ digits.bnf:2"));
+    }
+
+    #[test]
+    fn doc_comments() {
+        let parser = Parser::from_bnf_source(
+            "digits.bnf",
+            "# A list of digits.\n# At least one.\n\
+             ROOT(root) = DIGIT { DIGIT } SIGN;\n\
+             \n/// A single binary digit.\nDIGIT(raw) = '0' | '1';\n\
+             SIGN(raw) = '+' | '-';\n",
+        )
+        .unwrap();
+
+        let root = parser.get_symbol(&parser.find_symbol("ROOT").unwrap());
+        let digit = parser.get_symbol(&parser.find_symbol("DIGIT").unwrap());
+        let sign = parser.get_symbol(&parser.find_symbol("SIGN").unwrap());
+        assert_eq!(Some("A list of digits.\nAt least one."), root.doc());
+        assert_eq!(Some("A single binary digit."), digit.doc());
+        assert_eq!(None, sign.doc());
+        assert_eq!(Some(("digits.bnf", 3)), root.source_location());
+        assert_eq!(Some(("digits.bnf", 6)), digit.source_location());
+        assert!(accepts(&parser, "0110+"));
+        assert!(parser
+            .to_bnf()
+            .unwrap()
+            .starts_with("# A list of digits.\n# At least one.\nROOT(root)"));
+    }
+
+    #[test]
+    fn line_comments() {
+        let bnf = "// Digits, then a sign.\n\
+                   ROOT(root) = DIGITS SIGN; # Trailing comment.\n\
+                   DIGITS(raw) = { // Inside a rule.\n  '0' | '1'\n};\n\
+                   SIGN(raw) = # Before the body.\n'+' | '-';\n\
+                   # At the end, without a line break.";
+        let ast = Parser::parse_bnf_to_ast(bnf).unwrap();
+
+        assert!(!format!("{:?}", ast).contains("comment"));
+        assert!(get_children_of_node(&ast)
+            .iter()
+            .all(|stmt| get_stmt_doc(stmt).is_none()));
+        let parser = Parser::from_bnf(bnf).unwrap();
+        assert!(accepts(&parser, "0110+"));
+        assert!(!accepts(&parser, "#"));
+    }
+
+    #[test]
+    fn memo_flag() {
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+
+        for (flags, expected) in [("", 2), ("(memo)", 1)] {
+            let mut parser = Parser::from_bnf(&format!(
+                "ROOT(root) = ( X 'a' ) | ( X 'b' );\nX{} = 'x' 'x';",
+                flags
+            ))
+            .unwrap();
+            let built = Arc::new(AtomicUsize::new(0));
+            let counter = Arc::clone(&built);
+            parser.on_complete(
+                "X",
+                Box::new(move |_| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }),
+            );
+
+            assert!(accepts(&parser, "xxb"));
+            assert_eq!(expected, built.load(Ordering::Relaxed), "{}", flags);
+        }
+    }
+
+    #[test]
+    fn astral_range() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = EMOJI { EMOJI };\nEMOJI(raw) = '\u{1F600}'..'\u{1F60F}';",
+        ).unwrap();
+
+        let root = parse(&parser, "\u{1F600}\u{1F60F}\u{1F605}");
+        assert_eq!(
+            vec![
+                NodeData::Raw("\u{1F600}".to_owned()),
+                NodeData::Raw("\u{1F60F}".to_owned()),
+                NodeData::Raw("\u{1F605}".to_owned()),
+            ],
+            get_children_of_node(&root)
+                .iter()
+                .map(|n| n.data.clone())
+                .collect::<Vec<_>>()
+        );
+        assert!(!accepts(&parser, "\u{1F610}"));
+        assert!(!accepts(&parser, "\u{1F5FF}"));
+    }
+
+    #[test]
+    fn codepoint_range() {
+        let parser =
+            Parser::from_bnf("ROOT(root) = U+D7FF..U+E000 'a'..'c';").unwrap();
+
+        assert!(accepts(&parser, "\u{D7FF}b"));
+        assert!(accepts(&parser, "\u{E000}c"));
+        assert!(!accepts(&parser, "\u{E001}a"));
+        assert!(!accepts(&parser, "\u{D7FF}d"));
+    }
+
+    #[test]
+    fn string_terminal() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = VALUE \": \" 'x';\nVALUE(raw) = \"true\" | \"false\";",
+        )
+        .unwrap();
+
+        let node = parse(&parser, "false: x");
+        assert_eq!(r#"(ROOT (VALUE "false"))"#, node.to_sexpr());
+        assert!(!accepts(&parser, "fals: x"));
+        assert!(!accepts(&parser, "TRUE: x"));
+        assert!(accepts(
+            &Parser::from_bnf_ci("ROOT(root) = \"true\";").unwrap(),
+            "TRUE"
+        ));
+        assert_eq!(
+            "Empty string in rule ROOT at 1:18",
+            Parser::from_bnf("ROOT(root) = 'a' \"\";")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn escaped_terminals() {
+        let parser = Parser::from_bnf(
+            r"ROOT(root) = '\n' '\t' '\r' '\\' '\'' '\u{1F600}' '\' 'a'..'\u{63}';",
+        )
+        .unwrap();
+
+        assert!(accepts(&parser, "\n\t\r\\'😀\\b"));
+        assert!(!accepts(&parser, "\n\t\r\\'😀\\d"));
+        assert_eq!(
+            r"Invalid codepoint '\u{D800}' in rule ROOT at 1:14",
+            Parser::from_bnf(r"ROOT(root) = '\u{D800}';")
+                .unwrap_err()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn bounded_many() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = { A }<2,3> { B }<2> { C }<1,> 'd';\n\
+             A(raw) = 'a';\nB(raw) = 'b';\nC(raw) = 'c';",
+        )
+        .unwrap();
+
+        assert_eq!(
+            "(ROOT (A \"a\") (A \"a\") (B \"b\") (B \"b\") (C \"c\"))",
+            parse(&parser, "aabbcd").to_sexpr()
+        );
+        assert!(accepts(&parser, "aaabbcccd"));
+        for input in ["abbcd", "aaaabbcd", "aabcd", "aabbbcd", "aabbd"] {
+            assert!(!accepts(&parser, input), "{}", input);
+        }
+        assert_eq!(
+            "Invalid bounds <3,2> in rule ROOT at 1:21",
+            Parser::from_bnf("ROOT(root) = { 'a' }<3,2>;")
+                .unwrap_err()
+                .to_string()
+        );
+        let emitted = Parser::from_bnf(&parser.to_bnf().unwrap()).unwrap();
+        assert!(accepts(&emitted, "aaabbcccd"));
+        assert!(!accepts(&emitted, "aaaabbcd"));
+    }
+
+    #[test]
+    fn large_bounds() {
+        // One symbol however large the bounds, matched without recursion.
+        let parser = Parser::from_bnf("A(root) = { 'a' }<0,200000>;").unwrap();
+        assert!(parser.symbol_count() < 10);
+        let parser = Parser::from_bnf("A(root) = { 'a' }<0,20000>;").unwrap();
+        assert!(accepts(&parser, &"a".repeat(20000)));
+        assert!(!accepts(&parser, &"a".repeat(20001)));
+    }
+
+    #[test]
+    fn and_predicate() {
+        // A keyword only where it is not the start of a longer word.
+        let parser = Parser::from_bnf(
+            "ROOT(root) = { ( KEYWORD | NAME ) ( ' ' | ';' ) };\n\
+             KEYWORD(raw) = 'i' 'f' &( ' ' | ';' );\n\
+             NAME(raw) = 'a'..'z' { 'a'..'z' };",
+        )
+        .unwrap();
+
+        let tree = "(ROOT (KEYWORD \"if\") (NAME \"iffy\") (KEYWORD \"if\"))";
+        assert_eq!(tree, parse(&parser, "if iffy if;").to_sexpr());
+        let emitted = Parser::from_bnf(&parser.to_bnf().unwrap()).unwrap();
+        assert_eq!(tree, parse(&emitted, "if iffy if;").to_sexpr());
+    }
+
+    #[test]
+    fn not_predicate() {
+        let parser = Parser::from_bnf(
+            "STRING(root,raw) = '\"' { STRING_CHAR } '\"';\n\
+             STRING_CHAR = !'\"' *;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            "(STRING \"\\\"a'b\\\"\")",
+            parse(&parser, "\"a'b\"").to_sexpr()
+        );
+        assert!(!accepts(&parser, "\"a\"b\""));
+        assert_eq!(
+            "Expected '\\\"' in STRING at 1:4",
+            parser
+                .parse(&mut CharStream::from("\"ab"))
+                .unwrap_err()
+                .to_string()
+        );
+        let emitted = Parser::from_bnf(&parser.to_bnf().unwrap()).unwrap();
+        assert!(accepts(&emitted, "\"a'b\""));
+        assert!(!accepts(&emitted, "\"a\"b\""));
+
+        // A matching `!X` keeps what earlier alternatives expected.
+        let expected = |bnf: &str| match Parser::from_bnf(bnf)
+            .unwrap()
+            .parse(&mut CharStream::from("xz"))
+        {
+            Err(ParseError::Syntax { expected, .. }) => expected,
+            other => panic!("Expected a syntax error, got {:?}", other),
+        };
+        assert_eq!(
+            vec!['q', 'y'],
+            expected("ROOT(root) = A | B;\nA = 'x' 'y';\nB = 'x' 'q';")
+        );
+        assert_eq!(
+            vec!['y'],
+            expected("ROOT(root) = A | B;\nA = 'x' 'y';\nB = 'x' !'z' 'q';")
+        );
+    }
+
+    #[test]
+    fn recursive_wrappers() {
+        let nested =
+            Parser::from_bnf("NESTED(root) = [ '(' NESTED ')' ];").unwrap();
+        let aliased =
+            Parser::from_bnf("ROOT(root) = A;\nA = B;\nB = [ 'x' A ];")
+                .unwrap();
+
+        assert!(accepts(&nested, "((()))"));
+        assert!(!accepts(&nested, "(()"));
+        assert!(accepts(&aliased, "xxx"));
+        assert_eq!(
+            Some(nested.find_symbol("NESTED").unwrap()),
+            nested.root_node
+        );
+    }
+
+    #[test]
+    fn any_except() {
+        let any = Parser::from_bnf("ROOT(root) = * *;").unwrap();
+        let except =
+            Parser::from_bnf("ROOT(root) = *( 'a'..'c',U+22 , '\\' ) 'x';")
+                .unwrap();
+
+        assert!(accepts(&any, "\"\\"));
+        assert!(accepts(&except, "dx"));
+        for input in ["ax", "cx", "\"x", "\\x", "dy"] {
+            assert!(!accepts(&except, input), "{}", input);
+        }
+        assert!(matches!(
+            Parser::from_bnf("ROOT(root) = *('b'..'a');"),
+            Err(GrammarError::InvalidExpression { .. })
+        ));
+    }
+
+    #[test]
+    fn dash_range() {
+        let parser =
+            Parser::from_bnf("ROOT(root) = '0' - '9' 'a'-'f' U+41 -\\x43;")
+                .unwrap();
+
+        assert!(accepts(&parser, "5cB"));
+        assert!(accepts(&parser, "0fC"));
+        assert!(!accepts(&parser, "5gA"));
+        assert!(!accepts(&parser, "5aD"));
+        assert!(matches!(
+            Parser::from_bnf("ROOT(root) = 'z' - 'a';"),
+            Err(GrammarError::InvalidExpression { .. })
+        ));
+    }
+
+    #[test]
+    fn range_char_set() {
+        let parser =
+            Parser::from_bnf("ROOT(root) = 'a'..'c' | 'x' - 'z';").unwrap();
+
+        let root = parser.get_symbol(&parser.root_node.unwrap());
+        for id in root.sub_symbols() {
+            let symbol_type = &parser.get_symbol(id).symbol_type;
+            assert!(matches!(symbol_type, SymbolType::CharSet(_)));
+        }
+        assert_eq!(vec!["a", "b", "c", "x", "y", "z"], parser.literals());
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let parser = Parser::from_bnf_ci(
+            "ROOT(root) = METHOD ' ' PATH;\nMETHOD(raw) = 'G' 'E' 'T';\n\
+             PATH(raw) = '/' 'x';",
+        )
+        .unwrap();
+
+        for method in ["GET", "get", "Get"] {
+            let node = parse(&parser, &format!("{} /X", method));
+            assert_eq!(
+                NodeData::Raw(method.to_owned()),
+                node.field("METHOD").unwrap().data
+            );
+        }
+        assert!(!accepts(&parser, "GOT /x"));
+        assert!(!accepts(
+            &Parser::from_bnf("ROOT(root) = 'G';").unwrap(),
+            "g"
+        ));
+        assert_eq!("ß", cases('ß'));
+        assert_eq!("Kk", cases('K'));
+        assert_eq!("é", cases('é'));
+
+        // Terminals and ranges fold the same, ASCII letters only.
+        let parser =
+            Parser::from_bnf_ci("ROOT(root) = 'é' 'x' 'à'..'ö' 'a'..'c';")
+                .unwrap();
+        assert!(accepts(&parser, "éxàb"));
+        assert!(accepts(&parser, "éXöB"));
+        assert!(!accepts(&parser, "Éxàb"));
+        assert!(!accepts(&parser, "éxÀb"));
+    }
+
+    #[test]
+    fn ci_flag() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = METHOD ' ' PATH ' ' HEX;\n\
+             METHOD(raw,ci) = \"get\";\n\
+             PATH(raw) = '/' 'a'..'z';\n\
+             HEX(raw,ci) = { '0'..'9' | 'a'..'f' }<2>;",
+        )
+        .unwrap();
+
+        let node = parse(&parser, "GeT /x 9C");
+        assert_eq!(
+            NodeData::Raw("GeT".to_owned()),
+            node.field("METHOD").unwrap().data
+        );
+        assert_eq!(
+            NodeData::Raw("9C".to_owned()),
+            node.field("HEX").unwrap().data
+        );
+        assert!(accepts(&parser, "get /x fa"));
+        assert!(!accepts(&parser, "get /X fa"));
+        assert!(!accepts(&parser, "get /x fG"));
+    }
+
+    #[test]
+    fn tight() {
+        let bnf = "ROOT(root) = '(' NAME ')';\n\
+                   NAME(raw,tight) = $WHITESPACE 'a'..'z' { 'a'..'z' } \
+                   $WHITESPACE;";
+        let tight = Parser::from_bnf(bnf).unwrap();
+        let padded = Parser::from_bnf(&bnf.replace(",tight", "")).unwrap();
+
+        let node = parse(&tight, "( abc  )");
+        let name = node.field("NAME").unwrap();
+        assert_eq!(NodeData::Raw("abc".to_owned()), name.data);
+        assert_eq!(2..5, name.loc.byte_span());
+        let node = parse(&padded, "( abc  )");
+        assert_eq!(
+            NodeData::Raw(" abc  ".to_owned()),
+            node.field("NAME").unwrap().data
+        );
+        let optional = Parser::from_bnf(
+            "ROOT(root) = '(' NAME ')';\n\
+             NAME(raw,tight) = [ $WHITESPACE ] 'a' [ $WHITESPACE ];",
+        )
+        .unwrap();
+        let node = parse(&optional, "( a )");
+        let name = node.field("NAME").unwrap();
+        assert_eq!(NodeData::Raw("a".to_owned()), name.data);
+    }
+
+    #[test]
+    fn deeply_nested() {
+        // A hundred parentheses, as groups alternate between sequences and
+        // alternatives.
+        let mut expr = "( 'b' 'c' )".to_owned();
+        for _ in 0..50 {
+            expr = format!("( 'a' ( {} | 'z' ) )", expr);
+        }
+        let parser =
+            Parser::from_bnf(&format!("ROOT(root) = 'x' ( {} | 'z' );", expr))
+                .unwrap();
+        assert!(accepts(&parser, &format!("x{}bc", "a".repeat(50))));
+        assert!(accepts(&parser, &format!("x{}z", "a".repeat(20))));
+
+        // Nest deeper than the BNF parser could, straight in the AST.
+        let mut ast = Parser::parse_bnf_to_ast("ROOT(root) = 'a';").unwrap();
+        let NodeData::Children(statements) = &mut ast.data else {
+            panic!("Root is raw")
+        };
+        let NodeData::Children(parts) = &mut statements[0].data else {
+            panic!("Statement is raw")
+        };
+        let expr = parts.last_mut().unwrap();
+        for _ in 0..20_000 {
+            let loc = expr.loc.clone();
+            let group = Node::new("GROUP", loc, NodeData::Children(vec![]));
+            let inner = std::mem::replace(expr, group);
+            expr.data = NodeData::Children(vec![inner]);
+        }
+        let parser = ParserBuilder::new().build(&ast).unwrap();
+        assert!(accepts(&parser, "a"));
+        // Dropping the AST recursively would overflow the stack as well.
+        let mut nodes = vec![ast];
+        while let Some(mut node) = nodes.pop() {
+            if let NodeData::Children(children) = &mut node.data {
+                nodes.append(children);
+            }
+        }
+    }
+
+    #[test]
+    fn max_symbols() {
+        let bnf = format!("ROOT(root) = {};", vec!["'x'"; 500].join(" "));
+        let ast = Parser::parse_bnf_to_ast(&bnf).unwrap();
+
+        let parser = ParserBuilder::new().build(&ast).unwrap();
+        assert!(parser.symbol_count() > 100);
+        assert_eq!(
+            Err(GrammarError::TooLarge(100)),
+            ParserBuilder::new()
+                .with_max_symbols(100)
+                .build(&ast)
+                .map(|p| p.symbol_count())
+        );
+        assert!(ParserBuilder::new()
+            .with_max_symbols(parser.symbol_count())
+            .build(&ast)
+            .is_ok());
+    }
+
+    #[test]
+    fn duplicate_rule() {
+        let ast = Parser::parse_bnf_to_ast(
+            "ROOT(root) = NUMBER;\nNUMBER = '1';\nNUMBER = '2';",
+        )
+        .unwrap();
+
+        match ParserBuilder::new().build(&ast) {
+            Err(GrammarError::DuplicateRule {
+                name,
+                first_loc,
+                second_loc,
+            }) => {
+                assert_eq!("NUMBER", name);
+                assert_eq!(2, first_loc.line());
+                assert_eq!(3, second_loc.line());
             }
+            other => panic!("Unexpected result {:?}", other),
         }
-        NodeData::Raw(_) => panic!("Statement node is raw"),
     }
 }
@@ -0,0 +1,278 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::input::Position;
+use crate::input::Range;
+use crate::parse::Node;
+use crate::parse::NodeData;
+
+/// Tag byte of a node with children.
+const CHILDREN: u8 = 0;
+/// Tag byte of a raw node.
+const RAW: u8 = 1;
+/// Set in the tag byte of nodes whose rule is marked as a list.
+const LIST: u8 = 2;
+/// How deeply [`Node::from_bytes`] lets nodes nest. Deeper trees could not
+/// even be dropped without overflowing the stack.
+const MAX_DEPTH: usize = 10_000;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The bytes end in the middle of a node.
+    UnexpectedEnd,
+    /// A number does not fit into a `usize`.
+    Overflow,
+    /// A string is not valid UTF-8.
+    InvalidUtf8,
+    /// A node has an unknown tag byte.
+    InvalidTag(u8),
+    /// There are bytes left after the root node.
+    TrailingBytes(usize),
+    /// Nodes nest more than 10000 levels deep.
+    TooDeep,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "Unexpected end of tree"),
+            DecodeError::Overflow => write!(f, "Number out of range"),
+            DecodeError::InvalidUtf8 => write!(f, "String is not UTF-8"),
+            DecodeError::InvalidTag(tag) => {
+                write!(f, "Invalid node tag {}", tag)
+            }
+            DecodeError::TrailingBytes(count) => {
+                write!(f, "{} bytes after the tree", count)
+            }
+            DecodeError::TooDeep => {
+                write!(f, "Tree nested deeper than {}", MAX_DEPTH)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Node {
+    /// The tree in a compact binary format that [`Node::from_bytes`] reads
+    /// back, e.g. to cache parse results on disk.
+    ///
+    /// Each node is its length-prefixed type, its range as two positions
+    /// of four numbers each, a tag byte and either its length-prefixed raw
+    /// text or its child count followed by the children. Numbers are
+    /// LEB128 encoded.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    /// Reads a tree written by [`Node::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Node, DecodeError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let node = reader.node()?;
+        match bytes.len() - reader.pos {
+            0 => Ok(node),
+            rest => Err(DecodeError::TrailingBytes(rest)),
+        }
+    }
+
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_str(out, &self.node_type);
+        for pos in [self.loc.0, self.loc.1] {
+            for n in [pos.line(), pos.col(), pos.index(), pos.byte_offset()] {
+                write_usize(out, n);
+            }
+        }
+        let list = if self.is_list { LIST } else { 0 };
+        match &self.data {
+            NodeData::Children(children) => {
+                out.push(CHILDREN | list);
+                write_usize(out, children.len());
+                for child in children {
+                    child.encode(out);
+                }
+            }
+            NodeData::Raw(raw) => {
+                out.push(RAW | list);
+                write_str(out, raw);
+            }
+        }
+    }
+}
+
+fn write_usize(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_usize(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Reader<'b> {
+    /// A node with all its children. The nodes whose children are still
+    /// being read wait on a stack instead of the call stack, so corrupt
+    /// input fails with [`DecodeError::TooDeep`] rather than overflowing it.
+    fn node(&mut self) -> Result<Node, DecodeError> {
+        // Each with the number of children still to read.
+        let mut open: Vec<(Node, usize)> = Vec::new();
+        loop {
+            let (mut node, mut left) = self.header()?;
+            loop {
+                if left > 0 {
+                    if open.len() == MAX_DEPTH {
+                        return Err(DecodeError::TooDeep);
+                    }
+                    open.push((node, left));
+                    break;
+                }
+                let Some((mut parent, parent_left)) = open.pop() else {
+                    return Ok(node);
+                };
+                if let NodeData::Children(children) = &mut parent.data {
+                    children.push(node);
+                }
+                node = parent;
+                left = parent_left - 1;
+            }
+        }
+    }
+
+    /// A node without its children, and how many children follow it.
+    fn header(&mut self) -> Result<(Node, usize), DecodeError> {
+        let node_type = self.str()?;
+        let start = self.position()?;
+        let end = self.position()?;
+        let tag = self.byte()?;
+        let (data, count) = match tag & !LIST {
+            CHILDREN => {
+                let count = self.usize()?;
+                // Every child takes several bytes, so a count beyond the
+                // remaining bytes is corrupt and must not be reserved.
+                let children =
+                    Vec::with_capacity(count.min(self.bytes.len() - self.pos));
+                (NodeData::Children(children), count)
+            }
+            RAW => (NodeData::Raw(self.str()?.to_owned()), 0),
+            _ => return Err(DecodeError::InvalidTag(tag)),
+        };
+        let mut node = Node::new(node_type, Range::new(start, end), data);
+        node.is_list = tag & LIST != 0;
+        Ok((node, count))
+    }
+
+    fn position(&mut self) -> Result<Position, DecodeError> {
+        Ok(Position::new(
+            self.usize()?,
+            self.usize()?,
+            self.usize()?,
+            self.usize()?,
+        ))
+    }
+
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let byte =
+            *self.bytes.get(self.pos).ok_or(DecodeError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn usize(&mut self) -> Result<usize, DecodeError> {
+        let mut n: usize = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as usize;
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(DecodeError::Overflow);
+            }
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+            shift += 7;
+        }
+    }
+
+    fn str(&mut self) -> Result<&'b str, DecodeError> {
+        let len = self.usize()?;
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        let s = std::str::from_utf8(&self.bytes[self.pos..end])
+            .map_err(|_| DecodeError::InvalidUtf8)?;
+        self.pos = end;
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+    use crate::input::CharStream;
+
+    #[test]
+    fn round_trip() {
+        let parser = json_parser();
+        for input in ["[[[1]]]", "{\"é\": [true, \"a\\\"b\"]}"] {
+            let tree = parser.parse(&mut CharStream::from(input)).unwrap();
+
+            let bytes = tree.to_bytes();
+            assert_eq!(Ok(tree), Node::from_bytes(&bytes));
+        }
+    }
+
+    #[test]
+    fn corrupt() {
+        let tree = json_parser()
+            .parse(&mut CharStream::from("[[[1]]]"))
+            .unwrap();
+        let bytes = tree.to_bytes();
+
+        assert_eq!(
+            Err(DecodeError::UnexpectedEnd),
+            Node::from_bytes(&bytes[..bytes.len() - 1])
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            Err(DecodeError::TrailingBytes(1)),
+            Node::from_bytes(&trailing)
+        );
+        assert_eq!(Err(DecodeError::Overflow), Node::from_bytes(&[0xff; 11]));
+    }
+
+    #[test]
+    fn deeply_nested() {
+        // Type "A", two positions of zeros and one child each, around a raw
+        // node.
+        let nested = |depth: usize| {
+            let mut bytes = Vec::new();
+            for _ in 0..depth {
+                bytes.extend([1, b'A', 0, 0, 0, 0, 0, 0, 0, 0, CHILDREN, 1]);
+            }
+            bytes.extend([1, b'B', 0, 0, 0, 0, 0, 0, 0, 0, RAW, 0]);
+            bytes
+        };
+
+        let node = Node::from_bytes(&nested(1000)).unwrap();
+        assert_eq!(1001, node.descendants().count());
+        assert_eq!(
+            Err(DecodeError::TooDeep),
+            Node::from_bytes(&nested(100_000))
+        );
+    }
+}
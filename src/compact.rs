@@ -0,0 +1,261 @@
+use crate::parse::char_after;
+use crate::parse::new_tag;
+use crate::parse::Parser;
+use crate::parse::SymbolIdent;
+use crate::parse::SymbolType;
+use crate::trie::LiteralTrie;
+
+impl Parser {
+    /// Rewrites sequences of plain terminals, such as those built by
+    /// [`Parser::sequence_chars`], into a single `Literal` and alternatives
+    /// of plain terminals, such as those built by [`Parser::one_of_chars`],
+    /// into a `CharSet`. Terminals nothing refers to afterwards are removed
+    /// from the registry.
+    ///
+    /// Alternatives of plain literals and terminals then become a single
+    /// prefix tree, which scans a prefix shared by several of them, as in
+    /// `let | letrec | length`, only once. The first literal that matches
+    /// still wins.
+    ///
+    /// A terminal or literal is plain if it is unnamed and ignored, so the
+    /// abstract syntax tree does not change. The concrete one, see
+    /// [`Parser::parse_cst`], loses the `Terminal` nodes.
+    ///
+    /// Idents obtained before compacting refer to the old registry and must
+    /// not be used afterwards, which debug builds assert.
+    pub fn compact(&mut self) {
+        let mut orphans = vec![false; self.symbol_registry.len()];
+        for idx in 0..self.symbol_registry.len() {
+            let symbol = &self.symbol_registry[idx];
+            let compacted = match &symbol.symbol_type {
+                SymbolType::Sequence(s) => self.plain_chars(s).map(|chars| {
+                    SymbolType::Literal(chars.into_iter().collect())
+                }),
+                SymbolType::OneOf(s) => self
+                    .plain_chars(s)
+                    .map(|chars| SymbolType::CharSet(ranges(chars))),
+                _ => None,
+            };
+            if let Some(compacted) = compacted {
+                for id in symbol.sub_symbols() {
+                    orphans[id.0] = true;
+                }
+                self.symbol_registry[idx].symbol_type = compacted;
+            }
+        }
+        for idx in 0..self.symbol_registry.len() {
+            let symbol = &self.symbol_registry[idx];
+            let words = match &symbol.symbol_type {
+                SymbolType::OneOf(s) if s.len() >= 2 => self.plain_words(s),
+                _ => None,
+            };
+            if let Some(words) = words {
+                for id in symbol.sub_symbols() {
+                    orphans[id.0] = true;
+                }
+                self.symbol_registry[idx].symbol_type =
+                    SymbolType::LiteralSet(LiteralTrie::new(words));
+            }
+        }
+        for symbol in &self.symbol_registry {
+            for id in symbol.sub_symbols() {
+                orphans[id.0] = false;
+            }
+        }
+        if let Some(root) = self.root_node {
+            orphans[root.0] = false;
+        }
+        self.remove(&orphans);
+    }
+
+    /// The chars of `symbols` if they are all plain terminals.
+    fn plain_chars(&self, symbols: &[SymbolIdent]) -> Option<Vec<char>> {
+        if symbols.is_empty() {
+            return None;
+        }
+        symbols
+            .iter()
+            .map(|id| match self.plain(id)? {
+                SymbolType::Terminal(c) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The texts of `symbols` if they are all plain terminals or literals.
+    fn plain_words(&self, symbols: &[SymbolIdent]) -> Option<Vec<String>> {
+        symbols
+            .iter()
+            .map(|id| match self.plain(id)? {
+                SymbolType::Terminal(c) => Some(c.to_string()),
+                SymbolType::Literal(s) => Some(s.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The type of `ident` if it leaves no trace in the abstract syntax
+    /// tree.
+    fn plain(&self, ident: &SymbolIdent) -> Option<&SymbolType> {
+        let symbol = &self.symbol_registry[ident.0];
+        let properties = &symbol.properties;
+        let plain = properties.node_name.is_none()
+            && properties.ignore
+            && !properties.memoize
+            && properties.error_message.is_none();
+        plain.then_some(&symbol.symbol_type)
+    }
+
+    /// Drops the symbols flagged in `removed` and renumbers the rest. The
+    /// parser gets a new tag, as all idents change.
+    fn remove(&mut self, removed: &[bool]) {
+        self.tag = new_tag();
+        let mut kept = 0;
+        let idents: Vec<SymbolIdent> = removed
+            .iter()
+            .map(|removed| {
+                let ident = self.ident(kept);
+                if !removed {
+                    kept += 1;
+                }
+                ident
+            })
+            .collect();
+        let mut idx = 0;
+        self.symbol_registry.retain(|_| {
+            idx += 1;
+            !removed[idx - 1]
+        });
+        for symbol in &mut self.symbol_registry {
+            match &mut symbol.symbol_type {
+                SymbolType::Sequence(s) | SymbolType::OneOf(s) => {
+                    for id in s {
+                        *id = idents[id.0];
+                    }
+                }
+                SymbolType::Optional(id)
+                | SymbolType::OneOrMore(id)
+                | SymbolType::ZeroOrMore(id)
+                | SymbolType::Repeat { symbol: id, .. }
+                | SymbolType::Lookbehind { symbol: id, .. }
+                | SymbolType::And(id)
+                | SymbolType::Not(id) => *id = idents[id.0],
+                _ => {}
+            }
+        }
+        self.root_node = self.root_node.map(|root| idents[root.0]);
+    }
+}
+
+/// `chars` as sorted, merged ranges.
+fn ranges(mut chars: Vec<char>) -> Vec<(char, char)> {
+    chars.sort();
+    chars.dedup();
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    for c in chars {
+        match ranges.last_mut() {
+            Some((_, to)) if char_after(*to) == Some(c) => *to = c,
+            _ => ranges.push((c, c)),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+    use crate::input::CharStream;
+    use crate::parse::ParseError;
+
+    #[test]
+    fn json() {
+        let json = json_parser();
+        let mut compacted = json_parser();
+        compacted.compact();
+
+        assert!(compacted.symbol_count() < json.symbol_count() / 2);
+        let reparsed = Parser::from_bnf(&compacted.to_bnf().unwrap()).unwrap();
+        for input in [
+            "[]",
+            r#"{"a": [1, -2.5e+3, true, null], "b": "é\n"}"#,
+            "[tru]",
+            "01",
+            r#"{"a" 1}"#,
+        ] {
+            // Failed sequence elements differ where chars became literals.
+            let parse = |parser: &Parser| {
+                parser.parse(&mut CharStream::from(input)).map_err(
+                    |e| match e {
+                        ParseError::Syntax { pos, .. } => pos,
+                        other => panic!("Unexpected error {:?}", other),
+                    },
+                )
+            };
+            assert_eq!(parse(&json), parse(&compacted), "{}", input);
+            assert_eq!(
+                json.parse(&mut CharStream::from(input)).is_ok(),
+                reparsed.parse(&mut CharStream::from(input)).is_ok(),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_named_terminals() {
+        let mut parser =
+            Parser::from_bnf("ROOT(root) = ( A 'b' ) | 'c';\nA = 'a';")
+                .unwrap();
+        parser.compact();
+
+        let root = parser.root_node.unwrap();
+        assert!(matches!(
+            parser.get_symbol(&root).symbol_type,
+            SymbolType::OneOf(_)
+        ));
+        let node = parser.parse(&mut CharStream::from("ab")).unwrap();
+        assert!(node.field("A").is_some());
+        assert!(parser.parse(&mut CharStream::from("c")).is_ok());
+    }
+
+    #[test]
+    fn keyword_trie() {
+        let bnf = "ROOT(root) = KEYWORD { ' ' KEYWORD };\n\
+                   KEYWORD(raw) = ( 'l' 'e' 't' ) | ( 'l' 'e' 't' 'r' 'e' 'c' ) \
+                   | ( 'l' 'e' 'n' 'g' 't' 'h' ) | 'x';";
+        let parser = Parser::from_bnf(bnf).unwrap();
+        let mut compacted = Parser::from_bnf(bnf).unwrap();
+        compacted.compact();
+
+        let keyword = compacted.find_symbol("KEYWORD").unwrap();
+        assert!(matches!(
+            compacted.get_symbol(&keyword).symbol_type,
+            SymbolType::LiteralSet(_)
+        ));
+        let reparsed = Parser::from_bnf(&compacted.to_bnf().unwrap()).unwrap();
+        for input in ["let length x", "letrec", "let rec", "len", "x let"] {
+            let result = parser.parse(&mut CharStream::from(input)).ok();
+            assert_eq!(
+                result,
+                compacted.parse(&mut CharStream::from(input)).ok(),
+                "{}",
+                input
+            );
+            assert_eq!(
+                result,
+                reparsed.parse(&mut CharStream::from(input)).ok(),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn ranges_merge() {
+        assert_eq!(
+            vec![('0', '3'), ('a', 'a')],
+            ranges(vec!['3', 'a', '1', '0', '2', '1'])
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use crate::build::get_children_of_node;
+use crate::build::get_stmt_expr;
+use crate::build::GrammarError;
+use crate::build::ParserBuilder;
+use crate::parse::Parser;
+
+impl Parser {
+    /// A copy of this parser in which the named rules match the BNF rule
+    /// bodies in `overrides` instead, e.g. `"'a' | 'b'"` for a rule `A`.
+    ///
+    /// Everything referring to an overridden rule now refers to its new body,
+    /// while its node name and flags stay the same. The bodies may refer to
+    /// any named rule of this parser. The hooks and conversions of the rules
+    /// that are not overridden are shared with this parser, those of the
+    /// overridden rules are dropped. Idents of this parser stay valid for
+    /// the copy.
+    ///
+    /// Fails with [`GrammarError::LeftRecursion`] if an override makes a rule
+    /// reach itself without consuming input, like [`Parser::from_bnf`].
+    pub fn derive_with_overrides(
+        &self,
+        overrides: HashMap<String, &str>,
+    ) -> Result<Parser, GrammarError> {
+        let mut names: Vec<&String> = overrides.keys().collect();
+        names.sort();
+        let asts = names
+            .iter()
+            .map(|name| {
+                let rule = format!("{} = {};", name, overrides[*name]);
+                Parser::parse_bnf_to_ast(&rule)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut builder = ParserBuilder::extending(self.clone_grammar());
+        for (name, ast) in names.iter().zip(&asts) {
+            let statement = &get_children_of_node(ast)[0];
+            builder.override_rule(name, get_stmt_expr(statement))?;
+        }
+        let mut parser = builder.into_parser();
+        for name in names {
+            parser.remove_hooks(name);
+        }
+        parser.validate()?;
+        Ok(parser)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+    use crate::input::CharStream;
+    use crate::parse::NodeData;
+
+    #[test]
+    fn single_quote_strings() {
+        let json = json_parser();
+        let dialect = json
+            .derive_with_overrides(HashMap::from([(
+                "STRING".to_owned(),
+                "( '\"' { *('\"', '\\') | ESCAPED } '\"' ) | ( U+27 { U+0..U+26 | \
+                 U+28..U+5B | U+5D..U+10FFFF | ESCAPED } U+27 )",
+            )]))
+            .unwrap();
+
+        let input = r#"{'a': ["b", 'c"']}"#;
+        assert!(json.parse(&mut CharStream::from(input)).is_err());
+        let node = dialect.parse(&mut CharStream::from(input)).unwrap();
+        let key = node.field("OBJECT").unwrap().field("PROPERTY").unwrap();
+        assert_eq!(
+            NodeData::Raw("'a'".to_owned()),
+            key.field("STRING").unwrap().data
+        );
+        assert!(dialect.parse(&mut CharStream::from(r#"["a", 1]"#)).is_ok());
+    }
+
+    #[test]
+    fn hooks_of_other_rules() {
+        let mut json = json_parser();
+        json.parse_as::<u32>("NUMBER");
+        json.on_complete("STRING", Box::new(|_| Err("No strings".to_owned())));
+        let dialect = json
+            .derive_with_overrides(HashMap::from([(
+                "STRING".to_owned(),
+                "U+27 { U+0..U+26 | U+28..U+10FFFF } U+27",
+            )]))
+            .unwrap();
+
+        let node = dialect.parse(&mut CharStream::from("['a', 42]")).unwrap();
+        let array = node.field("ARRAY").unwrap();
+        let number = array.field("NUMBER").unwrap();
+        assert_eq!(Some(&42), number.value::<u32>());
+        assert!(array.field("STRING").is_some());
+    }
+
+    #[test]
+    fn left_recursive_override() {
+        let json = json_parser();
+
+        assert!(matches!(
+            json.derive_with_overrides(HashMap::from([(
+                "ARRAY".to_owned(),
+                "( ARRAY '+' 'x' ) | 'x'"
+            )])),
+            Err(GrammarError::LeftRecursion(_))
+        ));
+    }
+
+    #[test]
+    fn unknown_rule() {
+        let json = json_parser();
+
+        assert_eq!(
+            Some(GrammarError::UnknownRule("STRINGS".to_owned())),
+            json.derive_with_overrides(HashMap::from([(
+                "STRINGS".to_owned(),
+                "'a'"
+            )]))
+            .err()
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::parse::bounds;
+use crate::parse::Parser;
+use crate::parse::SymbolIdent;
+use crate::parse::SymbolType;
+
+/// The named rules that differ between two grammars, see
+/// [`Parser::diff`]. Each list is sorted by name.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GrammarDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl GrammarDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+    }
+}
+
+/// One line per rule, `+ ADDED`, `- REMOVED` and `~ CHANGED`.
+impl Display for GrammarDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for name in &self.added {
+            writeln!(f, "+ {}", name)?;
+        }
+        for name in &self.removed {
+            writeln!(f, "- {}", name)?;
+        }
+        for name in &self.changed {
+            writeln!(f, "~ {}", name)?;
+        }
+        Ok(())
+    }
+}
+
+impl Parser {
+    /// The named rules `other` adds, removes or changes compared to this
+    /// grammar. A rule changed if its flags or its body differ, where rules
+    /// it refers to are compared by name only. Of several symbols with the
+    /// same name the first one counts.
+    pub fn diff(&self, other: &Parser) -> GrammarDiff {
+        let old = self.rule_shapes();
+        let new = other.rule_shapes();
+        let mut diff = GrammarDiff::default();
+        for (name, shape) in &new {
+            match old.get(name) {
+                None => diff.added.push(name.clone()),
+                Some(old_shape) if old_shape != shape => {
+                    diff.changed.push(name.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = old
+            .keys()
+            .filter(|name| !new.contains_key(*name))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /// Every named rule with a description of its flags and body.
+    fn rule_shapes(&self) -> BTreeMap<String, String> {
+        let mut shapes = BTreeMap::new();
+        for (idx, symbol) in self.symbol_registry.iter().enumerate() {
+            if let Some(name) = &symbol.properties.node_name {
+                if !shapes.contains_key(name) {
+                    let mut shape = String::new();
+                    self.shape(
+                        &self.ident(idx),
+                        true,
+                        &mut Vec::new(),
+                        &mut shape,
+                    );
+                    shapes.insert(name.clone(), shape);
+                }
+            }
+        }
+        shapes
+    }
+
+    /// Writes the structure of `ident` to `out`, referring to named symbols
+    /// other than the `top` one by name.
+    fn shape(
+        &self,
+        ident: &SymbolIdent,
+        top: bool,
+        path: &mut Vec<SymbolIdent>,
+        out: &mut String,
+    ) {
+        let symbol = self.get_symbol(ident);
+        let properties = &symbol.properties;
+        if let Some(name) = &properties.node_name {
+            if !top {
+                out.push_str(name);
+                return;
+            }
+        }
+        if path.contains(ident) {
+            out.push_str("<cycle>");
+            return;
+        }
+        path.push(*ident);
+        out.push_str(&format!(
+            "[{} {} {} {:?} {} {}]",
+            properties.raw,
+            properties.ignore,
+            properties.memoize,
+            properties.error_message,
+            properties.is_list,
+            properties.tight
+        ));
+        let kind = match &symbol.symbol_type {
+            SymbolType::Sequence(_) => "Sequence".to_owned(),
+            SymbolType::OneOf(_) => "OneOf".to_owned(),
+            SymbolType::Optional(_) => "Optional".to_owned(),
+            SymbolType::OneOrMore(_) => "OneOrMore".to_owned(),
+            SymbolType::ZeroOrMore(_) => "ZeroOrMore".to_owned(),
+            SymbolType::Repeat { min, max, .. } => {
+                format!("Repeat{}", bounds(*min, *max))
+            }
+            SymbolType::Lookbehind { .. } => "Lookbehind".to_owned(),
+            SymbolType::And(_) => "And".to_owned(),
+            SymbolType::Not(_) => "Not".to_owned(),
+            other => format!("{:?}", other),
+        };
+        out.push_str(&kind);
+        out.push('(');
+        for (idx, id) in symbol.sub_symbols().iter().enumerate() {
+            if idx > 0 {
+                out.push(' ');
+            }
+            self.shape(id, false, path, out);
+        }
+        out.push(')');
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRAMMAR: &str = "ROOT(root) = ITEM { ',' ITEM };\n\
+                           ITEM = NAME | NUMBER;\n\
+                           NAME(raw) = 'a'..'z' { 'a'..'z' };\n\
+                           NUMBER(raw) = '0'..'9' { '0'..'9' };";
+
+    #[test]
+    fn one_rule_changed() {
+        let old = Parser::from_bnf(GRAMMAR).unwrap();
+        let new = Parser::from_bnf(
+            &GRAMMAR
+                .replace("NUMBER(raw) = '0'..'9'", "NUMBER(raw) = '1'..'9'"),
+        )
+        .unwrap();
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            GrammarDiff {
+                added: vec![],
+                removed: vec![],
+                changed: vec!["NUMBER".to_owned()],
+            },
+            diff
+        );
+        assert_eq!("~ NUMBER\n", diff.to_string());
+        assert!(old.diff(&Parser::from_bnf(GRAMMAR).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn added_removed_and_flags() {
+        let old = Parser::from_bnf(GRAMMAR).unwrap();
+        let new = Parser::from_bnf(
+            &GRAMMAR
+                .replace("ITEM = NAME | NUMBER;", "ITEM(memo) = NAME | WORD;")
+                .replace("NUMBER(raw)", "WORD(raw)"),
+        )
+        .unwrap();
+
+        assert_eq!("+ WORD\n- NUMBER\n~ ITEM\n", old.diff(&new).to_string());
+    }
+}
@@ -0,0 +1,331 @@
+use crate::build::get_children_of_node;
+use crate::build::get_raw_value_of_node;
+use crate::build::GrammarError;
+use crate::build::ParserBuilder;
+use crate::input::CharStream;
+use crate::parse::Node;
+use crate::parse::NodeData;
+use crate::parse::Parser;
+use crate::parse::SymbolProperties;
+
+/// A parser for ISO-style EBNF. Its trees are rewritten by [`to_bnf_ast`]
+/// into the shape [`ParserBuilder`] builds from.
+#[allow(non_snake_case)]
+pub fn ebnf_parser() -> Parser {
+    let mut parser = Parser::new();
+
+    // TERMINALS
+    let EQUALS = parser.terminal('=', None);
+    let STATEMENT_TERM = parser.terminal(';', None);
+    let PIPE = parser.terminal('|', None);
+    let COMMA = parser.terminal(',', None);
+    let STAR = parser.terminal('*', None);
+    let DQ = parser.terminal('"', None);
+    let SQ = parser.terminal('\'', None);
+    let PAR_BEGIN = parser.terminal('(', None);
+    let PAR_END = parser.terminal(')', None);
+    let OPT_BEGIN = parser.terminal('[', None);
+    let OPT_END = parser.terminal(']', None);
+    let MANY_BEGIN = parser.terminal('{', None);
+    let MANY_END = parser.terminal('}', None);
+    let LC_CHAR = parser.one_of_chars("abcdefghijklmnopqrstuvwxyz", None);
+    let NAME_START = parser.one_of_chars(
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_",
+        None,
+    );
+    let NAME_CHAR = parser.one_of_chars(
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789_",
+        None,
+    );
+
+    // WHITESPACE AND COMMENTS
+    let WHITESPACE_CHAR = parser.one_of_chars(" \n\r\t", None);
+    let STARS = parser.one_or_more(&STAR, None);
+    let NOT_STAR = parser.any_except(&['*'], None);
+    let NOT_STAR_OR_END = parser.any_except(&['*', ')'], None);
+    let STARS_IN_COMMENT =
+        parser.sequence(vec![&STARS, &NOT_STAR_OR_END], None);
+    let COMMENT_CHAR = parser.one_of(vec![&NOT_STAR, &STARS_IN_COMMENT], None);
+    let COMMENT_BODY = parser.zero_or_more(&COMMENT_CHAR, None);
+    let COMMENT = parser.sequence(
+        vec![&PAR_BEGIN, &STAR, &COMMENT_BODY, &STARS, &PAR_END],
+        None,
+    );
+    let S_ITEM = parser.one_of(vec![&WHITESPACE_CHAR, &COMMENT], None);
+    let S = parser.zero_or_more(&S_ITEM, None);
+
+    // PRIMARIES
+    let NAME_REST = parser.zero_or_more(&NAME_CHAR, None);
+    let NAME = parser.sequence(
+        vec![&NAME_START, &NAME_REST],
+        SymbolProperties::new("NAME", true, false),
+    );
+    let DQ_CHAR = parser.any_except(&['"'], None);
+    let DQ_CHARS = parser.one_or_more(&DQ_CHAR, None);
+    let DQ_STRING = parser.sequence(vec![&DQ, &DQ_CHARS, &DQ], None);
+    let SQ_CHAR = parser.any_except(&['\''], None);
+    let SQ_CHARS = parser.one_or_more(&SQ_CHAR, None);
+    let SQ_STRING = parser.sequence(vec![&SQ, &SQ_CHARS, &SQ], None);
+    let STRING = parser.one_of(
+        vec![&DQ_STRING, &SQ_STRING],
+        SymbolProperties::new("STRING", true, false),
+    );
+
+    // Filled in below, once the expressions nesting in it exist.
+    let ALTERNATION = parser
+        .sequence(vec![], SymbolProperties::new("ALTERNATION", false, false));
+    let GROUP = parser.sequence(
+        vec![&PAR_BEGIN, &S, &ALTERNATION, &S, &PAR_END],
+        SymbolProperties::new("GROUP", false, false),
+    );
+    let OPTION = parser.sequence(
+        vec![&OPT_BEGIN, &S, &ALTERNATION, &S, &OPT_END],
+        SymbolProperties::new("OPTION", false, false),
+    );
+    let REPEAT = parser.sequence(
+        vec![&MANY_BEGIN, &S, &ALTERNATION, &S, &MANY_END],
+        SymbolProperties::new("REPEAT", false, false),
+    );
+    let PRIMARY =
+        parser.one_of(vec![&NAME, &STRING, &GROUP, &OPTION, &REPEAT], None);
+
+    // FACTOR
+    let REPETITION = parser
+        .one_of_chars("*+?", SymbolProperties::new("REPETITION", true, false));
+    let POSTFIX = parser.sequence(vec![&S, &REPETITION], None);
+    let POSTFIX_OPT = parser.optional(&POSTFIX, None);
+    let FACTOR = parser.sequence(
+        vec![&PRIMARY, &POSTFIX_OPT],
+        SymbolProperties::new("FACTOR", false, false),
+    );
+
+    // CONCATENATION AND ALTERNATION
+    let CONCAT_ITEM = parser.sequence(vec![&S, &COMMA, &S, &FACTOR], None);
+    let CONCAT_ITEM_OPT = parser.zero_or_more(&CONCAT_ITEM, None);
+    let CONCATENATION = parser.sequence(
+        vec![&FACTOR, &CONCAT_ITEM_OPT],
+        SymbolProperties::new("CONCATENATION", false, false),
+    );
+    let ALT_ITEM = parser.sequence(vec![&S, &PIPE, &S, &CONCATENATION], None);
+    let ALT_ITEM_OPT = parser.zero_or_more(&ALT_ITEM, None);
+    parser.add_ident(&ALTERNATION, &CONCATENATION);
+    parser.add_ident(&ALTERNATION, &ALT_ITEM_OPT);
+
+    // RULE
+    let FLAG = parser
+        .one_or_more(&LC_CHAR, SymbolProperties::new("FLAG", true, false));
+    let FLAG_ITEM = parser.sequence(vec![&COMMA, &FLAG], None);
+    let FLAG_ITEM_OPT = parser.zero_or_more(&FLAG_ITEM, None);
+    let FLAGS = parser.sequence(
+        vec![&PAR_BEGIN, &FLAG, &FLAG_ITEM_OPT, &PAR_END],
+        SymbolProperties::new("FLAGS", false, false),
+    );
+    let FLAGS_OPT = parser.optional(&FLAGS, None);
+    let RULE = parser.sequence(
+        vec![
+            &S,
+            &NAME,
+            &FLAGS_OPT,
+            &S,
+            &EQUALS,
+            &S,
+            &ALTERNATION,
+            &S,
+            &STATEMENT_TERM,
+        ],
+        SymbolProperties::new("RULE", false, false),
+    );
+    let RULES = parser.zero_or_more(&RULE, None);
+    let GRAMMAR = parser.sequence(
+        vec![&RULES, &S],
+        SymbolProperties::new("GRAMMAR", false, false),
+    );
+
+    parser.root_node(&GRAMMAR);
+    parser
+}
+
+impl Parser {
+    /// Builds a parser from a grammar in ISO-style EBNF: `,` concatenates,
+    /// `|` separates alternatives, `*`, `+` and `?` repeat or make optional
+    /// what they follow, `[ ]` and `{ }` work as in BNF, strings are quoted
+    /// with `"` or `'` and `(* *)` encloses comments.
+    ///
+    /// Rule names and their flags, such as `EXPR(root)`, are written as in
+    /// BNF. Fails like [`Parser::from_bnf`].
+    pub fn from_ebnf(ebnf: &str) -> Result<Self, GrammarError> {
+        let root_node = Parser::parse_ebnf_to_ast(ebnf)?;
+        ParserBuilder::new().build(&root_node)
+    }
+
+    /// Like [`Parser::parse_bnf_to_ast`], for a grammar in EBNF. The tree
+    /// has the same shape as for the equivalent BNF grammar.
+    pub fn parse_ebnf_to_ast(ebnf: &str) -> Result<Node, GrammarError> {
+        let mut stream = CharStream::from(ebnf);
+        ebnf_parser()
+            .parse(&mut stream)
+            .map(|grammar| to_bnf_ast(&grammar))
+            .map_err(GrammarError::Syntax)
+    }
+}
+
+/// The BNF grammar tree of the EBNF grammar tree `grammar`.
+fn to_bnf_ast(grammar: &Node) -> Node {
+    let statements = get_children_of_node(grammar).iter().map(statement);
+    children(grammar, "ROOT", statements.collect())
+}
+
+fn statement(rule: &Node) -> Node {
+    let parts = get_children_of_node(rule);
+    let mut converted = vec![raw(&parts[0], "IDENTIFIER", None)];
+    if parts[1].node_type == "FLAGS" {
+        let flags = get_children_of_node(&parts[1])
+            .iter()
+            .map(|flag| raw(flag, "FLAG", None))
+            .collect();
+        converted.push(children(&parts[1], "STMT_INFO", flags));
+    }
+    converted.push(expression(parts.last().unwrap()));
+    children(rule, "STATEMENT", converted)
+}
+
+fn expression(node: &Node) -> Node {
+    let parts = match &node.data {
+        NodeData::Children(children) => &children[..],
+        NodeData::Raw(_) => &[],
+    };
+    match node.node_type.as_str() {
+        "ALTERNATION" | "CONCATENATION" if parts.len() == 1 => {
+            expression(&parts[0])
+        }
+        "ALTERNATION" => {
+            children(node, "ONE_OF", parts.iter().map(expression).collect())
+        }
+        "CONCATENATION" => {
+            children(node, "SEQUENCE", parts.iter().map(expression).collect())
+        }
+        "FACTOR" => {
+            let primary = expression(&parts[0]);
+            match parts.get(1).map(get_raw_value_of_node) {
+                None => primary,
+                Some("*") => children(node, "MANY", vec![primary]),
+                // `x+` is `x { x }`.
+                Some("+") => {
+                    let many = children(node, "MANY", vec![primary.clone()]);
+                    children(node, "SEQUENCE", vec![primary, many])
+                }
+                Some(_) => children(node, "OPTIONAL", vec![primary]),
+            }
+        }
+        "NAME" => raw(node, "IDENTIFIER", None),
+        "STRING" => {
+            let quoted = get_raw_value_of_node(node);
+            let terminals: Vec<Node> = quoted[1..quoted.len() - 1]
+                .chars()
+                .map(|c| raw(node, "TERMINAL", Some(&format!("'{}'", c))))
+                .collect();
+            if terminals.len() == 1 {
+                terminals.into_iter().next().unwrap()
+            } else {
+                children(node, "SEQUENCE", terminals)
+            }
+        }
+        "GROUP" => children(node, "GROUP", vec![expression(&parts[0])]),
+        "OPTION" => children(node, "OPTIONAL", vec![expression(&parts[0])]),
+        "REPEAT" => children(node, "MANY", vec![expression(&parts[0])]),
+        other => unreachable!("Unexpected EBNF node {}", other),
+    }
+}
+
+/// A node of type `node_type` at the place of `node`, with `nodes` as its
+/// children.
+fn children(node: &Node, node_type: &str, nodes: Vec<Node>) -> Node {
+    Node::new(node_type, node.loc.clone(), NodeData::Children(nodes))
+}
+
+/// A raw node of type `node_type` at the place of `node`, holding `text` or
+/// the text of `node`.
+fn raw(node: &Node, node_type: &str, text: Option<&str>) -> Node {
+    let text = text.unwrap_or_else(|| get_raw_value_of_node(node));
+    Node::new(node_type, node.loc.clone(), NodeData::Raw(text.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARITH_BNF: &str = r#"
+EXPR(root) = TERM { OP TERM };
+TERM = NUMBER | ( '(' EXPR ')' );
+OP(raw) = '+' | '*';
+NUMBER(raw) = DIGIT { DIGIT };
+DIGIT(ignore) = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9';
+"#;
+
+    const ARITH_EBNF: &str = r#"
+(* The arithmetic grammar of the BNF tests. *)
+EXPR(root) = TERM, { OP, TERM };
+TERM = NUMBER | "(", EXPR, ")";
+OP(raw) = "+" | '*';
+NUMBER(raw) = DIGIT+;
+DIGIT(ignore) = "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9";
+"#;
+
+    fn raw_of(node: &Node) -> &str {
+        get_raw_value_of_node(node)
+    }
+
+    #[test]
+    fn arith() {
+        let bnf = Parser::from_bnf(ARITH_BNF).unwrap();
+        let ebnf = Parser::from_ebnf(ARITH_EBNF).unwrap();
+
+        for input in ["1", "12+3", "(1+2)*34", "((7))", "1+", "(1", "a"] {
+            assert_eq!(
+                bnf.parse(&mut CharStream::from(input)),
+                ebnf.parse(&mut CharStream::from(input)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn postfix_operators() {
+        let parser = Parser::from_ebnf(
+            "LIST(root) = '[', [ ITEM, ( ',', ITEM )* ], ']';\n\
+             ITEM(raw) = SIGN?, 'x'+;\n\
+             SIGN = '-';",
+        )
+        .unwrap();
+
+        let list = parser.parse(&mut CharStream::from("[x,-xx]")).unwrap();
+        let items = get_children_of_node(&list);
+        assert_eq!(
+            vec!["x", "-xx"],
+            items.iter().map(raw_of).collect::<Vec<_>>()
+        );
+        assert!(parser.parse(&mut CharStream::from("[]")).is_ok());
+        assert!(parser.parse(&mut CharStream::from("[-]")).is_err());
+    }
+
+    #[test]
+    fn comments_and_strings() {
+        let parser = Parser::from_ebnf(
+            "(* a (* b ** *)\nKEYWORD(root,raw) = (* c *) \"let\" | 'it\"s';",
+        )
+        .unwrap();
+
+        assert!(parser.parse(&mut CharStream::from("let")).is_ok());
+        assert!(parser.parse(&mut CharStream::from("it\"s")).is_ok());
+        assert!(parser.parse(&mut CharStream::from("(* c *)let")).is_err());
+        assert!(matches!(
+            Parser::parse_ebnf_to_ast("A(root) = 'a' 'b';"),
+            Err(GrammarError::Syntax(_))
+        ));
+        assert!(matches!(
+            Parser::from_ebnf("A(root) = B;"),
+            Err(GrammarError::UndeclaredRule { .. })
+        ));
+    }
+}
@@ -0,0 +1,449 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::build::GrammarError;
+use crate::parse::bounds;
+use crate::parse::char_after;
+use crate::parse::char_before;
+use crate::parse::Parser;
+use crate::parse::Symbol;
+use crate::parse::SymbolIdent;
+use crate::parse::SymbolType;
+
+impl Parser {
+    /// Renders the grammar reachable from the root as BNF that
+    /// [`Parser::from_bnf`] accepts.
+    ///
+    /// The result accepts the same inputs, but is normalized rather than a
+    /// copy of the original source:
+    /// - every symbol that is not a plain char or char range becomes its own
+    ///   rule, unnamed ones as `(ignore)` rules called `RULE_<letters>`,
+    /// - symbols whose name is not a valid identifier or is shared with
+    ///   another symbol get such a generated name too,
+    /// - `OneOrMore(X)` is written as `X { X }`,
+    /// - chars the grammar excludes are written as the ranges it includes.
+    ///
+    /// Fails with [`GrammarError::Unrepresentable`] for symbols the BNF
+    /// dialect cannot express: `EndOfInput`, `Balanced`, `NestedComment`,
+    /// `Lookbehind`, sequences and alternatives with fewer than two elements
+    /// and chars that exclude everything.
+    pub fn to_bnf(&self) -> Result<String, GrammarError> {
+        let root = self.root_node.ok_or(GrammarError::NoRoot)?;
+        let rules = self.reachable_rules(root);
+        let idents = self.rule_idents(&rules);
+
+        let mut bnf = String::new();
+        for ident in &rules {
+            let symbol = self.get_symbol(ident);
+            let mut flags = Vec::new();
+            if *ident == root {
+                flags.push("root".to_owned());
+            }
+            if symbol.properties.raw {
+                flags.push("raw".to_owned());
+            }
+            if symbol.properties.ignore {
+                flags.push("ignore".to_owned());
+            }
+            if symbol.properties.memoize {
+                flags.push("memo".to_owned());
+            }
+            if symbol.properties.is_list {
+                flags.push("list".to_owned());
+            }
+            if symbol.properties.tight {
+                flags.push("tight".to_owned());
+            }
+            if let Some(message) = &symbol.properties.error_message {
+                if !message.contains('"') {
+                    flags.push(format!("error=\"{}\"", message));
+                }
+            }
+            let flags = if flags.is_empty() {
+                String::new()
+            } else {
+                format!("({})", flags.join(","))
+            };
+            let body = self.rule_body(ident, &idents)?;
+            if let Some(doc) = &symbol.properties.doc {
+                for line in doc.lines() {
+                    bnf.push_str(&format!("# {}\n", line));
+                }
+            }
+            bnf.push_str(&format!("{}{} = {};\n", idents[ident], flags, body));
+        }
+        Ok(bnf)
+    }
+
+    /// Symbols that need a rule of their own, root first.
+    fn reachable_rules(&self, root: SymbolIdent) -> Vec<SymbolIdent> {
+        let mut rules = Vec::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![root];
+        while let Some(ident) = stack.pop() {
+            if !seen.insert(ident) {
+                continue;
+            }
+            let symbol = self.get_symbol(&ident);
+            if ident != root && inline(symbol).is_some() {
+                continue;
+            }
+            rules.push(ident);
+            stack.extend(symbol.sub_symbols().iter().rev());
+        }
+        rules
+    }
+
+    fn rule_idents(
+        &self,
+        rules: &[SymbolIdent],
+    ) -> HashMap<SymbolIdent, String> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for ident in rules {
+            if let Some(name) = &self.get_symbol(ident).properties.node_name {
+                *counts.entry(name).or_default() += 1;
+            }
+        }
+        let mut idents = HashMap::new();
+        for ident in rules {
+            let name = self.get_symbol(ident).properties.node_name.as_deref();
+            let ident_name = match name {
+                Some(name) if counts[name] == 1 && is_identifier(name) => {
+                    name.to_owned()
+                }
+                _ => {
+                    let mut generated = format!("RULE_{}", letters(ident.0));
+                    while counts.contains_key(generated.as_str()) {
+                        generated.push('_');
+                    }
+                    generated
+                }
+            };
+            idents.insert(*ident, ident_name);
+        }
+        idents
+    }
+
+    fn rule_body(
+        &self,
+        ident: &SymbolIdent,
+        idents: &HashMap<SymbolIdent, String>,
+    ) -> Result<String, GrammarError> {
+        let reference = |id: &SymbolIdent| match idents.get(id) {
+            Some(name) => name.clone(),
+            None => inline(self.get_symbol(id)).unwrap(),
+        };
+        let unrepresentable = GrammarError::Unrepresentable(*ident);
+        let symbol = self.get_symbol(ident);
+        let body = match &symbol.symbol_type {
+            SymbolType::Sequence(s) if s.len() >= 2 => {
+                s.iter().map(reference).collect::<Vec<_>>().join(" ")
+            }
+            SymbolType::OneOf(s) if s.len() >= 2 => {
+                s.iter().map(reference).collect::<Vec<_>>().join(" | ")
+            }
+            SymbolType::Optional(id) => format!("[ {} ]", reference(id)),
+            SymbolType::And(id) => format!("&{}", reference(id)),
+            SymbolType::Not(id) => format!("!{}", reference(id)),
+            SymbolType::ZeroOrMore(id) => format!("{{ {} }}", reference(id)),
+            SymbolType::OneOrMore(id) => {
+                format!("{} {{ {} }}", reference(id), reference(id))
+            }
+            SymbolType::Repeat { symbol, min, max } => {
+                format!("{{ {} }}{}", reference(symbol), bounds(*min, *max))
+            }
+            SymbolType::Terminal(c) => format!("'{}'", c),
+            SymbolType::AnyExcept(ranges) => {
+                let included = included_ranges(ranges);
+                if included.is_empty() {
+                    return Err(unrepresentable);
+                }
+                included
+                    .iter()
+                    .map(|(from, to)| char_range(*from, *to))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            }
+            SymbolType::Literal(s) if !s.is_empty() => s
+                .chars()
+                .map(|c| format!("'{}'", c))
+                .collect::<Vec<_>>()
+                .join(" "),
+            SymbolType::RestOfLine => "$EOL_TEXT".to_owned(),
+            SymbolType::Take(n) => format!(".{{{}}}", n),
+            SymbolType::LiteralSet(trie) => trie
+                .words()
+                .iter()
+                .map(|s| {
+                    let chars: Vec<String> =
+                        s.chars().map(|c| format!("'{}'", c)).collect();
+                    if chars.len() == 1 {
+                        chars[0].clone()
+                    } else {
+                        format!("( {} )", chars.join(" "))
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" | "),
+            SymbolType::CharSet(ranges) if !ranges.is_empty() => ranges
+                .iter()
+                .map(|(from, to)| char_range(*from, *to))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            _ => return Err(unrepresentable),
+        };
+        Ok(body)
+    }
+}
+
+/// How an unnamed char or single char range is written in place, `None`
+/// for symbols that need a rule.
+fn inline(symbol: &Symbol) -> Option<String> {
+    if symbol.properties.node_name.is_some() {
+        return None;
+    }
+    match &symbol.symbol_type {
+        SymbolType::Terminal(c) => Some(format!("'{}'", c)),
+        SymbolType::AnyExcept(ranges) => match included_ranges(ranges)[..] {
+            [(from, to)] => Some(char_range(from, to)),
+            _ => None,
+        },
+        SymbolType::CharSet(ranges) => match ranges[..] {
+            [(from, to)] => Some(char_range(from, to)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn char_range(from: char, to: char) -> String {
+    if from == to {
+        format!("'{}'", from)
+    } else {
+        format!("'{}'..'{}'", from, to)
+    }
+}
+
+/// The complement of sorted, merged `excluded` ranges.
+pub(crate) fn included_ranges(excluded: &[(char, char)]) -> Vec<(char, char)> {
+    let mut included = Vec::new();
+    let mut next = Some('\0');
+    for (from, to) in excluded {
+        if let Some(start) = next {
+            if start < *from {
+                // `from` is above `start`, so it has a predecessor.
+                included.push((start, char_before(*from).unwrap()));
+            }
+        }
+        next = char_after(*to);
+    }
+    if let Some(start) = next {
+        included.push((start, char::MAX));
+    }
+    included
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+}
+
+/// `0` -> `A`, `25` -> `Z`, `26` -> `AA`, ...
+fn letters(mut n: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push((b'A' + (n % 26) as u8) as char);
+        if n < 26 {
+            break;
+        }
+        n = n / 26 - 1;
+    }
+    letters.iter().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+    use crate::input::CharStream;
+
+    #[test]
+    fn json_round_trip() {
+        let json = json_parser();
+        let bnf = json.to_bnf().unwrap();
+        let reparsed = Parser::from_bnf(&bnf).unwrap();
+
+        for input in [
+            "[]",
+            r#"{"a": [1, -2.5e+3, true, null], "b": "é\n"}"#,
+            "[1,]",
+            "01",
+            r#"{"a" 1}"#,
+        ] {
+            assert_eq!(
+                json.parse(&mut CharStream::from(input)).is_ok(),
+                reparsed.parse(&mut CharStream::from(input)).is_ok(),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn unrepresentable() {
+        let mut parser = Parser::new();
+        let a = parser.terminal('a', None);
+        let eof = parser.end_of_input(None);
+        let root = parser.sequence(
+            vec![&a, &eof],
+            crate::parse::SymbolProperties::new("ROOT", false, false),
+        );
+        parser.root_node(&root);
+
+        assert_eq!(Err(GrammarError::Unrepresentable(eof)), parser.to_bnf());
+        assert_eq!(Err(GrammarError::NoRoot), Parser::new().to_bnf());
+    }
+
+    #[test]
+    fn generated_names() {
+        assert_eq!("A", letters(0));
+        assert_eq!("Z", letters(25));
+        assert_eq!("AA", letters(26));
+        assert_eq!("BA", letters(52));
+        assert_eq!(
+            vec![('\0', '`'), ('{', char::MAX)],
+            included_ranges(&[('a', 'z')])
+        );
+    }
+
+    mod round_trip {
+        use proptest::prelude::*;
+
+        use super::*;
+        use crate::parse::SymbolProperties;
+
+        #[derive(Clone, Debug)]
+        enum Expr {
+            Char(char),
+            Except(Vec<char>),
+            Seq(Vec<Expr>, bool),
+            OneOf(Vec<Expr>, bool),
+            Optional(Box<Expr>),
+            Many(Box<Expr>),
+            Plus(Box<Expr>),
+        }
+
+        impl Expr {
+            fn nullable(&self) -> bool {
+                match self {
+                    Expr::Char(_) | Expr::Except(_) => false,
+                    Expr::Seq(s, _) => s.iter().all(Expr::nullable),
+                    Expr::OneOf(s, _) => s.iter().any(Expr::nullable),
+                    Expr::Optional(_) | Expr::Many(_) => true,
+                    Expr::Plus(e) => e.nullable(),
+                }
+            }
+        }
+
+        fn expr() -> impl Strategy<Value = Expr> {
+            let chars = prop::sample::select(vec!['a', 'b', 'c']);
+            let leaf = prop_oneof![
+                chars.clone().prop_map(Expr::Char),
+                prop::collection::vec(chars, 1..3).prop_map(Expr::Except),
+            ];
+            leaf.prop_recursive(4, 24, 3, |inner| {
+                let items = prop::collection::vec(inner.clone(), 2..4);
+                prop_oneof![
+                    (items.clone(), any::<bool>())
+                        .prop_map(|(s, named)| Expr::Seq(s, named)),
+                    (items, any::<bool>())
+                        .prop_map(|(s, named)| Expr::OneOf(s, named)),
+                    inner.clone().prop_map(|e| Expr::Optional(Box::new(e))),
+                    inner.clone().prop_map(|e| Expr::Many(Box::new(e))),
+                    inner.prop_map(|e| Expr::Plus(Box::new(e))),
+                ]
+            })
+        }
+
+        fn build(parser: &mut Parser, expr: &Expr) -> SymbolIdent {
+            let props = |parser: &Parser, named: bool| {
+                let name =
+                    format!("NAMED_{}", letters(parser.symbol_registry.len()));
+                if named {
+                    SymbolProperties::new(&name, false, false)
+                } else {
+                    None
+                }
+            };
+            match expr {
+                Expr::Char(c) => parser.terminal(*c, None),
+                Expr::Except(chars) => parser.any_except(chars, None),
+                Expr::Seq(s, named) => {
+                    let ids: Vec<_> =
+                        s.iter().map(|e| build(parser, e)).collect();
+                    let props = props(parser, *named);
+                    parser.sequence(ids.iter().collect(), props)
+                }
+                Expr::OneOf(s, named) => {
+                    let ids: Vec<_> =
+                        s.iter().map(|e| build(parser, e)).collect();
+                    let props = props(parser, *named);
+                    parser.one_of(ids.iter().collect(), props)
+                }
+                Expr::Optional(e) => {
+                    let id = build(parser, e);
+                    parser.optional(&id, None)
+                }
+                Expr::Many(e) | Expr::Plus(e) => {
+                    // Repeating a nullable body never terminates, so make
+                    // sure every iteration consumes something.
+                    let body = match e.nullable() {
+                        true => {
+                            Expr::Seq(vec![Expr::Char('a'), *e.clone()], false)
+                        }
+                        false => *e.clone(),
+                    };
+                    let id = build(parser, &body);
+                    match expr {
+                        Expr::Many(_) => parser.zero_or_more(&id, None),
+                        _ => parser.one_or_more(&id, None),
+                    }
+                }
+            }
+        }
+
+        fn accepts(parser: &Parser, input: &str) -> bool {
+            parser.parse(&mut CharStream::from(input)).is_ok()
+        }
+
+        proptest! {
+            #[test]
+            fn accepts_same_inputs(
+                first in expr(),
+                second in expr(),
+                mut inputs in prop::collection::vec("[abcd]{0,6}", 24),
+            ) {
+                let mut parser = Parser::new();
+                let first = build(&mut parser, &first);
+                let second = build(&mut parser, &second);
+                let root = parser.sequence(
+                    vec![&first, &second],
+                    SymbolProperties::new("ROOT", false, false),
+                );
+                parser.root_node(&root);
+                let bnf = parser.to_bnf().unwrap();
+                let reparsed = Parser::from_bnf(&bnf).unwrap();
+
+                inputs.extend(parser.shortest_match(&root));
+                for input in inputs {
+                    prop_assert_eq!(
+                        accepts(&parser, &input),
+                        accepts(&reparsed, &input),
+                        "{:?} with\n{}",
+                        input,
+                        bnf
+                    );
+                }
+            }
+        }
+    }
+}
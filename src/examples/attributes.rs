@@ -0,0 +1,120 @@
+use crate::parse::Node;
+use crate::parse::NodeData;
+use crate::parse::Parser;
+use crate::parse::SymbolProperties;
+
+const WHITESPACE: &str = " \t\r\n";
+
+/// A parser for `key <kv_sep> value <pair_sep> key <kv_sep> value ...`, such
+/// as `a=1,b=2` or `a: 1; b: 2`. Whitespace is allowed around separators and
+/// at both ends, keys and values are words without whitespace or separators.
+/// A whitespace separator, such as a newline, may also end the input.
+///
+/// The root `ATTRIBUTES` node has one `PAIR` child per pair, each with a raw
+/// `KEY` and a raw `VALUE`, see [`pairs_from_node`].
+pub fn attribute_parser(kv_sep: char, pair_sep: char) -> Parser {
+    let mut parser = Parser::new();
+    let spaces: String = WHITESPACE
+        .chars()
+        .filter(|c| *c != kv_sep && *c != pair_sep)
+        .collect();
+    let space = parser.one_of_chars(&spaces, None);
+    let ws = parser.zero_or_more(&space, None);
+    let any_space = parser.one_of_chars(WHITESPACE, None);
+    let outer_ws = parser.zero_or_more(&any_space, None);
+
+    let mut excluded: Vec<char> = WHITESPACE.chars().collect();
+    excluded.extend([kv_sep, pair_sep]);
+    let word = parser.any_except(&excluded, None);
+    let key =
+        parser.one_or_more(&word, SymbolProperties::new("KEY", true, false));
+    let value =
+        parser.one_or_more(&word, SymbolProperties::new("VALUE", true, false));
+
+    let kv_sep = parser.terminal(kv_sep, None);
+    let pair = parser.sequence(
+        vec![&key, &ws, &kv_sep, &ws, &value],
+        SymbolProperties::new("PAIR", false, false),
+    );
+    let pair_sep = parser.terminal(pair_sep, None);
+    let next = parser.sequence(vec![&ws, &pair_sep, &ws, &pair], None);
+    let rest = parser.zero_or_more(&next, None);
+    let root = parser.sequence(
+        vec![&outer_ws, &pair, &rest, &outer_ws],
+        SymbolProperties::new("ATTRIBUTES", false, false),
+    );
+    parser.root_node(&root);
+    parser
+}
+
+/// The key and value of every `PAIR` below `node`, in input order.
+pub fn pairs_from_node(node: &Node) -> Vec<(String, String)> {
+    let children = match &node.data {
+        NodeData::Children(c) => c,
+        NodeData::Raw(_) => return Vec::new(),
+    };
+    children
+        .iter()
+        .filter(|n| n.node_type == "PAIR")
+        .filter_map(|pair| match (pair.field("KEY"), pair.field("VALUE")) {
+            (Some(key), Some(value)) => Some((raw(key)?, raw(value)?)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn raw(node: &Node) -> Option<String> {
+    match &node.data {
+        NodeData::Raw(raw) => Some(raw.clone()),
+        NodeData::Children(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::CharStream;
+
+    fn pairs(parser: &Parser, input: &str) -> Vec<(String, String)> {
+        let node = parser.parse(&mut CharStream::from(input)).unwrap();
+        pairs_from_node(&node)
+    }
+
+    fn owned(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn equals_comma() {
+        let parser = attribute_parser('=', ',');
+
+        assert_eq!(owned(&[("a", "1"), ("b", "2")]), pairs(&parser, "a=1,b=2"));
+        assert_eq!(owned(&[("a", "1")]), pairs(&parser, " a = 1 "));
+        assert!(parser.parse(&mut CharStream::from("a=1,")).is_err());
+        assert!(parser.parse(&mut CharStream::from("a 1")).is_err());
+    }
+
+    #[test]
+    fn colon_semicolon() {
+        let parser = attribute_parser(':', ';');
+
+        assert_eq!(
+            owned(&[("a", "1"), ("b", "2")]),
+            pairs(&parser, "a: 1; b: 2")
+        );
+        assert!(parser.parse(&mut CharStream::from("a=1,b=2")).is_err());
+    }
+
+    #[test]
+    fn newline_separated() {
+        let parser = attribute_parser('=', '\n');
+
+        assert_eq!(
+            owned(&[("host", "localhost"), ("port", "80")]),
+            pairs(&parser, "host = localhost\nport = 80\n")
+        );
+    }
+}
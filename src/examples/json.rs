@@ -2,7 +2,7 @@ use crate::parse::Parser;
 
 const JSON_BNF: &str = r#"
 
-ROOT(root) = [ VALUE ];
+ROOT(root) = $WHITESPACE VALUE;
 VALUE(ignore) = $WHITESPACE (STRING | NUMBER | OBJECT | ARRAY | TRUE | FALSE | NULL) $WHITESPACE;
 
 TRUE(raw) = 't' 'r' 'u' 'e';
@@ -11,14 +11,14 @@ NULL(raw) = 'n' 'u' 'l' 'l';
 
 NUMBER(raw) = [ '-' ] ( '0' | NUMBER_NONZERO ) [ NUMBER_F ] [ NUMBER_E ];
 NUMBER_NONZERO(ignore) = DIGIT_NONZERO { DIGIT };
-NUMBER_F(ignore) = '.' { DIGIT };
-NUMBER_E(ignore) = ( 'E' | 'e' ) [ '-' | '+' ] { DIGIT };
+NUMBER_F(ignore) = '.' DIGIT { DIGIT };
+NUMBER_E(ignore) = ( 'E' | 'e' ) [ '-' | '+' ] DIGIT { DIGIT };
 DIGIT(ignore) = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9';
 DIGIT_NONZERO(ignore) = '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9';
 
-STRING(raw) = '"' { * | ESCAPED } '"';
-ESCAPED(ignore) = '\' ('"' | '\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | ('u' HEX_DIGIT HEX_DIGIT HEX_DIGIT HEX_DIGIT));
-HEX_DIGIT(ignore) = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' | 'A' | 'B' | 'C' | 'D' | 'E' | 'F';
+STRING(raw) = '"' { *('"', '\', U+0..U+1F) | ESCAPED } '"';
+ESCAPED(ignore) = '\' ('"' | '\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | ('u' { HEX_DIGIT }<4>));
+HEX_DIGIT(ignore) = '0' | '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' | 'A' | 'B' | 'C' | 'D' | 'E' | 'F' | 'a' | 'b' | 'c' | 'd' | 'e' | 'f';
 
 ARRAY = ( '[' $WHITESPACE ']' ) | ( '[' VALUE { ',' VALUE } ']' );
 
@@ -28,5 +28,437 @@ PROPERTY = $WHITESPACE STRING $WHITESPACE ':' VALUE;
 "#;
 
 pub fn json_parser() -> Parser {
-    Parser::from_bnf(JSON_BNF)
+    Parser::from_bnf(JSON_BNF).expect("JSON grammar is valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::input::CharStream;
+    use crate::input::Position;
+    use crate::parse::Node;
+    use crate::parse::NodeData;
+    use crate::parse::ParseError;
+
+    #[test]
+    fn numbers() {
+        let parser = json_parser();
+        let accepts =
+            |input: &str| parser.parse(&mut CharStream::from(input)).is_ok();
+
+        for ok in ["0", "-0", "1.0e+10", "-0.5E-3", "10", "1e5", "2.25"] {
+            assert!(accepts(ok), "{} should be accepted", ok);
+        }
+        for err in ["1e", "01", "1.", ".5", "-", "1.e5", "1e+", "+1"] {
+            assert!(!accepts(err), "{} should be rejected", err);
+        }
+    }
+
+    // Cases taken from JSONTestSuite (github.com/nst/JSONTestSuite), named
+    // after their files there. `y_` must parse, `n_` must not.
+    const ACCEPT: &[(&str, &str)] = &[
+        ("y_array_empty", "[]"),
+        ("y_array_heterogeneous", r#"[null, 1, "1", {}]"#),
+        ("y_array_with_several_null", "[1,null,null,null,2]"),
+        ("y_number_0e+1", "[0e+1]"),
+        ("y_number_negative_zero", "[-0]"),
+        ("y_number_real_capital_e_neg_exp", "[1E-2]"),
+        ("y_object_duplicated_key", r#"{"a":"b","a":"c"}"#),
+        ("y_object_empty_key", r#"{"":0}"#),
+        ("y_object_nested", r#"{"a":{"b":[{}]}}"#),
+        ("y_string_allowed_escapes", r#"["\"\\\/\b\f\n\r\t"]"#),
+        ("y_string_escaped_noncharacter", r#"["\uFFFF"]"#),
+        ("y_string_unicode_escaped_lowercase", r#"["\u00e9"]"#),
+        ("y_string_surrogates_U+1D11E", r#"["\uD834\uDd1e"]"#),
+        ("y_string_utf8", "[\"\u{20AC}\u{1D11E}\"]"),
+        ("y_structure_lonely_false", "false"),
+        ("y_structure_lonely_string", r#""asd""#),
+        ("y_structure_whitespace_array", " [] "),
+        ("y_structure_trailing_newline", "[\"a\"]\n"),
+        (
+            "y_nested_arrays",
+            "[[[[[[[[[[[[[[[[[[[[1]]]]]]]]]]]]]]]]]]]]",
+        ),
+    ];
+
+    const REJECT: &[(&str, &str)] = &[
+        ("n_array_comma_after_close", "[\"\"],"),
+        ("n_array_extra_comma", "[\"\",]"),
+        ("n_array_just_comma", "[,]"),
+        ("n_array_missing_value", "[   , \"\"]"),
+        ("n_array_unclosed", "[\"\""),
+        ("n_array_items_separated_by_semicolon", "[1:2]"),
+        ("n_number_-01", "[-01]"),
+        ("n_number_hex_1_digit", "[0x1]"),
+        ("n_number_infinity", "[Infinity]"),
+        ("n_number_NaN", "[NaN]"),
+        ("n_number_real_without_fractional_part", "[1.]"),
+        ("n_number_starting_with_dot", "[.123]"),
+        ("n_object_missing_colon", r#"{"a" b}"#),
+        ("n_object_missing_value", r#"{"a":"#),
+        ("n_object_non_string_key", "{1:1}"),
+        ("n_object_single_quote", "{'a':0}"),
+        ("n_object_trailing_comma", r#"{"id":0,}"#),
+        ("n_string_escape_x", r#"["\x00"]"#),
+        ("n_string_incomplete_escaped_character", r#"["\u00A"]"#),
+        ("n_string_single_quote", "['single quote']"),
+        ("n_string_unescaped_tab", "[\"\t\"]"),
+        ("n_string_unescaped_newline", "[\"new\nline\"]"),
+        ("n_structure_no_data", ""),
+        ("n_structure_whitespace_only", " "),
+        ("n_structure_double_array", "[][]"),
+        ("n_structure_trailing_garbage", "[1]x"),
+        ("n_structure_UTF8_BOM_no_data", "\u{FEFF}"),
+        ("n_incomplete_true", "[tru]"),
+    ];
+
+    #[test]
+    fn json_test_suite() {
+        let parser = json_parser();
+        let accepts =
+            |input: &str| parser.parse(&mut CharStream::from(input)).is_ok();
+
+        for (name, input) in ACCEPT {
+            assert!(accepts(input), "{} should be accepted", name);
+        }
+        for (name, input) in REJECT {
+            assert!(!accepts(input), "{} should be rejected", name);
+        }
+    }
+
+    // Implementation-defined (`i_`) cases: the grammar checks syntax only, so
+    // huge numbers and lone surrogate escapes are accepted, while a leading
+    // byte order mark is not whitespace and is rejected.
+    #[test]
+    fn implementation_defined() {
+        let parser = json_parser();
+        let accepts =
+            |input: &str| parser.parse(&mut CharStream::from(input)).is_ok();
+
+        assert!(accepts("[1e309]"));
+        assert!(accepts(r#"["\uD800"]"#));
+        assert!(!accepts("\u{FEFF}[]"));
+    }
+
+    #[test]
+    fn duplicate_keys_hook() {
+        let mut parser = json_parser();
+        parser.on_complete(
+            "OBJECT",
+            Box::new(|object| {
+                let mut keys = HashSet::new();
+                let NodeData::Children(properties) = &object.data else {
+                    return Ok(());
+                };
+                for property in properties {
+                    if let Some(NodeData::Raw(key)) =
+                        property.field("STRING").map(|n| &n.data)
+                    {
+                        if !keys.insert(key.clone()) {
+                            return Err(format!("Duplicate key {}", key));
+                        }
+                    }
+                }
+                Ok(())
+            }),
+        );
+
+        assert!(parser
+            .parse(&mut CharStream::from(r#"{"a":1,"b":2}"#))
+            .is_ok());
+        match parser.parse(&mut CharStream::from(r#"[{"a":1,"a":2}]"#)) {
+            Err(ParseError::Rejected { message, loc }) => {
+                assert_eq!(r#"Duplicate key "a""#, message);
+                assert_eq!(1..14, loc.byte_span());
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    fn count_nodes(node: &Node) -> usize {
+        match &node.data {
+            NodeData::Children(c) => {
+                1 + c.iter().map(count_nodes).sum::<usize>()
+            }
+            NodeData::Raw(_) => 1,
+        }
+    }
+
+    #[test]
+    fn concrete_syntax_tree() {
+        let parser = json_parser();
+        let input = r#"{"a": [1, 2]}"#;
+
+        let ast = parser.parse(&mut CharStream::from(input)).unwrap();
+        let cst = parser.parse_cst(&mut CharStream::from(input)).unwrap();
+
+        // ROOT, OBJECT, PROPERTY, STRING, ARRAY and two NUMBERs.
+        assert_eq!(7, count_nodes(&ast));
+        assert!(count_nodes(&cst) > count_nodes(&ast));
+        assert!(ast.field("VALUE").is_none());
+        let value = cst.field("VALUE").unwrap();
+        assert_eq!(0..input.len(), value.loc.byte_span());
+    }
+
+    #[test]
+    fn iterators() {
+        let parser = json_parser();
+        let input = r#"{"a": [1, 2, {"b": 3}], "c": "d"}"#;
+        let tree = parser.parse(&mut CharStream::from(input)).unwrap();
+
+        let count = |node_type: &str| {
+            tree.descendants()
+                .filter(|n| n.node_type == node_type)
+                .count()
+        };
+        assert_eq!(3, count("NUMBER"));
+        assert_eq!(4, count("STRING"));
+        assert_eq!(3, count("PROPERTY"));
+        assert_eq!(count_nodes(&tree), tree.descendants().count());
+
+        let types: Vec<&str> = tree
+            .descendants()
+            .take(4)
+            .map(|n| n.node_type.as_str())
+            .collect();
+        assert_eq!(vec!["ROOT", "OBJECT", "PROPERTY", "STRING"], types);
+        let object = tree.field("OBJECT").unwrap();
+        assert_eq!(2, object.iter_children().count());
+        let string = object.descendants().nth(2).unwrap();
+        assert_eq!(0, string.iter_children().count());
+    }
+
+    #[test]
+    fn typed_numbers() {
+        let mut parser = json_parser();
+        parser.parse_as::<i32>("NUMBER");
+
+        let tree = parser
+            .parse(&mut CharStream::from("[1, -20, 300]"))
+            .unwrap();
+        let numbers: Vec<i32> = tree
+            .descendants()
+            .filter_map(|n| n.value::<i32>().copied())
+            .collect();
+        assert_eq!(vec![1, -20, 300], numbers);
+        let number = tree.descendants().find(|n| n.node_type == "NUMBER");
+        assert_eq!(None, number.unwrap().value::<i64>());
+
+        match parser.parse(&mut CharStream::from("[1, 99999999999]")) {
+            Err(ParseError::Rejected { message, loc }) => {
+                assert_eq!(
+                    "Cannot convert \"99999999999\" to i32: \
+                     number too large to fit in target type",
+                    message
+                );
+                assert_eq!(4..15, loc.byte_span());
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn list_flag() {
+        let bnf = JSON_BNF.replace("ARRAY = ", "ARRAY(list) = ");
+        let parser = Parser::from_bnf(&bnf).unwrap();
+        let tree = parser
+            .parse(&mut CharStream::from(r#"[1, "a", [true]]"#))
+            .unwrap();
+
+        let array = tree.field("ARRAY").unwrap();
+        let types: Vec<&str> = array
+            .as_list()
+            .unwrap()
+            .iter()
+            .map(|n| n.node_type.as_str())
+            .collect();
+        assert_eq!(vec!["NUMBER", "STRING", "ARRAY"], types);
+        assert!(tree.as_list().is_none());
+        assert!(json_parser()
+            .parse(&mut CharStream::from("[1]"))
+            .unwrap()
+            .field("ARRAY")
+            .unwrap()
+            .as_list()
+            .is_none());
+    }
+
+    #[test]
+    fn sexpr() {
+        let parser = json_parser();
+        let parse = |input: &str| {
+            parser
+                .parse(&mut CharStream::from(input))
+                .unwrap()
+                .to_sexpr()
+        };
+
+        assert_eq!(
+            r#"(ROOT (ARRAY (ARRAY (ARRAY (NUMBER "1")))))"#,
+            parse("[[[1]]]")
+        );
+        assert_eq!(
+            r#"(ROOT (OBJECT (PROPERTY (STRING "\"(\\\")\"") (NULL "null"))))"#,
+            parse(r#"{"(\")": null}"#)
+        );
+    }
+
+    #[test]
+    fn node_len() {
+        let parser = json_parser();
+        let input = r#"["é\"", ""]"#;
+        let node = parser.parse(&mut CharStream::from(input)).unwrap();
+        let strings: Vec<&Node> = node
+            .descendants()
+            .filter(|n| n.node_type == "STRING")
+            .collect();
+
+        assert_eq!(5, strings[0].len());
+        assert_eq!(6, strings[0].loc.byte_span().len());
+        assert!(!strings[0].is_empty());
+        assert_eq!(2, strings[1].len());
+        assert!(strings
+            .iter()
+            .all(|s| s.len() == input[s.loc.byte_span()].chars().count()));
+    }
+
+    #[test]
+    fn prefixed_node_types() {
+        let mut parser = json_parser();
+        parser.on_complete("TRUE", Box::new(|_| Err("no".to_owned())));
+        parser.parse_as::<u32>("NUMBER");
+        parser.prefix_node_types("json.");
+
+        let node = parser
+            .parse(&mut CharStream::from(r#"{"a": [1, false]}"#))
+            .unwrap();
+        let types: HashSet<&str> =
+            node.descendants().map(|n| n.node_type.as_str()).collect();
+        assert_eq!(
+            HashSet::from([
+                "json.ROOT",
+                "json.OBJECT",
+                "json.PROPERTY",
+                "json.STRING",
+                "json.ARRAY",
+                "json.NUMBER",
+                "json.FALSE",
+            ]),
+            types
+        );
+        assert!(parser.find_symbol("json.VALUE").is_none());
+        let number = node
+            .descendants()
+            .find(|n| n.node_type == "json.NUMBER")
+            .unwrap();
+        assert_eq!(Some(&1), number.value::<u32>());
+        assert!(matches!(
+            parser.parse(&mut CharStream::from("[true]")),
+            Err(ParseError::Rejected { .. })
+        ));
+    }
+
+    #[test]
+    fn node_at() {
+        let parser = json_parser();
+        let input = "{\"a\": [1, 23],\n \"b\": null}";
+        let node = parser.parse(&mut CharStream::from(input)).unwrap();
+        let at = |idx: usize| {
+            let mut stream = CharStream::from(input);
+            for _ in 0..idx {
+                stream.next();
+            }
+            node.node_at(stream.get_pos()).map(|n| n.loc.byte_span())
+        };
+
+        assert_eq!(Some(10..12), at(11));
+        assert_eq!(Some(6..13), at(12));
+        assert_eq!(Some(1..13), at(4));
+        let null = node.node_at(Position::new(2, 7, 21, 21)).unwrap();
+        assert_eq!("NULL", null.node_type);
+        assert_eq!(
+            "OBJECT",
+            node.node_at(Position::new(1, 1, 0, 0)).unwrap().node_type
+        );
+        assert_eq!(Some(0..input.len()), at(0));
+        assert_eq!(None, at(input.len()));
+    }
+
+    #[test]
+    fn path_to() {
+        let parser = json_parser();
+        let input = "[1, [2, [3, 4]]]";
+        let node = parser.parse(&mut CharStream::from(input)).unwrap();
+
+        let path = node.path_to(Position::new(1, 13, 12, 12));
+        let types: Vec<&str> =
+            path.iter().map(|n| n.node_type.as_str()).collect();
+        assert_eq!(vec!["ROOT", "ARRAY", "ARRAY", "ARRAY", "NUMBER"], types);
+        let spans: Vec<_> = path.iter().map(|n| n.loc.byte_span()).collect();
+        assert_eq!(vec![0..16, 0..16, 4..15, 8..14, 12..13], spans);
+        assert!(node.path_to(Position::new(1, 17, 16, 16)).is_empty());
+    }
+
+    #[test]
+    fn custom_error_message() {
+        let bnf = JSON_BNF.replace(
+            "PROPERTY = $WHITESPACE STRING $WHITESPACE ':' VALUE;",
+            "PROPERTY(error=\"expected a value after ':'\") = \
+             $WHITESPACE STRING $WHITESPACE ':' VALUE;",
+        );
+        let parser = Parser::from_bnf(&bnf).unwrap();
+
+        match parser.parse(&mut CharStream::from("{\"a\": 1, \"b\": }")) {
+            Err(ParseError::Syntax {
+                pos,
+                message: Some(message),
+                ..
+            }) => {
+                assert_eq!("expected a value after ':'", message);
+                assert_eq!(14, pos.index());
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match parser.parse(&mut CharStream::from("[1 2]")) {
+            Err(ParseError::Syntax { message, .. }) => {
+                assert!(message.is_none())
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn shebang() {
+        let parser = json_parser().with_shebang_skip();
+        let input = "#!/usr/bin/env json-tool --strict\n{\"a\": 1}";
+
+        let node = parser.parse(&mut CharStream::from(input)).unwrap();
+        assert_eq!(
+            r#"(ROOT (OBJECT (PROPERTY (STRING "\"a\"") (NUMBER "1"))))"#,
+            node.to_sexpr()
+        );
+        let number = node.descendants().find(|n| n.node_type == "NUMBER");
+        assert_eq!(2, number.unwrap().loc.0.line());
+        assert!(parser.parse(&mut CharStream::from("#!")).is_err());
+        assert!(json_parser().parse(&mut CharStream::from(input)).is_err());
+    }
+
+    #[test]
+    fn farthest_failure() {
+        let parser = json_parser();
+
+        match parser.parse(&mut CharStream::from("[[[1]]x")) {
+            Err(ParseError::Syntax { pos, expected, .. }) => {
+                assert_eq!(6, pos.index());
+                assert_eq!(vec!['\t', '\n', '\r', ' ', ',', ']'], expected);
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match parser.parse(&mut CharStream::from("[1, tru]")) {
+            Err(ParseError::Syntax { pos, .. }) => assert_eq!(7, pos.index()),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
 }
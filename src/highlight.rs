@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use crate::emit::included_ranges;
+use crate::parse::Parser;
+use crate::parse::Symbol;
+use crate::parse::SymbolIdent;
+use crate::parse::SymbolType;
+
+/// How a token is highlighted, see [`Parser::token_classes`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenClass {
+    /// A fixed word of letters, such as `true`.
+    Keyword,
+    /// Digits, possibly with signs, points and exponents.
+    Number,
+    /// Text between two equal delimiters, such as `"a"`.
+    String,
+    /// A fixed run of punctuation chars, such as `,` or `=>`.
+    Punctuation,
+}
+
+/// Chars a number may contain besides digits.
+const NUMBER_CHARS: &str = "+-._eExXabcdefABCDEF";
+
+impl Parser {
+    /// A guess at the token class of every named rule that is raw or does
+    /// not refer to other named rules, for syntax highlighting. Rules whose
+    /// structure fits none of the classes are left out.
+    pub fn token_classes(&self) -> HashMap<String, TokenClass> {
+        let mut classes = HashMap::new();
+        for (idx, symbol) in self.symbol_registry.iter().enumerate() {
+            let ident = self.ident(idx);
+            let name = match &symbol.properties.node_name {
+                Some(name) => name,
+                None => continue,
+            };
+            if !symbol.properties.raw && self.refers_to_named(&ident) {
+                continue;
+            }
+            if let Some(class) = self.token_class(&ident) {
+                classes.insert(name.clone(), class);
+            }
+        }
+        classes
+    }
+
+    fn token_class(&self, ident: &SymbolIdent) -> Option<TokenClass> {
+        let symbol = self.get_symbol(ident);
+        if let Some(text) = self.fixed_text(symbol) {
+            return if text.chars().all(char::is_alphabetic) {
+                Some(TokenClass::Keyword)
+            } else if text.chars().all(|c| c.is_ascii_punctuation()) {
+                Some(TokenClass::Punctuation)
+            } else {
+                None
+            };
+        }
+        match &symbol.symbol_type {
+            SymbolType::Balanced { .. } => return Some(TokenClass::String),
+            SymbolType::Sequence(s) if s.len() >= 2 => {
+                let delimiter = |id: &SymbolIdent| match self
+                    .get_symbol(id)
+                    .symbol_type
+                {
+                    SymbolType::Terminal(c) if !c.is_alphanumeric() => Some(c),
+                    _ => None,
+                };
+                let first = delimiter(&s[0]);
+                if first.is_some() && first == delimiter(&s[s.len() - 1]) {
+                    return Some(TokenClass::String);
+                }
+            }
+            _ => {}
+        }
+        let mut seen = vec![false; self.symbol_count()];
+        let number = self.all_chars(ident, &mut seen, &|c| {
+            c.is_ascii_digit() || NUMBER_CHARS.contains(c)
+        });
+        let mut seen = vec![false; self.symbol_count()];
+        if number && self.any_char(ident, &mut seen, &|c| c.is_ascii_digit()) {
+            return Some(TokenClass::Number);
+        }
+        None
+    }
+
+    /// The text of a single terminal, a literal or a sequence of terminals.
+    fn fixed_text(&self, symbol: &Symbol) -> Option<String> {
+        match &symbol.symbol_type {
+            SymbolType::Terminal(c) => Some(c.to_string()),
+            SymbolType::Literal(s) if !s.is_empty() => Some(s.clone()),
+            _ => self.word(symbol),
+        }
+    }
+
+    /// Whether the body of `ident` contains another named symbol.
+    fn refers_to_named(&self, ident: &SymbolIdent) -> bool {
+        let mut seen = vec![false; self.symbol_count()];
+        let mut stack = self.get_symbol(ident).sub_symbols().to_vec();
+        while let Some(id) = stack.pop() {
+            if std::mem::replace(&mut seen[id.0], true) {
+                continue;
+            }
+            let symbol = self.get_symbol(&id);
+            if symbol.properties.node_name.is_some() {
+                return true;
+            }
+            stack.extend(symbol.sub_symbols());
+        }
+        false
+    }
+
+    /// Whether every char `ident` can match satisfies `pred`.
+    fn all_chars(
+        &self,
+        ident: &SymbolIdent,
+        seen: &mut Vec<bool>,
+        pred: &dyn Fn(char) -> bool,
+    ) -> bool {
+        if std::mem::replace(&mut seen[ident.0], true) {
+            return true;
+        }
+        let symbol = self.get_symbol(ident);
+        let ranges = match &symbol.symbol_type {
+            SymbolType::Terminal(c) => vec![(*c, *c)],
+            SymbolType::Literal(s) => s.chars().map(|c| (c, c)).collect(),
+            SymbolType::LiteralSet(trie) => trie
+                .words()
+                .iter()
+                .flat_map(|s| s.chars().map(|c| (c, c)))
+                .collect(),
+            SymbolType::CharSet(ranges) => ranges.clone(),
+            SymbolType::AnyExcept(ranges) => included_ranges(ranges),
+            SymbolType::Sequence(_)
+            | SymbolType::OneOf(_)
+            | SymbolType::Optional(_)
+            | SymbolType::OneOrMore(_)
+            | SymbolType::ZeroOrMore(_)
+            | SymbolType::Repeat { .. } => {
+                return symbol
+                    .sub_symbols()
+                    .iter()
+                    .all(|id| self.all_chars(id, seen, pred));
+            }
+            _ => return false,
+        };
+        ranges.into_iter().all(|(from, to)| (from..=to).all(pred))
+    }
+
+    /// Whether `ident` can match a char that satisfies `pred`.
+    fn any_char(
+        &self,
+        ident: &SymbolIdent,
+        seen: &mut Vec<bool>,
+        pred: &dyn Fn(char) -> bool,
+    ) -> bool {
+        if std::mem::replace(&mut seen[ident.0], true) {
+            return false;
+        }
+        let symbol = self.get_symbol(ident);
+        match &symbol.symbol_type {
+            SymbolType::Terminal(c) => pred(*c),
+            SymbolType::Literal(s) => s.chars().any(pred),
+            SymbolType::LiteralSet(trie) => {
+                trie.words().iter().any(|s| s.chars().any(pred))
+            }
+            SymbolType::CharSet(ranges) => {
+                ranges.iter().any(|(from, to)| (*from..=*to).any(pred))
+            }
+            SymbolType::AnyExcept(ranges) => included_ranges(ranges)
+                .into_iter()
+                .any(|(from, to)| (from..=to).any(pred)),
+            _ => symbol
+                .sub_symbols()
+                .iter()
+                .any(|id| self.any_char(id, seen, pred)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+
+    #[test]
+    fn json() {
+        let classes = json_parser().token_classes();
+
+        assert_eq!(Some(&TokenClass::Keyword), classes.get("TRUE"));
+        assert_eq!(Some(&TokenClass::Keyword), classes.get("NULL"));
+        assert_eq!(Some(&TokenClass::Number), classes.get("NUMBER"));
+        assert_eq!(Some(&TokenClass::String), classes.get("STRING"));
+        assert_eq!(None, classes.get("ARRAY"));
+        assert_eq!(None, classes.get("ESCAPED"));
+    }
+
+    #[test]
+    fn ranges_and_punctuation() {
+        let classes = Parser::from_bnf(
+            "ROOT(root) = INT ARROW;\nINT = '0'..'9' { '0'..'9' };\n\
+             ARROW = '=' '>';",
+        )
+        .unwrap()
+        .token_classes();
+
+        assert_eq!(Some(&TokenClass::Number), classes.get("INT"));
+        assert_eq!(Some(&TokenClass::Punctuation), classes.get("ARROW"));
+    }
+}
@@ -1,23 +1,89 @@
+use std::cell::Cell;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Result;
+use std::io::BufRead;
+use std::sync::Arc;
+
+use crate::parse::ParseError;
 
 #[derive(Debug)]
 pub struct CharStream {
-    chars: Vec<char>,
+    chars: Arc<Vec<char>>,
+    /// Position of the first buffered char. Everything but `index` is
+    /// absolute, `index` counts from here.
+    start: Position,
     index: usize,
 
     line: usize,
     col: usize,
+    byte: usize,
+    /// Whether anything looked past the end of the input.
+    hit_end: Cell<bool>,
+    /// Where the rest of the input comes from, see
+    /// [`CharStream::from_reader`].
+    source: Option<Source>,
+    /// The first position that was needed after it had been dropped from
+    /// the buffer.
+    evicted: Cell<Option<Position>>,
+}
+
+/// The reader of a stream that reads its input lazily.
+struct Source {
+    reader: Box<dyn BufRead + Send>,
+    /// How many chars are kept behind the current one.
+    backtrack: usize,
+    /// The start of a UTF-8 sequence split between two reads.
+    pending: Vec<u8>,
+    /// Whether the reader is exhausted.
+    done: bool,
+    /// Why reading failed, if it did.
+    error: Option<String>,
+}
+
+impl Debug for Source {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.debug_struct("Source")
+            .field("backtrack", &self.backtrack)
+            .field("done", &self.done)
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
 }
 
+/// A location in the input: line, column, char index and byte offset.
+/// Serializes as an object with the fields `line`, `col`, `index` and
+/// `byte`.
 #[derive(PartialEq, Eq, Clone, Copy)]
-pub struct Position(pub usize, pub usize, pub usize);
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "PositionFields", into = "PositionFields")
+)]
+pub struct Position(pub usize, pub usize, pub usize, pub usize);
 
 impl Position {
-    pub fn new(start: usize, end: usize, idx: usize) -> Position {
-        Position(start, end, idx)
+    pub fn new(line: usize, col: usize, idx: usize, byte: usize) -> Position {
+        Position(line, col, idx, byte)
+    }
+
+    pub fn line(&self) -> usize {
+        self.0
+    }
+
+    pub fn col(&self) -> usize {
+        self.1
+    }
+
+    /// Index of the position in chars.
+    pub fn index(&self) -> usize {
+        self.2
+    }
+
+    /// Offset of the position in bytes of the UTF-8 input.
+    pub fn byte_offset(&self) -> usize {
+        self.3
     }
 }
 
@@ -33,13 +99,93 @@ impl Display for Position {
     }
 }
 
-#[derive(Clone)]
+/// Serializes as an object with the fields `start` and `end`.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "RangeFields", into = "RangeFields")
+)]
 pub struct Range(pub Position, pub Position);
 
+/// The named fields [`Position`] serializes as.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PositionFields {
+    line: usize,
+    col: usize,
+    index: usize,
+    byte: usize,
+}
+
+#[cfg(feature = "serde")]
+impl From<Position> for PositionFields {
+    fn from(pos: Position) -> Self {
+        PositionFields {
+            line: pos.line(),
+            col: pos.col(),
+            index: pos.index(),
+            byte: pos.byte_offset(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<PositionFields> for Position {
+    fn from(fields: PositionFields) -> Self {
+        Position::new(fields.line, fields.col, fields.index, fields.byte)
+    }
+}
+
+/// The named fields [`Range`] serializes as.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RangeFields {
+    start: Position,
+    end: Position,
+}
+
+#[cfg(feature = "serde")]
+impl From<Range> for RangeFields {
+    fn from(range: Range) -> Self {
+        RangeFields {
+            start: range.0,
+            end: range.1,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<RangeFields> for Range {
+    fn from(fields: RangeFields) -> Self {
+        Range::new(fields.start, fields.end)
+    }
+}
+
 impl Range {
     pub fn new(start: Position, end: Position) -> Range {
         Range(start, end)
     }
+
+    /// The byte range covered, as expected by `codespan-reporting` and
+    /// similar diagnostic crates.
+    pub fn byte_span(&self) -> std::ops::Range<usize> {
+        self.0.byte_offset()..self.1.byte_offset()
+    }
+
+    /// The number of chars covered.
+    pub fn len(&self) -> usize {
+        self.1.index() - self.0.index()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether `pos` lies inside, counting the start but not the end.
+    pub fn contains(&self, pos: Position) -> bool {
+        self.0.index() <= pos.index() && pos.index() < self.1.index()
+    }
 }
 
 impl Display for Range {
@@ -54,17 +200,240 @@ impl Debug for Range {
     }
 }
 
+/// The Unicode normalization forms [`CharStream::from_normalized`] supports.
+#[cfg(feature = "unicode-normalization")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition: `e` + U+0301 becomes `é`.
+    Nfc,
+    /// Canonical decomposition: `é` becomes `e` + U+0301.
+    Nfd,
+}
+
 impl CharStream {
     pub fn from(s: &str) -> CharStream {
+        CharStream::starting_at(
+            Arc::new(s.chars().collect()),
+            Position::new(1, 1, 0, 0),
+        )
+    }
+
+    /// Like [`CharStream::from`], for a fragment of a larger document that
+    /// begins at `start`. All positions, and with them node locations and
+    /// errors, are reported relative to the whole document.
+    pub fn from_at(s: &str, start: Position) -> CharStream {
+        CharStream::starting_at(Arc::new(s.chars().collect()), start)
+    }
+
+    /// How many chars a stream from [`CharStream::from_reader`] reads ahead
+    /// of the current one. Lookbehinds and [`Parser::matches_at`] see no
+    /// further than this, as they run on a copy of the buffer.
+    ///
+    /// [`Parser::matches_at`]: crate::Parser::matches_at
+    pub const LOOKAHEAD: usize = 4096;
+
+    /// How many chars behind the current one a stream from
+    /// [`CharStream::from_reader`] keeps at least. The parser can backtrack
+    /// this far, raw nodes can be this long.
+    pub const BACKTRACK: usize = 1 << 20;
+
+    /// A stream that reads `reader` as UTF-8 while it is parsed, rather
+    /// than holding all of it in memory. Only a window around the current
+    /// position is kept, see [`CharStream::LOOKAHEAD`] and
+    /// [`CharStream::BACKTRACK`].
+    ///
+    /// Going back to a position that was already dropped fails: the
+    /// parse then fails with [`ParseError::Evicted`], as it does with
+    /// [`ParseError::Read`] if the reader fails or the input is not UTF-8.
+    /// Methods that look at the input as a whole, like
+    /// [`CharStream::line_at`], only see the current window.
+    pub fn from_reader<R: BufRead + Send + 'static>(reader: R) -> CharStream {
+        CharStream::reading(reader, CharStream::BACKTRACK)
+    }
+
+    fn reading<R: BufRead + Send + 'static>(
+        reader: R,
+        backtrack: usize,
+    ) -> CharStream {
+        let mut stream = CharStream::starting_at(
+            Arc::new(Vec::new()),
+            Position::new(1, 1, 0, 0),
+        );
+        stream.source = Some(Source {
+            reader: Box::new(reader),
+            backtrack,
+            pending: Vec::new(),
+            done: false,
+            error: None,
+        });
+        stream.fill();
+        stream
+    }
+
+    fn starting_at(chars: Arc<Vec<char>>, start: Position) -> CharStream {
         CharStream {
-            chars: s.chars().collect(),
+            chars,
+            start,
             index: 0,
-            line: 1,
-            col: 1,
+            line: start.line(),
+            col: start.col(),
+            byte: start.byte_offset(),
+            hit_end: Cell::new(false),
+            source: None,
+            evicted: Cell::new(None),
         }
     }
+
+    /// Reads until [`CharStream::LOOKAHEAD`] chars are buffered ahead of the
+    /// current one, first dropping chars too far behind it.
+    fn fill(&mut self) {
+        let Some(source) = &mut self.source else {
+            return;
+        };
+        // Drop chars in large batches, so the remaining ones are not moved
+        // on every read.
+        if self.index >= 2 * source.backtrack.max(CharStream::LOOKAHEAD) {
+            let count = self.index - source.backtrack;
+            let chars = Arc::make_mut(&mut self.chars);
+            let dropped = &chars[..count];
+            let newlines = dropped.iter().filter(|c| **c == '\n').count();
+            let col = match dropped.iter().rposition(|c| *c == '\n') {
+                Some(i) => count - i,
+                None => self.start.col() + count,
+            };
+            self.start = Position::new(
+                self.start.line() + newlines,
+                col,
+                self.start.index() + count,
+                self.start.byte_offset()
+                    + dropped.iter().map(|c| c.len_utf8()).sum::<usize>(),
+            );
+            chars.drain(..count);
+            self.index -= count;
+        }
+        while !source.done && self.chars.len() <= self.index + Self::LOOKAHEAD {
+            let read = match source.reader.fill_buf() {
+                Ok(read) => read,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {
+                    continue;
+                }
+                Err(e) => {
+                    source.error = Some(e.to_string());
+                    source.done = true;
+                    break;
+                }
+            };
+            if read.is_empty() {
+                if !source.pending.is_empty() {
+                    source.error =
+                        Some("stream did not contain valid UTF-8".into());
+                }
+                source.done = true;
+                break;
+            }
+            let mut bytes = std::mem::take(&mut source.pending);
+            bytes.extend_from_slice(read);
+            let len = read.len();
+            source.reader.consume(len);
+            let valid = match std::str::from_utf8(&bytes) {
+                Ok(_) => bytes.len(),
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(_) => {
+                    source.error =
+                        Some("stream did not contain valid UTF-8".into());
+                    source.done = true;
+                    break;
+                }
+            };
+            let text = std::str::from_utf8(&bytes[..valid]).unwrap();
+            Arc::make_mut(&mut self.chars).extend(text.chars());
+            source.pending = bytes.split_off(valid);
+        }
+    }
+
+    /// What went wrong with the input during a parse: the reader failed
+    /// or a position was needed after it had been dropped. Clears the
+    /// latter.
+    pub(crate) fn take_error(&mut self) -> Option<ParseError> {
+        if let Some(error) = self.source.as_ref().and_then(|s| s.error.clone())
+        {
+            return Some(ParseError::Read(error));
+        }
+        self.evicted.take().map(ParseError::Evicted)
+    }
+
+    /// Records that `pos`, before the start of the buffer, was needed.
+    fn evict(&self, pos: Position) {
+        if self.evicted.get().is_none() {
+            self.evicted.set(Some(pos));
+        }
+    }
+
+    /// Like [`CharStream::from`], but normalizes `s` first so terminals match
+    /// regardless of how the input composes its chars.
+    ///
+    /// Positions, including byte offsets, refer to the normalized text, not
+    /// to `s`.
+    #[cfg(feature = "unicode-normalization")]
+    pub fn from_normalized(s: &str, form: NormalizationForm) -> CharStream {
+        use unicode_normalization::UnicodeNormalization;
+
+        let chars = match form {
+            NormalizationForm::Nfc => s.nfc().collect(),
+            NormalizationForm::Nfd => s.nfd().collect(),
+        };
+        CharStream::starting_at(Arc::new(chars), Position::new(1, 1, 0, 0))
+    }
+
+    /// Rewinds the stream to the start of the input, keeping the buffer. A
+    /// stream from [`CharStream::from_reader`] only rewinds to the start of
+    /// its current window.
+    pub fn reset(&mut self) {
+        self.set_pos(self.start);
+    }
+
+    /// A new stream at the start of the same input. The chars are shared,
+    /// not copied. For a stream from [`CharStream::from_reader`] that is
+    /// only what it has buffered so far.
+    pub fn clone_fresh(&self) -> CharStream {
+        CharStream::starting_at(Arc::clone(&self.chars), self.start)
+    }
+
+    /// Appends `more` to the input, e.g. the next chunk of a network stream.
+    /// The position stays where it is.
+    pub fn push_str(&mut self, more: &str) {
+        Arc::make_mut(&mut self.chars).extend(more.chars());
+        self.hit_end.set(false);
+    }
+
+    /// Whether anything looked past the end of the input since the last
+    /// [`CharStream::clear_hit_end`].
+    pub(crate) fn hit_end(&self) -> bool {
+        self.hit_end.get()
+    }
+
+    pub(crate) fn clear_hit_end(&self) {
+        self.hit_end.set(false);
+    }
+
+    /// Number of chars in the whole input, or in the buffer of a stream from
+    /// [`CharStream::from_reader`].
+    pub fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    #[inline]
     pub fn get_pos(&self) -> Position {
-        Position::new(self.line, self.col, self.index)
+        Position::new(
+            self.line,
+            self.col,
+            self.start.index() + self.index,
+            self.byte,
+        )
     }
 
     pub fn get_loc(&self) -> (usize, usize) {
@@ -72,33 +441,168 @@ impl CharStream {
     }
 
     pub fn since_pos(&mut self, pos: Position) -> (&[char], Range) {
-        (
-            &self.chars[pos.2..self.index],
-            Range::new(pos, Position(self.line, self.col, self.index)),
-        )
+        let range = Range::new(pos, self.get_pos());
+        (self.slice(&range), range)
+    }
+
+    /// The chars inside `range`, which must lie between the start of the
+    /// stream and the current position. Chars already dropped from the
+    /// buffer are left out.
+    pub(crate) fn slice(&self, range: &Range) -> &[char] {
+        let start = self.start.index();
+        if range.0.index() < start {
+            self.evict(range.0);
+        }
+        let end = range.1.index().max(start) - start;
+        &self.chars[range.0.index().max(start) - start..end]
     }
 
+    /// The position `n` chars before the current one, `None` if that lies
+    /// before the start of the stream.
+    pub(crate) fn pos_before(&self, n: usize) -> Option<Position> {
+        let index = self.index.min(self.chars.len()).checked_sub(n)?;
+        let skipped = &self.chars[index..self.index.min(self.chars.len())];
+        let byte =
+            self.byte - skipped.iter().map(|c| c.len_utf8()).sum::<usize>();
+        let newlines = skipped.iter().filter(|c| **c == '\n').count();
+        let col = if newlines == 0 {
+            self.col - n
+        } else {
+            let line_start = self.chars[..index]
+                .iter()
+                .rposition(|c| *c == '\n')
+                .map_or(0, |i| i + 1);
+            match line_start {
+                0 => self.start.col() + index,
+                _ => index - line_start + 1,
+            }
+        };
+        Some(Position::new(
+            self.line - newlines,
+            col,
+            self.start.index() + index,
+            byte,
+        ))
+    }
+
+    /// Moves the stream to `pos`. For a position already dropped from the
+    /// buffer of a stream from [`CharStream::from_reader`], see
+    /// [`CharStream::try_set_pos`], it moves to the start of the buffer
+    /// instead and the running parse fails with [`ParseError::Evicted`].
+    #[inline]
     pub fn set_pos(&mut self, pos: Position) {
+        if self.try_set_pos(pos).is_err() {
+            self.evict(pos);
+            self.set_pos(self.start);
+        }
+    }
+
+    /// Moves the stream to `pos`, or fails with [`ParseError::Evicted`] if
+    /// `pos` was already dropped from the buffer.
+    #[inline]
+    pub fn try_set_pos(
+        &mut self,
+        pos: Position,
+    ) -> std::result::Result<(), ParseError> {
+        if pos.index() < self.start.index() {
+            return Err(ParseError::Evicted(pos));
+        }
         self.line = pos.0;
         self.col = pos.1;
-        self.index = pos.2;
+        self.index = pos.2 - self.start.index();
+        self.byte = pos.3;
+        Ok(())
     }
 
+    #[inline]
     fn next(&mut self) -> Option<char> {
         let char = self.chars.get(self.index).copied();
+        if char.is_none() {
+            self.hit_end.set(true);
+        }
         self.index += 1;
         if let Some(c) = char {
-            self.col += 1;
+            self.byte += c.len_utf8();
+            // A line break is the last char of its line, the char after it
+            // starts the next line at column 1.
             if c == '\n' {
                 self.line += 1;
                 self.col = 1;
+            } else {
+                self.col += 1;
             }
         }
+        if self.source.is_some()
+            && self.index + Self::LOOKAHEAD >= self.chars.len()
+        {
+            self.fill();
+        }
         char
     }
+    #[inline]
     pub fn peek(&self) -> Option<&char> {
-        self.chars.get(self.index)
+        let char = self.chars.get(self.index);
+        if char.is_none() {
+            self.hit_end.set(true);
+        }
+        char
     }
+
+    /// Up to `n` of the next chars, fewer at the end of the input, without
+    /// advancing. A stream from [`CharStream::from_reader`] shows at most
+    /// [`CharStream::LOOKAHEAD`] chars.
+    #[inline]
+    pub fn peek_n(&self, n: usize) -> &[char] {
+        let rest = self.chars.get(self.index..).unwrap_or_default();
+        if rest.len() < n {
+            self.hit_end.set(true);
+        }
+        &rest[..n.min(rest.len())]
+    }
+
+    /// The chars of the line the stream is at, without its line break. At a
+    /// line break, that is the line the break ends.
+    pub fn current_line(&self) -> &[char] {
+        let index = self.index.min(self.chars.len());
+        let start = self.chars[..index]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1);
+        let end = self.chars[index..]
+            .iter()
+            .position(|c| *c == '\n')
+            .map_or(self.chars.len(), |i| index + i);
+        without_cr(&self.chars[start..end])
+    }
+
+    /// The chars of line `line`, counted like [`Position::line`], without
+    /// its line break. `None` if the input has no such line.
+    pub fn line_at(&self, line: usize) -> Option<&[char]> {
+        let nth = line.checked_sub(self.start.line())?;
+        self.chars.split(|c| *c == '\n').nth(nth).map(without_cr)
+    }
+
+    /// The column of `pos` counted in UTF-16 code units, as the Language
+    /// Server Protocol does, starting at 1 like [`Position::col`]. Chars
+    /// outside of the Basic Multilingual Plane take two units. `None` if
+    /// `pos` lies before the chars the stream holds.
+    pub fn utf16_col(&self, pos: Position) -> Option<usize> {
+        let index = pos
+            .index()
+            .checked_sub(self.start.index())?
+            .min(self.chars.len());
+        let line_start = self.chars[..index]
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |i| i + 1);
+        let first = if line_start == 0 { self.start.col() } else { 1 };
+        let units: usize = self.chars[line_start..index]
+            .iter()
+            .map(|c| c.len_utf16())
+            .sum();
+        Some(first + units)
+    }
+
     pub fn report_unknown(&mut self) -> ! {
         panic!(
             "Unknown symbol {} at {}:{}",
@@ -109,6 +613,11 @@ impl CharStream {
     }
 }
 
+/// `line` without the `\r` of a `\r\n` line break.
+fn without_cr(line: &[char]) -> &[char] {
+    line.strip_suffix(&['\r']).unwrap_or(line)
+}
+
 impl Iterator for CharStream {
     type Item = char;
     fn next(&mut self) -> Option<Self::Item> {
@@ -119,6 +628,8 @@ impl Iterator for CharStream {
 #[cfg(test)]
 mod tests {
     use super::CharStream;
+    use super::Position;
+    use crate::parse::ParseError;
 
     #[test]
     fn it_works() {
@@ -161,4 +672,248 @@ mod tests {
         stream.next(); // None
         assert_eq!((2, 3), stream.get_loc());
     }
+
+    #[test]
+    fn line_break_position() {
+        let mut stream = CharStream::from("a\nb");
+
+        assert_eq!((1, 1), stream.get_loc());
+        assert_eq!(Some('a'), stream.next());
+        assert_eq!((1, 2), stream.get_loc());
+        assert_eq!(Some('\n'), stream.next());
+        assert_eq!((2, 1), stream.get_loc());
+        assert_eq!(Some('b'), stream.next());
+        assert_eq!((2, 2), stream.get_loc());
+        assert_eq!(None, stream.next());
+        assert_eq!((2, 2), stream.get_loc());
+    }
+
+    #[test]
+    fn peek_n() {
+        let mut stream = CharStream::from("//x");
+
+        assert_eq!(['/', '/'], stream.peek_n(2));
+        assert!(!stream.hit_end());
+        stream.next();
+        assert_eq!(['/', 'x'], stream.peek_n(5));
+        assert!(stream.hit_end());
+        stream.by_ref().count();
+        assert!(stream.peek_n(1).is_empty());
+        assert_eq!((1, 4), stream.get_loc());
+    }
+
+    #[test]
+    fn reset_and_clone_fresh() {
+        let parser = crate::examples::json::json_parser();
+        let mut stream = CharStream::from("{\"key\": [1, 2]}");
+
+        let first = format!("{:?}", parser.parse(&mut stream));
+        stream.reset();
+        assert_eq!((1, 1), stream.get_loc());
+        let second = format!("{:?}", parser.parse(&mut stream));
+        let third = format!("{:?}", parser.parse(&mut stream.clone_fresh()));
+
+        assert!(first.starts_with("Ok"));
+        assert_eq!(first, second);
+        assert_eq!(first, third);
+    }
+
+    #[test]
+    fn byte_offsets() {
+        let mut stream = CharStream::from("aä€b");
+
+        let start = stream.get_pos();
+        stream.next(); // a
+        stream.next(); // ä
+        let mid = stream.get_pos();
+        stream.next(); // €
+        let (_, range) = stream.since_pos(mid);
+
+        assert_eq!(0, start.byte_offset());
+        assert_eq!(3, mid.byte_offset());
+        assert_eq!(2, mid.index());
+        assert_eq!(3..6, range.byte_span());
+        assert_eq!("€", &"aä€b"[range.byte_span()]);
+    }
+
+    #[cfg(feature = "codespan-reporting")]
+    #[test]
+    fn codespan_diagnostic() {
+        use codespan_reporting::diagnostic::Diagnostic;
+        use codespan_reporting::diagnostic::Label;
+        use codespan_reporting::files::SimpleFile;
+        use codespan_reporting::term;
+        use codespan_reporting::term::termcolor::NoColor;
+
+        use crate::examples::json::json_parser;
+        use crate::parse::ParseError;
+
+        let input = "[\"ä\"] x";
+        let pos = match json_parser().parse(&mut CharStream::from(input)) {
+            Err(ParseError::Syntax { pos, .. }) => pos,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        let file = SimpleFile::new("input.json", input);
+        let diagnostic = Diagnostic::error()
+            .with_message("unexpected input")
+            .with_labels(vec![Label::primary(
+                (),
+                pos.byte_offset()..pos.byte_offset() + 1,
+            )]);
+
+        let mut out = NoColor::new(Vec::new());
+        term::emit(&mut out, &Default::default(), &file, &diagnostic).unwrap();
+        let rendered = String::from_utf8(out.into_inner()).unwrap();
+        assert_eq!(7, pos.byte_offset());
+        assert!(rendered.contains("input.json:1:7"));
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn normalized() {
+        use super::NormalizationForm;
+        use crate::parse::Parser;
+        use crate::parse::SymbolProperties;
+
+        let mut parser = Parser::new();
+        let e_acute = parser
+            .terminal('\u{e9}', SymbolProperties::new("E_ACUTE", true, false));
+        parser.root_node(&e_acute);
+        let decomposed = "e\u{301}";
+
+        assert!(parser.parse(&mut CharStream::from(decomposed)).is_err());
+        let mut stream =
+            CharStream::from_normalized(decomposed, NormalizationForm::Nfc);
+        assert_eq!(1, stream.len());
+        assert!(parser.parse(&mut stream).is_ok());
+    }
+
+    #[test]
+    fn lines() {
+        let line = |l: &[char]| l.iter().collect::<String>();
+        let parser = crate::examples::json::json_parser();
+        let mut stream = CharStream::from("[1,\r\n 2] x,\n 3");
+
+        assert_eq!("[1,", line(stream.current_line()));
+        assert!(parser.parse_partial(&mut stream).is_ok());
+        assert_eq!(Some(&'x'), stream.peek());
+        assert_eq!(" 2] x,", line(stream.current_line()));
+        stream.by_ref().take_while(|c| *c != '\n').count();
+        assert_eq!(" 3", line(stream.current_line()));
+        stream.by_ref().count();
+        assert_eq!(" 3", line(stream.current_line()));
+
+        assert_eq!("[1,", line(stream.line_at(1).unwrap()));
+        assert_eq!(" 2] x,", line(stream.line_at(2).unwrap()));
+        assert_eq!(" 3", line(stream.line_at(3).unwrap()));
+        assert!(stream.line_at(0).is_none());
+        assert!(stream.line_at(4).is_none());
+    }
+
+    #[test]
+    fn utf16_col() {
+        let parser = crate::examples::json::json_parser();
+        let input = "[1,\n\"😀\", x]";
+
+        let pos = match parser.parse(&mut CharStream::from(input)) {
+            Err(crate::parse::ParseError::Syntax { pos, .. }) => pos,
+            other => panic!("Unexpected result {:?}", other),
+        };
+        let stream = CharStream::from(input);
+        assert_eq!((2, 6), (pos.line(), pos.col()));
+        assert_eq!(Some(7), stream.utf16_col(pos));
+        assert_eq!(Some(1), stream.utf16_col(stream.get_pos()));
+
+        let later = CharStream::from_at("x", Position::new(3, 4, 20, 20));
+        assert_eq!(Some(4), later.utf16_col(later.get_pos()));
+        assert_eq!(None, later.utf16_col(pos));
+    }
+
+    #[test]
+    fn pos_before() {
+        let mut stream = CharStream::from("ab\ncé\nd");
+        let positions: Vec<_> = (0..7)
+            .map(|_| {
+                let pos = stream.get_pos();
+                stream.next();
+                pos
+            })
+            .collect();
+
+        for (n, expected) in positions.iter().rev().enumerate() {
+            assert_eq!(Some(*expected), stream.pos_before(n + 1));
+        }
+        assert_eq!(None, stream.pos_before(8));
+    }
+
+    #[test]
+    fn fragment_positions() {
+        use super::Position;
+        use crate::parse::ParseError;
+
+        let document = "{\n  \"a\": [1, 2]\n}";
+        let fragment_start = document.find('[').unwrap();
+        let start = Position::new(2, 8, fragment_start, fragment_start);
+        let parser = crate::examples::json::json_parser();
+
+        let mut stream = CharStream::from_at("[1, 2]", start);
+        let array = parser.parse(&mut stream).unwrap();
+        assert_eq!(start, array.loc.0);
+        assert_eq!(Position::new(2, 14, 15, 15), array.loc.1);
+        assert_eq!("[1, 2]", &document[array.loc.byte_span()]);
+
+        let mut stream = CharStream::from_at("[1, 2] x", start);
+        match parser.parse(&mut stream) {
+            Err(ParseError::Syntax { pos, .. }) => {
+                assert_eq!((2, 15), (pos.line(), pos.col()))
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+        stream.reset();
+        assert_eq!(start, stream.get_pos());
+    }
+
+    #[test]
+    fn from_reader() {
+        use std::io::BufReader;
+        use std::io::Cursor;
+
+        let parser = crate::examples::json::json_parser();
+        let items: Vec<String> =
+            (0..5000).map(|n| format!("{}, \"ä€😀{}\"", n, n)).collect();
+        let input = format!("[{}]", items.join(",\n"));
+
+        // Small reads split chars, a small window drops them mid-parse.
+        let reader = BufReader::with_capacity(7, Cursor::new(input.clone()));
+        assert_eq!(
+            parser.parse(&mut CharStream::from(input.as_str())),
+            parser.parse(&mut CharStream::reading(reader, 64))
+        );
+
+        let invalid = Cursor::new(vec![b'[', 0xff, b']']);
+        assert_eq!(
+            Err(ParseError::Read(
+                "stream did not contain valid UTF-8".into()
+            )),
+            parser.parse(&mut CharStream::from_reader(invalid))
+        );
+    }
+
+    #[test]
+    fn from_reader_evicted() {
+        let parser = crate::Parser::from_bnf(
+            "A(root) = B | C; B = { 'a' } 'b'; C = { 'a' } 'c';",
+        )
+        .unwrap();
+        let input = "a".repeat(20_000) + "c";
+
+        let mut stream =
+            CharStream::reading(std::io::Cursor::new(input.clone()), 64);
+        assert_eq!(
+            Err(ParseError::Evicted(Position::new(1, 1, 0, 0))),
+            parser.parse(&mut stream)
+        );
+        let mut stream = CharStream::from_reader(std::io::Cursor::new(input));
+        assert!(parser.parse(&mut stream).is_ok());
+    }
 }
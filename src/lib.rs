@@ -1,8 +1,57 @@
+mod ambiguity;
+mod arena;
 mod bnf;
 mod build;
+mod bytes;
+mod compact;
+mod dialect;
+mod diff;
+mod ebnf;
+mod emit;
 pub mod examples;
+mod highlight;
 mod input;
+mod lint;
+mod literals;
+mod ll1;
+mod multi;
 mod parse;
+mod precedence;
+mod schema;
+mod shortest;
+mod trie;
+mod writer;
 
+pub use arena::FlatNode;
+pub use build::GrammarError;
+pub use build::ParserBuilder;
+pub use bytes::DecodeError;
+pub use diff::GrammarDiff;
+pub use highlight::TokenClass;
 pub use input::CharStream;
+#[cfg(feature = "unicode-normalization")]
+pub use input::NormalizationForm;
+pub use input::Position;
+pub use input::Range;
+pub use lint::Lint;
+pub use ll1::Ll1Conflict;
+pub use ll1::Ll1Error;
+pub use ll1::Ll1Parser;
+pub use multi::MultiParser;
+pub use parse::CompleteHook;
+pub use parse::FailedElement;
+pub use parse::Node;
+pub use parse::NodeData;
+pub use parse::ParseError;
+pub use parse::ParseResult;
 pub use parse::Parser;
+pub use parse::SymbolIdent;
+pub use parse::SymbolProperties;
+pub use parse::TrailingMode;
+pub use parse::TypedValue;
+pub use parse::Visitor;
+pub use precedence::Associativity;
+pub use precedence::PrecedenceTable;
+pub use schema::GrammarSchema;
+pub use writer::OutputFormat;
+pub use writer::WriteError;
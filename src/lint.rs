@@ -0,0 +1,407 @@
+use crate::build::GrammarError;
+use crate::parse::Parser;
+use crate::parse::SymbolIdent;
+use crate::parse::SymbolType;
+
+/// A structural problem in a grammar that is detectable without parsing.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lint {
+    /// A `OneOf` without alternatives. It can never match.
+    EmptyOneOf(SymbolIdent),
+    /// An ignored `Sequence` without elements. It matches nothing but the
+    /// empty input. Named ones are markers, see [`Parser::marker`].
+    EmptySequence(SymbolIdent),
+    /// A `ZeroOrMore`/`OneOrMore`, or a `Repeat` without upper bound, whose
+    /// body can match the empty input.
+    NullableRepetition(SymbolIdent),
+    /// An element of a sequence that has to consume input but comes after an
+    /// `EndOfInput` anchor.
+    AfterEndOfInput {
+        sequence: SymbolIdent,
+        element: usize,
+    },
+    /// A raw rule whose body contains `$WHITESPACE`. The raw text then
+    /// includes the whitespace, as it captures all input the rule matched.
+    RawWhitespace {
+        rule: SymbolIdent,
+        whitespace: SymbolIdent,
+    },
+    /// An alternative of a `OneOf` that can match the empty input but is
+    /// not the last one. It always matches, so the alternatives after it
+    /// are never tried, see [`Parser::normalize`].
+    ShadowingAlternative {
+        one_of: SymbolIdent,
+        alternative: usize,
+    },
+}
+
+impl Parser {
+    pub fn lint(&self) -> Vec<Lint> {
+        let nullable = self.nullable_symbols();
+        let mut lints = Vec::new();
+        for (idx, symbol) in self.symbol_registry.iter().enumerate() {
+            let ident = self.ident(idx);
+            match &symbol.symbol_type {
+                SymbolType::OneOf(s) if s.is_empty() => {
+                    lints.push(Lint::EmptyOneOf(ident))
+                }
+                SymbolType::Sequence(s)
+                    if s.is_empty() && symbol.properties.ignore =>
+                {
+                    lints.push(Lint::EmptySequence(ident))
+                }
+                SymbolType::Sequence(s) => {
+                    let eof = s.iter().position(|id| {
+                        matches!(
+                            self.get_symbol(id).symbol_type,
+                            SymbolType::EndOfInput
+                        )
+                    });
+                    if let Some(eof) = eof {
+                        for (element, id) in s.iter().enumerate().skip(eof) {
+                            if !nullable[id.0] {
+                                lints.push(Lint::AfterEndOfInput {
+                                    sequence: ident,
+                                    element,
+                                })
+                            }
+                        }
+                    }
+                }
+                SymbolType::OneOf(s) => {
+                    let last = s.len() - 1;
+                    for (alternative, id) in s.iter().enumerate() {
+                        if alternative < last && nullable[id.0] {
+                            lints.push(Lint::ShadowingAlternative {
+                                one_of: ident,
+                                alternative,
+                            })
+                        }
+                    }
+                }
+                SymbolType::ZeroOrMore(id)
+                | SymbolType::OneOrMore(id)
+                | SymbolType::Repeat {
+                    symbol: id,
+                    max: None,
+                    ..
+                } if nullable[id.0] => {
+                    lints.push(Lint::NullableRepetition(ident))
+                }
+                _ => {}
+            }
+            if symbol.properties.raw {
+                if let Some(whitespace) = self.find_whitespace(&ident) {
+                    lints.push(Lint::RawWhitespace {
+                        rule: ident,
+                        whitespace,
+                    })
+                }
+            }
+        }
+        lints
+    }
+
+    /// Moves the alternatives of every `OneOf` that can match the empty
+    /// input behind the others, keeping their order otherwise, so none of
+    /// them hides the ones after it. This changes what the grammar matches
+    /// wherever [`Lint::ShadowingAlternative`] is reported.
+    pub fn normalize(&mut self) {
+        let nullable = self.nullable_symbols();
+        for symbol in &mut self.symbol_registry {
+            if let SymbolType::OneOf(s) = &mut symbol.symbol_type {
+                s.sort_by_key(|id| nullable[id.0]);
+            }
+        }
+    }
+
+    /// Fails with [`GrammarError::LeftRecursion`] if a rule can reach itself
+    /// without consuming input. Parsing such a grammar would recurse until
+    /// the stack overflows. [`ParserBuilder::build`] checks this already.
+    ///
+    /// [`ParserBuilder::build`]: crate::ParserBuilder::build
+    pub fn validate(&self) -> Result<(), GrammarError> {
+        self.validate_named(|_| None)
+    }
+
+    /// Like [`Parser::validate`], calling the symbols `name` has a name for
+    /// by that name rather than by their node name.
+    pub(crate) fn validate_named(
+        &self,
+        name: impl Fn(&SymbolIdent) -> Option<String>,
+    ) -> Result<(), GrammarError> {
+        let name = |id: &SymbolIdent| {
+            name(id)
+                .or_else(|| self.get_symbol(id).properties.node_name.clone())
+        };
+        match self.left_recursion(&name) {
+            Some(cycle) => Err(GrammarError::LeftRecursion(
+                cycle
+                    .iter()
+                    .map(|id| name(id).unwrap_or_else(|| format!("#{}", id.0)))
+                    .collect(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// A cycle in the left-corner relation, from a symbol back to itself,
+    /// with the symbols `name` names only unless it names none of them.
+    fn left_recursion(
+        &self,
+        name: &dyn Fn(&SymbolIdent) -> Option<String>,
+    ) -> Option<Vec<SymbolIdent>> {
+        let nullable = self.nullable_symbols();
+        // The symbols each symbol tries at the position it starts at.
+        let corners: Vec<Vec<SymbolIdent>> = self
+            .symbol_registry
+            .iter()
+            .map(|symbol| match &symbol.symbol_type {
+                SymbolType::Sequence(s) => {
+                    let end = s
+                        .iter()
+                        .position(|id| !nullable[id.0])
+                        .map_or(s.len(), |i| i + 1);
+                    s[..end].to_vec()
+                }
+                SymbolType::OneOf(s) => s.clone(),
+                SymbolType::Optional(id)
+                | SymbolType::ZeroOrMore(id)
+                | SymbolType::OneOrMore(id)
+                | SymbolType::Repeat { symbol: id, .. }
+                | SymbolType::And(id)
+                | SymbolType::Not(id) => vec![*id],
+                _ => Vec::new(),
+            })
+            .collect();
+        // 0 is unvisited, 1 on the path, 2 done.
+        let mut state = vec![0u8; corners.len()];
+        for start in 0..corners.len() {
+            if state[start] != 0 {
+                continue;
+            }
+            state[start] = 1;
+            // Symbols on the path, each with the next corner to visit.
+            let mut path = vec![(start, 0)];
+            while let Some((idx, next)) = path.last().copied() {
+                let corner = match corners[idx].get(next) {
+                    Some(corner) => corner.0,
+                    None => {
+                        state[idx] = 2;
+                        path.pop();
+                        continue;
+                    }
+                };
+                path.last_mut().unwrap().1 += 1;
+                match state[corner] {
+                    0 => {
+                        state[corner] = 1;
+                        path.push((corner, 0));
+                    }
+                    1 => {
+                        let from = path
+                            .iter()
+                            .position(|(i, _)| *i == corner)
+                            .unwrap();
+                        let mut cycle: Vec<SymbolIdent> = path[from..]
+                            .iter()
+                            .map(|(i, _)| self.ident(*i))
+                            .collect();
+                        let is_named = |id: &SymbolIdent| name(id).is_some();
+                        if let Some(first) = cycle.iter().position(is_named) {
+                            cycle.rotate_left(first);
+                            cycle.retain(is_named);
+                        }
+                        cycle.push(cycle[0]);
+                        return Some(cycle);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// A `$WHITESPACE` symbol the body of `ident` can match.
+    fn find_whitespace(&self, ident: &SymbolIdent) -> Option<SymbolIdent> {
+        let mut seen = vec![false; self.symbol_registry.len()];
+        let mut stack = self.get_symbol(ident).sub_symbols().to_vec();
+        while let Some(id) = stack.pop() {
+            if std::mem::replace(&mut seen[id.0], true) {
+                continue;
+            }
+            let symbol = self.get_symbol(&id);
+            if symbol.is_whitespace() {
+                return Some(id);
+            }
+            stack.extend(symbol.sub_symbols());
+        }
+        None
+    }
+
+    /// Computes for every symbol whether it can match without consuming
+    /// input.
+    pub(crate) fn nullable_symbols(&self) -> Vec<bool> {
+        let mut nullable = vec![false; self.symbol_registry.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (idx, symbol) in self.symbol_registry.iter().enumerate() {
+                if nullable[idx] {
+                    continue;
+                }
+                let is_nullable = match &symbol.symbol_type {
+                    SymbolType::Sequence(s) => {
+                        s.iter().all(|id| nullable[id.0])
+                    }
+                    SymbolType::OneOf(s) => s.iter().any(|id| nullable[id.0]),
+                    SymbolType::Optional(_) => true,
+                    SymbolType::ZeroOrMore(_) => true,
+                    SymbolType::OneOrMore(id) => nullable[id.0],
+                    SymbolType::Repeat { symbol, min, .. } => {
+                        *min == 0 || nullable[symbol.0]
+                    }
+                    SymbolType::Terminal(_) => false,
+                    SymbolType::AnyExcept(_) => false,
+                    SymbolType::Literal(s) => s.is_empty(),
+                    SymbolType::LiteralSet(trie) => {
+                        trie.words().iter().any(|s| s.is_empty())
+                    }
+                    SymbolType::CharSet(_) => false,
+                    SymbolType::EndOfInput => true,
+                    SymbolType::Balanced { .. } => false,
+                    SymbolType::Lookbehind { .. } => true,
+                    SymbolType::And(_) => true,
+                    SymbolType::Not(_) => true,
+                    SymbolType::NestedComment { .. } => false,
+                    SymbolType::RestOfLine => true,
+                    SymbolType::Take(n) => *n == 0,
+                };
+                if is_nullable {
+                    nullable[idx] = true;
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::CharStream;
+
+    #[test]
+    fn empty_one_of() {
+        let mut parser = Parser::new();
+        let a = parser.terminal('a', None);
+        let empty = parser.one_of(vec![], None);
+        let root = parser.sequence(vec![&a, &empty], None);
+        parser.root_node(&root);
+
+        assert_eq!(vec![Lint::EmptyOneOf(empty)], parser.lint());
+    }
+
+    #[test]
+    fn terminal_after_end_of_input() {
+        let mut parser = Parser::new();
+        let a = parser.terminal('a', None);
+        let eof = parser.end_of_input(None);
+        let b = parser.terminal('b', None);
+        let b_opt = parser.optional(&b, None);
+        let root = parser.sequence(vec![&a, &eof, &b_opt, &b], None);
+        parser.root_node(&root);
+
+        assert_eq!(
+            vec![Lint::AfterEndOfInput {
+                sequence: root,
+                element: 3
+            }],
+            parser.lint()
+        );
+    }
+
+    #[test]
+    fn nullable_repetition() {
+        let mut parser = Parser::new();
+        let a = parser.terminal('a', None);
+        let a_opt = parser.optional(&a, None);
+        let many = parser.zero_or_more(&a_opt, None);
+
+        assert_eq!(vec![Lint::NullableRepetition(many)], parser.lint());
+    }
+
+    #[test]
+    fn shadowing_alternative() {
+        let mut parser = Parser::from_bnf(
+            "ROOT(root) = ( [ X ] | Y ) 'z';\nX = 'x';\nY = 'y';",
+        )
+        .unwrap();
+        let one_of =
+            parser.get_symbol(&parser.root_node.unwrap()).sub_symbols()[0];
+
+        assert_eq!(
+            vec![Lint::ShadowingAlternative {
+                one_of,
+                alternative: 0
+            }],
+            parser.lint()
+        );
+        assert!(parser.parse(&mut CharStream::from("yz")).is_err());
+        parser.normalize();
+        assert_eq!(Vec::<Lint>::new(), parser.lint());
+        assert!(parser.parse(&mut CharStream::from("yz")).is_ok());
+        assert!(parser.parse(&mut CharStream::from("xz")).is_ok());
+        assert!(parser.parse(&mut CharStream::from("z")).is_ok());
+    }
+
+    #[test]
+    fn raw_whitespace() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = CALL;\nCALL(raw) = NAME $WHITESPACE '(' ')';\n\
+             NAME(raw) = 'f';",
+        )
+        .unwrap();
+
+        let call = parser.find_symbol("CALL").unwrap();
+        match &parser.lint()[..] {
+            [Lint::RawWhitespace { rule, whitespace }] => {
+                assert_eq!(call, *rule);
+                assert_eq!(
+                    Some("WHITESPACE"),
+                    parser
+                        .get_symbol(whitespace)
+                        .properties
+                        .node_name
+                        .as_deref()
+                );
+            }
+            other => panic!("Unexpected lints {:?}", other),
+        }
+    }
+
+    #[test]
+    fn json_is_clean() {
+        let parser = crate::examples::json::json_parser();
+
+        assert_eq!(Vec::<Lint>::new(), parser.lint());
+    }
+
+    #[test]
+    fn left_recursion() {
+        let error = |bnf: &str| Parser::from_bnf(bnf).unwrap_err().to_string();
+
+        assert_eq!(
+            "Left recursion EXPR -> EXPR",
+            error("EXPR(root) = ( EXPR '+' 'a' ) | 'a';")
+        );
+        assert_eq!(
+            "Left recursion A -> B -> A",
+            error("A(root) = ( B 'x' ) | 'y';\nB = [ 'z' ] A;")
+        );
+        assert_eq!("Left recursion A -> A", error("A(root) = B;\nB = A;"));
+        assert!(Parser::from_bnf("A(root) = ( 'x' A ) | 'y';").is_ok());
+        assert_eq!(Ok(()), crate::examples::json::json_parser().validate());
+    }
+}
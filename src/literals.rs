@@ -0,0 +1,103 @@
+use std::collections::BTreeSet;
+
+use crate::parse::Parser;
+use crate::parse::Symbol;
+use crate::parse::SymbolType;
+
+/// The most chars a `CharSet` may hold to be listed char by char in
+/// [`Parser::literals`].
+const MAX_LITERAL_CHARS: u32 = 64;
+
+impl Parser {
+    /// Every literal the grammar matches, sorted and without duplicates.
+    ///
+    /// A sequence of at least two terminals, such as `'t' 'r' 'u' 'e'` or one
+    /// built by [`Parser::sequence_chars`], is taken as one multi-char
+    /// literal, as is a `Literal` left by [`Parser::compact`]. Every other
+    /// terminal, and every char of a `CharSet` of at most 64 chars, is a
+    /// single-char literal. Larger sets, such as any letter, are not listed.
+    pub fn literals(&self) -> Vec<String> {
+        let mut literals = BTreeSet::new();
+        for symbol in &self.symbol_registry {
+            if let Some(word) = self.word(symbol) {
+                literals.insert(word);
+                continue;
+            }
+            match &symbol.symbol_type {
+                SymbolType::Literal(s) if !s.is_empty() => {
+                    literals.insert(s.clone());
+                }
+                SymbolType::LiteralSet(trie) => {
+                    literals.extend(trie.words().iter().cloned());
+                }
+                SymbolType::CharSet(ranges) => {
+                    let size: u32 = ranges
+                        .iter()
+                        .map(|(from, to)| *to as u32 - *from as u32 + 1)
+                        .sum();
+                    if size <= MAX_LITERAL_CHARS {
+                        for (from, to) in ranges {
+                            literals.extend((*from..=*to).map(String::from));
+                        }
+                    }
+                }
+                _ => {}
+            }
+            for id in symbol.sub_symbols() {
+                if let SymbolType::Terminal(c) = self.get_symbol(id).symbol_type
+                {
+                    literals.insert(c.to_string());
+                }
+            }
+        }
+        literals.into_iter().collect()
+    }
+
+    /// The chars of `symbol` if it is a sequence of at least two terminals.
+    pub(crate) fn word(&self, symbol: &Symbol) -> Option<String> {
+        match &symbol.symbol_type {
+            SymbolType::Sequence(s) if s.len() >= 2 => s
+                .iter()
+                .map(|id| match self.get_symbol(id).symbol_type {
+                    SymbolType::Terminal(c) => Some(c),
+                    _ => None,
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords() {
+        let mut parser = Parser::from_bnf(
+            r#"
+ROOT(root) = VALUE { ',' VALUE };
+VALUE = TRUE | FALSE | NULL;
+TRUE(raw) = 't' 'r' 'u' 'e';
+FALSE(raw) = 'f' 'a' 'l' 's' 'e';
+NULL(raw) = 'n' 'u' 'l' 'l';
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(vec![",", "false", "null", "true"], parser.literals());
+        parser.compact();
+        assert_eq!(vec![",", "false", "null", "true"], parser.literals());
+    }
+
+    #[test]
+    fn char_sets() {
+        let mut parser = Parser::new();
+        let digit = parser.range('0', '2', None);
+        let any = parser.range('\0', char::MAX, None);
+        let root = parser.sequence(vec![&digit, &any], None);
+        parser.root_node(&root);
+
+        assert_eq!(vec!["0", "1", "2"], parser.literals());
+    }
+}
@@ -0,0 +1,780 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::emit::included_ranges;
+use crate::input::CharStream;
+use crate::input::Range;
+use crate::parse::eat;
+use crate::parse::in_ranges;
+use crate::parse::match_balanced;
+use crate::parse::match_nested_comment;
+use crate::parse::merge_ranges;
+use crate::parse::Node;
+use crate::parse::ParseError;
+use crate::parse::ParseResult;
+use crate::parse::Parser;
+use crate::parse::SymbolIdent;
+use crate::parse::SymbolType;
+
+/// Why a grammar cannot be compiled with [`Parser::compile_ll1`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Ll1Error {
+    /// No root symbol was set with [`Parser::root_node`].
+    NoRoot,
+    /// A symbol that needs to look behind or further ahead than one char,
+    /// such as `$EOL_TEXT` or `.{n}`.
+    Unsupported {
+        rule: Option<String>,
+        symbol: String,
+    },
+    /// Symbols where the next char does not decide how to continue.
+    Conflicts(Vec<Ll1Conflict>),
+}
+
+/// A symbol that needs more than one char of lookahead, see
+/// [`Ll1Error::Conflicts`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ll1Conflict {
+    /// The innermost named rule the symbol belongs to.
+    pub rule: Option<String>,
+    /// The symbol, by name or in BNF-like notation.
+    pub symbol: String,
+    /// The smallest char that starts two of the ways to continue. `None` if
+    /// they overlap at the end of the input or in matching nothing.
+    pub lookahead: Option<char>,
+}
+
+impl Display for Ll1Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Ll1Error::NoRoot => write!(f, "No root node defined"),
+            Ll1Error::Unsupported { rule, symbol } => {
+                write!(f, "{} is not supported in LL(1) mode", symbol)?;
+                if let Some(rule) = rule {
+                    write!(f, " (in {})", rule)?;
+                }
+                Ok(())
+            }
+            Ll1Error::Conflicts(conflicts) => {
+                write!(f, "Grammar is not LL(1):")?;
+                for conflict in conflicts {
+                    write!(f, "\n  {}", conflict.symbol)?;
+                    if let Some(rule) = &conflict.rule {
+                        write!(f, " (in {})", rule)?;
+                    }
+                    match conflict.lookahead {
+                        Some(c) => write!(f, " on '{}'", c.escape_debug())?,
+                        None => write!(f, " on end of input")?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Ll1Error {}
+
+/// The chars a symbol can start with, and whether it can start at the end
+/// of the input.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Lookahead {
+    /// Sorted, non-overlapping, inclusive ranges.
+    ranges: Vec<(char, char)>,
+    end: bool,
+}
+
+impl Lookahead {
+    fn chars(ranges: Vec<(char, char)>) -> Self {
+        Lookahead { ranges, end: false }
+    }
+
+    fn add(&mut self, other: &Lookahead) {
+        if !other.ranges.is_empty() {
+            self.ranges.extend_from_slice(&other.ranges);
+            self.ranges = merge_ranges(&self.ranges);
+        }
+        self.end |= other.end;
+    }
+
+    fn contains(&self, c: Option<char>) -> bool {
+        match c {
+            Some(c) => in_ranges(&self.ranges, c),
+            None => self.end,
+        }
+    }
+
+    /// The smallest char both contain, or `Some(None)` if they only share
+    /// the end of the input.
+    fn common(&self, other: &Lookahead) -> Option<Option<char>> {
+        let (mut i, mut j) = (0, 0);
+        while i < self.ranges.len() && j < other.ranges.len() {
+            let (a, b) = (self.ranges[i], other.ranges[j]);
+            if a.0.max(b.0) <= a.1.min(b.1) {
+                return Some(Some(a.0.max(b.0)));
+            }
+            if a.1 < b.1 {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        (self.end && other.end).then_some(None)
+    }
+}
+
+/// How a symbol decides, by the next char, how to continue.
+#[derive(Clone, Debug)]
+enum Decision {
+    /// The symbol has nothing to decide, or always goes on with its body.
+    None,
+    /// The alternative of a `OneOf` by char range, the alternative starting
+    /// at the end of the input and the alternative that matches nothing.
+    Alternatives {
+        table: Vec<(char, char, usize)>,
+        end: Option<usize>,
+        default: Option<usize>,
+    },
+    /// The body of an optional or repeated symbol is matched (again) if the
+    /// next char starts it.
+    Enter(Lookahead),
+}
+
+/// A parser for an LL(1) grammar, see [`Parser::compile_ll1`].
+#[derive(Debug)]
+pub struct Ll1Parser {
+    grammar: Parser,
+    decisions: Vec<Decision>,
+}
+
+impl Parser {
+    /// Checks that the grammar is LL(1), that is, the next char always
+    /// decides which alternative to take and whether to match an optional
+    /// or repeated symbol, and builds a parser that never backtracks and
+    /// runs in time linear in the input.
+    ///
+    /// The FIRST and FOLLOW sets are computed per char. Literals decide by
+    /// their first char, so alternatives `'ab' | 'ac'` conflict. A nullable
+    /// alternative has to be the last one, as [`Parser::parse`] would never
+    /// try the alternatives after it.
+    pub fn compile_ll1(&self) -> Result<Ll1Parser, Ll1Error> {
+        let root = self.root_node.ok_or(Ll1Error::NoRoot)?;
+        let nullable = self.nullable_symbols();
+        let first = self.first_sets(&nullable);
+        let follow = self.follow_sets(&root, &nullable, &first);
+
+        let mut decisions = vec![Decision::None; self.symbol_count()];
+        let mut conflicts = Vec::new();
+        let mut seen = vec![false; self.symbol_count()];
+        let mut stack = vec![(root, None::<String>)];
+        while let Some((ident, rule)) = stack.pop() {
+            if std::mem::replace(&mut seen[ident.0], true) {
+                continue;
+            }
+            let symbol = self.get_symbol(&ident);
+            let rule = symbol.properties.node_name.clone().or(rule);
+            let description = || match &symbol.properties.node_name {
+                Some(name) => name.clone(),
+                None => symbol.repr(self),
+            };
+            let mut conflict = |lookahead| {
+                conflicts.push(Ll1Conflict {
+                    rule: rule.clone(),
+                    symbol: description(),
+                    lookahead,
+                })
+            };
+            let idx = ident.0;
+            match &symbol.symbol_type {
+                SymbolType::OneOf(s) => {
+                    let last = s.len().saturating_sub(1);
+                    for (i, a) in s.iter().enumerate() {
+                        for b in &s[i + 1..] {
+                            if let Some(c) = first[a.0].common(&first[b.0]) {
+                                conflict(c);
+                            }
+                        }
+                        if nullable[a.0] && i < last {
+                            conflict(None);
+                        }
+                    }
+                    let default = s.last().filter(|id| nullable[id.0]);
+                    if default.is_some() {
+                        for id in &s[..last] {
+                            if let Some(c) = first[id.0].common(&follow[idx]) {
+                                conflict(c);
+                            }
+                        }
+                    }
+                    let mut table = Vec::new();
+                    for (i, id) in s.iter().enumerate() {
+                        table.extend(
+                            first[id.0].ranges.iter().map(|r| (r.0, r.1, i)),
+                        );
+                    }
+                    table.sort();
+                    decisions[idx] = Decision::Alternatives {
+                        table,
+                        end: s.iter().position(|id| first[id.0].end),
+                        default: default.map(|_| last),
+                    };
+                }
+                SymbolType::Optional(id) if nullable[id.0] => {}
+                // Repeated a fixed number of times, without a decision.
+                SymbolType::Repeat { min, max, .. } if Some(*min) == *max => {}
+                SymbolType::Optional(id)
+                | SymbolType::ZeroOrMore(id)
+                | SymbolType::OneOrMore(id)
+                | SymbolType::Repeat { symbol: id, .. } => {
+                    if nullable[id.0] {
+                        conflict(None);
+                    } else if let Some(c) = first[id.0].common(&follow[idx]) {
+                        conflict(c);
+                    }
+                    decisions[idx] = Decision::Enter(first[id.0].clone());
+                }
+                SymbolType::Lookbehind { .. }
+                | SymbolType::And(_)
+                | SymbolType::Not(_)
+                | SymbolType::RestOfLine
+                | SymbolType::Take(_) => {
+                    return Err(Ll1Error::Unsupported {
+                        symbol: description(),
+                        rule,
+                    });
+                }
+                _ => {}
+            }
+            for id in symbol.sub_symbols() {
+                stack.push((*id, rule.clone()));
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(Ll1Error::Conflicts(conflicts));
+        }
+        Ok(Ll1Parser {
+            grammar: self.clone_grammar(),
+            decisions,
+        })
+    }
+
+    /// The FIRST set of every symbol.
+    fn first_sets(&self, nullable: &[bool]) -> Vec<Lookahead> {
+        let mut first = vec![Lookahead::default(); self.symbol_count()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (idx, symbol) in self.symbol_registry.iter().enumerate() {
+                let set = match &symbol.symbol_type {
+                    SymbolType::Sequence(s) => {
+                        let mut set = Lookahead::default();
+                        for id in s {
+                            set.add(&first[id.0]);
+                            if !nullable[id.0] {
+                                break;
+                            }
+                        }
+                        set
+                    }
+                    SymbolType::OneOf(s) => {
+                        let mut set = Lookahead::default();
+                        for id in s {
+                            set.add(&first[id.0]);
+                        }
+                        set
+                    }
+                    SymbolType::Optional(id)
+                    | SymbolType::OneOrMore(id)
+                    | SymbolType::ZeroOrMore(id)
+                    | SymbolType::Repeat { symbol: id, .. } => {
+                        first[id.0].clone()
+                    }
+                    SymbolType::Terminal(c) => Lookahead::chars(vec![(*c, *c)]),
+                    SymbolType::AnyExcept(ranges) => {
+                        Lookahead::chars(included_ranges(ranges))
+                    }
+                    SymbolType::Literal(s) => Lookahead::chars(
+                        s.chars().take(1).map(|c| (c, c)).collect(),
+                    ),
+                    SymbolType::CharSet(ranges) => {
+                        Lookahead::chars(ranges.clone())
+                    }
+                    SymbolType::LiteralSet(trie) => {
+                        let chars: Vec<(char, char)> = trie
+                            .words()
+                            .iter()
+                            .filter_map(|s| s.chars().next())
+                            .map(|c| (c, c))
+                            .collect();
+                        Lookahead::chars(merge_ranges(&chars))
+                    }
+                    SymbolType::EndOfInput => Lookahead {
+                        ranges: vec![],
+                        end: true,
+                    },
+                    SymbolType::Balanced { open, .. } => {
+                        Lookahead::chars(vec![(*open, *open)])
+                    }
+                    SymbolType::NestedComment { open, .. } => Lookahead::chars(
+                        open.chars().take(1).map(|c| (c, c)).collect(),
+                    ),
+                    SymbolType::Lookbehind { .. }
+                    | SymbolType::And(_)
+                    | SymbolType::Not(_)
+                    | SymbolType::RestOfLine
+                    | SymbolType::Take(_) => Lookahead::default(),
+                };
+                if set != first[idx] {
+                    first[idx] = set;
+                    changed = true;
+                }
+            }
+        }
+        first
+    }
+
+    /// The FOLLOW set of every symbol, where the root is followed by the end
+    /// of the input.
+    fn follow_sets(
+        &self,
+        root: &SymbolIdent,
+        nullable: &[bool],
+        first: &[Lookahead],
+    ) -> Vec<Lookahead> {
+        let mut follow = vec![Lookahead::default(); self.symbol_count()];
+        follow[root.0].end = true;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (idx, symbol) in self.symbol_registry.iter().enumerate() {
+                let after = follow[idx].clone();
+                let mut add = |id: &SymbolIdent, set: &Lookahead| {
+                    let before = follow[id.0].clone();
+                    follow[id.0].add(set);
+                    changed |= follow[id.0] != before;
+                };
+                match &symbol.symbol_type {
+                    SymbolType::Sequence(s) => {
+                        let mut trailing = after;
+                        for id in s.iter().rev() {
+                            add(id, &trailing);
+                            if nullable[id.0] {
+                                trailing.add(&first[id.0]);
+                            } else {
+                                trailing = first[id.0].clone();
+                            }
+                        }
+                    }
+                    SymbolType::OneOf(s) => {
+                        for id in s {
+                            add(id, &after);
+                        }
+                    }
+                    SymbolType::Optional(id) => add(id, &after),
+                    SymbolType::OneOrMore(id)
+                    | SymbolType::ZeroOrMore(id)
+                    | SymbolType::Repeat { symbol: id, .. } => {
+                        add(id, &after);
+                        add(id, &first[id.0]);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        follow
+    }
+}
+
+impl Ll1Parser {
+    /// Parses like [`Parser::parse`], deciding every step by the next char.
+    pub fn parse(&self, stream: &mut CharStream) -> ParseResult {
+        let root = self.grammar.root_node.ok_or(ParseError::NoRoot)?;
+        if self.grammar.get_symbol(&root).properties.ignore {
+            panic!("Root node is set to be ignored");
+        }
+        self.grammar.skip_shebang(stream);
+        let mut nodes = Vec::with_capacity(1);
+        let result = self.advance(&root, stream, &mut nodes, 0, &mut 0);
+        if let Some(err) = stream.take_error() {
+            return Err(err);
+        }
+        result?;
+        if stream.peek().is_some() {
+            return Err(syntax_error(stream));
+        }
+        Ok(nodes.pop().unwrap())
+    }
+
+    /// Matches `ident` and appends its node, or the nodes of its children
+    /// if it is ignored, to `nodes`. `depth` counts the symbols it is
+    /// nested in and `built` the nodes built so far, see
+    /// [`Parser::with_max_depth`] and [`Parser::with_max_nodes`].
+    fn advance(
+        &self,
+        ident: &SymbolIdent,
+        stream: &mut CharStream,
+        nodes: &mut Vec<Node>,
+        depth: usize,
+        built: &mut usize,
+    ) -> Result<(), ParseError> {
+        if self.grammar.max_depth.is_some_and(|max| depth >= max) {
+            return Err(ParseError::TooDeep);
+        }
+        let symbol = self.grammar.get_symbol(ident);
+        let start = stream.get_pos();
+        let mut children = Vec::new();
+        let mut inner = None;
+        match self.advance_body(
+            ident,
+            stream,
+            &mut children,
+            &mut inner,
+            depth + 1,
+            built,
+        ) {
+            Ok(()) => {}
+            Err(ParseError::Syntax {
+                pos,
+                message: None,
+                element,
+                expected,
+            }) if symbol.properties.error_message.is_some() => {
+                return Err(ParseError::Syntax {
+                    pos,
+                    message: symbol.properties.error_message.clone(),
+                    element,
+                    expected,
+                });
+            }
+            Err(e) => return Err(e),
+        }
+        if symbol.properties.ignore {
+            nodes.append(&mut children);
+            return Ok(());
+        }
+        *built += 1;
+        if self.grammar.max_nodes.is_some_and(|max| *built > max) {
+            return Err(ParseError::TooManyNodes);
+        }
+        let range =
+            inner.unwrap_or_else(|| Range::new(start, stream.get_pos()));
+        let data = symbol.node_data(|| stream.slice(&range), children);
+        let mut node = symbol.node(range, data);
+        self.grammar.run_hooks(&mut node)?;
+        nodes.push(node);
+        Ok(())
+    }
+
+    fn advance_body(
+        &self,
+        ident: &SymbolIdent,
+        stream: &mut CharStream,
+        children: &mut Vec<Node>,
+        inner: &mut Option<Range>,
+        depth: usize,
+        built: &mut usize,
+    ) -> Result<(), ParseError> {
+        let symbol = self.grammar.get_symbol(ident);
+        let next = stream.peek().copied();
+        let matched = match &symbol.symbol_type {
+            SymbolType::Sequence(s) => {
+                let tight = symbol.properties.tight;
+                if tight {
+                    let start = stream.get_pos();
+                    *inner = Some(Range::new(start, start));
+                }
+                let mut tokens = 0;
+                for id in s {
+                    let start = stream.get_pos();
+                    self.advance(id, stream, children, depth, built)?;
+                    // Narrow the span to the elements that are not
+                    // whitespace, as `Parser::parse` does.
+                    if let Some(inner) = inner.as_mut() {
+                        if !self.grammar.only_whitespace(id) {
+                            if tokens == 0 {
+                                inner.0 = start;
+                            }
+                            inner.1 = stream.get_pos();
+                            tokens += 1;
+                        }
+                    }
+                }
+                true
+            }
+            SymbolType::OneOf(s) => match self.predict(ident, next) {
+                Some(alternative) => {
+                    self.advance(
+                        &s[alternative],
+                        stream,
+                        children,
+                        depth,
+                        built,
+                    )?;
+                    true
+                }
+                None => false,
+            },
+            SymbolType::Optional(id) => {
+                if self.enters(ident, next) {
+                    self.advance(id, stream, children, depth, built)?;
+                }
+                true
+            }
+            SymbolType::ZeroOrMore(id) => {
+                while self.enters(ident, stream.peek().copied()) {
+                    self.advance(id, stream, children, depth, built)?;
+                }
+                true
+            }
+            SymbolType::OneOrMore(id) => {
+                self.advance(id, stream, children, depth, built)?;
+                while self.enters(ident, stream.peek().copied()) {
+                    self.advance(id, stream, children, depth, built)?;
+                }
+                true
+            }
+            SymbolType::Repeat {
+                symbol: id,
+                min,
+                max,
+            } => {
+                let mut count = 0;
+                while max.is_none_or(|max| count < max)
+                    && (count < *min
+                        || self.enters(ident, stream.peek().copied()))
+                {
+                    self.advance(id, stream, children, depth, built)?;
+                    count += 1;
+                }
+                true
+            }
+            SymbolType::Terminal(c) => {
+                next == Some(*c) && stream.next().is_some()
+            }
+            SymbolType::AnyExcept(ranges) => {
+                next.is_some_and(|c| !in_ranges(ranges, c))
+                    && stream.next().is_some()
+            }
+            SymbolType::CharSet(ranges) => {
+                next.is_some_and(|c| in_ranges(ranges, c))
+                    && stream.next().is_some()
+            }
+            SymbolType::Literal(s) => eat(s, stream),
+            SymbolType::LiteralSet(trie) => trie.advance(stream),
+            SymbolType::EndOfInput => next.is_none(),
+            SymbolType::Balanced { open, close } => {
+                match_balanced(*open, *close, stream)
+            }
+            SymbolType::NestedComment { open, close } => {
+                match_nested_comment(open, close, stream)
+            }
+            SymbolType::Lookbehind { .. }
+            | SymbolType::And(_)
+            | SymbolType::Not(_)
+            | SymbolType::RestOfLine
+            | SymbolType::Take(_) => {
+                unreachable!("Rejected by Parser::compile_ll1")
+            }
+        };
+        if matched {
+            Ok(())
+        } else {
+            let mut err = syntax_error(stream);
+            if let ParseError::Syntax { expected, .. } = &mut err {
+                *expected = symbol.expected_chars();
+                expected.sort();
+                expected.dedup();
+            }
+            Err(err)
+        }
+    }
+
+    /// The alternative of the `OneOf` at `ident` to take before `next`.
+    fn predict(
+        &self,
+        ident: &SymbolIdent,
+        next: Option<char>,
+    ) -> Option<usize> {
+        match &self.decisions[ident.0] {
+            Decision::Alternatives {
+                table,
+                end,
+                default,
+            } => {
+                let found = match next {
+                    Some(c) => {
+                        let idx = table.partition_point(|e| e.1 < c);
+                        table.get(idx).filter(|e| e.0 <= c).map(|e| e.2)
+                    }
+                    None => *end,
+                };
+                found.or(*default)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the optional or repeated symbol at `ident` matches its body
+    /// before `next`.
+    fn enters(&self, ident: &SymbolIdent, next: Option<char>) -> bool {
+        match &self.decisions[ident.0] {
+            Decision::Enter(first) => first.contains(next),
+            _ => true,
+        }
+    }
+}
+
+fn syntax_error(stream: &CharStream) -> ParseError {
+    ParseError::Syntax {
+        pos: stream.get_pos(),
+        message: None,
+        element: None,
+        expected: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::NodeData;
+
+    const LISTS: &str = "LIST(root) = '[' [ ITEM { ',' ITEM } ] ']';\n\
+                         ITEM = NUMBER | LIST;\n\
+                         NUMBER(raw) = '0'..'9' { '0'..'9' };";
+
+    #[test]
+    fn same_trees() {
+        let parser = Parser::from_bnf(LISTS).unwrap();
+        let ll1 = parser.compile_ll1().unwrap();
+
+        for input in ["[]", "[1]", "[12,[3,[]],45]", "[1,]", "[1", "[1]2"] {
+            assert_eq!(
+                parser.parse(&mut CharStream::from(input)).is_ok(),
+                ll1.parse(&mut CharStream::from(input)).is_ok(),
+                "{}",
+                input
+            );
+            if let Ok(tree) = parser.parse(&mut CharStream::from(input)) {
+                assert_eq!(Ok(tree), ll1.parse(&mut CharStream::from(input)));
+            }
+        }
+        assert_eq!(
+            r#"(LIST (ITEM (NUMBER "12")) (ITEM (LIST (ITEM (NUMBER "3")) (ITEM (LIST)))))"#,
+            ll1.parse(&mut CharStream::from("[12,[3,[]]]"))
+                .unwrap()
+                .to_sexpr()
+        );
+    }
+
+    #[test]
+    fn max_depth() {
+        let parser = Parser::from_bnf(LISTS).unwrap().with_max_depth(50);
+        let ll1 = parser.compile_ll1().unwrap();
+        let input = "[".repeat(200000);
+
+        assert_eq!(
+            Err(ParseError::TooDeep),
+            parser.parse(&mut CharStream::from(input.as_str()))
+        );
+        assert_eq!(
+            Err(ParseError::TooDeep),
+            ll1.parse(&mut CharStream::from(input.as_str()))
+        );
+        assert!(ll1.parse(&mut CharStream::from("[[[1]]]")).is_ok());
+    }
+
+    #[test]
+    fn hooks_and_values() {
+        let mut parser = Parser::from_bnf(
+            "LIST(root) = '[' [ ITEM { ',' ITEM } ] ']';\n\
+             ITEM = NUMBER | LIST;\n\
+             NUMBER(raw,tight) = [ $WHITESPACE ] '0'..'9' { '0'..'9' } \
+             [ $WHITESPACE ];",
+        )
+        .unwrap();
+        parser.parse_as::<u8>("NUMBER");
+        parser.on_complete(
+            "LIST",
+            Box::new(|node| match &node.data {
+                NodeData::Children(c) if c.len() > 2 => {
+                    Err("Too long".to_owned())
+                }
+                _ => Ok(()),
+            }),
+        );
+        let ll1 = parser.compile_ll1().unwrap();
+
+        let tree = ll1.parse(&mut CharStream::from("[ 12 ,[3]]")).unwrap();
+        let number = tree.field("ITEM").unwrap().field("NUMBER").unwrap();
+        assert_eq!(NodeData::Raw("12".to_owned()), number.data);
+        assert_eq!(Some(&12), number.value::<u8>());
+        assert!(matches!(
+            ll1.parse(&mut CharStream::from("[300]")),
+            Err(ParseError::Rejected { .. })
+        ));
+        assert!(matches!(
+            ll1.parse(&mut CharStream::from("[1,2,3]")),
+            Err(ParseError::Rejected { .. })
+        ));
+    }
+
+    #[test]
+    fn max_nodes() {
+        let parser = Parser::from_bnf(LISTS).unwrap().with_max_nodes(5);
+        let ll1 = parser.compile_ll1().unwrap();
+
+        for input in ["[1,[2,3]]", "[1,2,3,4,5,6]"] {
+            assert_eq!(
+                Err(ParseError::TooManyNodes),
+                parser.parse(&mut CharStream::from(input))
+            );
+            assert_eq!(
+                Err(ParseError::TooManyNodes),
+                ll1.parse(&mut CharStream::from(input))
+            );
+        }
+        assert!(ll1.parse(&mut CharStream::from("[1,2]")).is_ok());
+    }
+
+    #[test]
+    fn exact_repetition() {
+        let parser =
+            Parser::from_bnf("ROOT(root) = 'u' { 'a'..'f' }<2> 'a';").unwrap();
+        let ll1 = parser.compile_ll1().unwrap();
+
+        assert!(ll1.parse(&mut CharStream::from("uaba")).is_ok());
+        assert!(ll1.parse(&mut CharStream::from("uaa")).is_err());
+    }
+
+    #[test]
+    fn conflicts() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = PAIR | NAME;\nPAIR = NAME ':' NAME;\n\
+             NAME(raw) = 'a'..'z' { 'a'..'z' };",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Err(Ll1Error::Conflicts(vec![Ll1Conflict {
+                rule: Some("ROOT".to_owned()),
+                symbol: "ROOT".to_owned(),
+                lookahead: Some('a'),
+            }])),
+            parser.compile_ll1().map(|_| ())
+        );
+    }
+
+    #[test]
+    fn repetition_conflict() {
+        let parser =
+            Parser::from_bnf("ROOT(root) = { 'a' 'b' } 'a' 'c';").unwrap();
+
+        match parser.compile_ll1() {
+            Err(Ll1Error::Conflicts(conflicts)) => {
+                assert_eq!(1, conflicts.len());
+                assert_eq!(Some('a'), conflicts[0].lookahead);
+            }
+            other => panic!("Expected a conflict, got {:?}", other.map(|_| ())),
+        }
+    }
+}
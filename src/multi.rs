@@ -0,0 +1,71 @@
+use crate::input::CharStream;
+use crate::parse::ParseResult;
+use crate::parse::Parser;
+
+/// Runs several grammars over the same input and picks the one that fits
+/// best.
+#[derive(Debug)]
+pub struct MultiParser {
+    parsers: Vec<Parser>,
+}
+
+impl MultiParser {
+    pub fn new(parsers: Vec<Parser>) -> Self {
+        assert!(!parsers.is_empty(), "MultiParser needs at least one parser");
+        MultiParser { parsers }
+    }
+
+    /// Returns the index of the winning grammar and its result.
+    ///
+    /// The first grammar that matches the whole input wins. If none does, the
+    /// grammar whose partial parse consumed the most input wins, with ties
+    /// going to the earlier one.
+    pub fn parse_best(&self, input: &str) -> (usize, ParseResult) {
+        let source = CharStream::from(input);
+        let mut best: Option<(usize, usize, ParseResult)> = None;
+        for (idx, parser) in self.parsers.iter().enumerate() {
+            let mut stream = source.clone_fresh();
+            let result = parser.parse_partial(&mut stream);
+            let consumed = match result {
+                ParseResult::Ok(_) => stream.get_pos().index(),
+                ParseResult::Err(_) => 0,
+            };
+            if matches!(result, ParseResult::Ok(_)) && stream.peek().is_none() {
+                return (idx, result);
+            }
+            if best.as_ref().is_none_or(|(_, c, _)| consumed > *c) {
+                best = Some((idx, consumed, result));
+            }
+        }
+        let (idx, _, result) = best.unwrap();
+        (idx, result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+
+    const TEXT_BNF: &str = r#"
+ROOT(root) = [ TEXT ];
+TEXT(raw) = CHAR { CHAR };
+CHAR(ignore) = *;
+"#;
+
+    #[test]
+    fn json_or_text() {
+        let multi = MultiParser::new(vec![
+            json_parser(),
+            Parser::from_bnf(TEXT_BNF).unwrap(),
+        ]);
+
+        let (json_idx, json_result) = multi.parse_best("{\"key\": [1, 2]}");
+        let (text_idx, text_result) = multi.parse_best("hello world");
+
+        assert_eq!(0, json_idx);
+        assert!(matches!(json_result, ParseResult::Ok(_)));
+        assert_eq!(1, text_idx);
+        assert!(matches!(text_result, ParseResult::Ok(_)));
+    }
+}
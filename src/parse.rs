@@ -1,10 +1,22 @@
+use std::any::Any;
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::str::FromStr;
+use std::sync::atomic::AtomicU32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 
 use crate::bnf::bnf_parser;
+use crate::build::GrammarError;
 use crate::build::ParserBuilder;
+use crate::emit::included_ranges;
 use crate::input::CharStream;
 use crate::input::Position;
 use crate::input::Range;
+use crate::trie::LiteralTrie;
+use crate::writer::OutputFormat;
 
 #[derive(Clone, Debug)]
 pub enum SymbolType {
@@ -13,17 +25,106 @@ pub enum SymbolType {
     Optional(SymbolIdent),
     OneOrMore(SymbolIdent),
     ZeroOrMore(SymbolIdent),
+    /// The symbol at least `min` and at most `max` times, as often as
+    /// possible, see [`Parser::repeat`].
+    Repeat {
+        symbol: SymbolIdent,
+        min: usize,
+        max: Option<usize>,
+    },
     Terminal(char),
-    AnyExcept(Vec<char>),
+    /// Any char outside of the given sorted, non-overlapping, inclusive
+    /// ranges.
+    AnyExcept(Vec<(char, char)>),
+    /// The chars of the string in order, see [`Parser::compact`].
+    Literal(String),
+    /// Any char inside the given sorted, non-overlapping, inclusive ranges,
+    /// see [`Parser::compact`].
+    CharSet(Vec<(char, char)>),
+    /// The first of several literals that matches, see [`Parser::compact`].
+    LiteralSet(LiteralTrie),
+    EndOfInput,
+    /// An `open` char up to its matching `close` char, with any number of
+    /// nested pairs in between.
+    Balanced {
+        open: char,
+        close: char,
+    },
+    /// A block comment from `open` to its matching `close`, with any number
+    /// of nested comments in between.
+    NestedComment {
+        open: String,
+        close: String,
+    },
+    /// Matches nothing, but only if the input right before the current
+    /// position matches the symbol, which always matches `length` chars, see
+    /// [`Parser::lookbehind`].
+    Lookbehind {
+        symbol: SymbolIdent,
+        length: usize,
+    },
+    /// Matches nothing, but only if the symbol matches at the current
+    /// position, see [`Parser::and`].
+    And(SymbolIdent),
+    /// Matches nothing, but only if the symbol does not match at the current
+    /// position, see [`Parser::not`].
+    Not(SymbolIdent),
+    /// Any chars up to, but not including, the next `\n` or the end of the
+    /// input.
+    RestOfLine,
+    /// Exactly the given number of chars, whatever they are.
+    Take(usize),
 }
 
 #[derive(Clone, Debug)]
 pub struct Symbol {
     pub symbol_type: SymbolType,
-    properties: SymbolProperties,
+    pub(crate) properties: SymbolProperties,
 }
 
 impl Symbol {
+    /// The symbols this symbol is built from, in matching order.
+    pub(crate) fn sub_symbols(&self) -> &[SymbolIdent] {
+        match &self.symbol_type {
+            SymbolType::Sequence(s) => s,
+            SymbolType::OneOf(s) => s,
+            SymbolType::Optional(s) => std::slice::from_ref(s),
+            SymbolType::OneOrMore(s) => std::slice::from_ref(s),
+            SymbolType::ZeroOrMore(s) => std::slice::from_ref(s),
+            SymbolType::Repeat { symbol, .. } => std::slice::from_ref(symbol),
+            SymbolType::Terminal(_) => &[],
+            SymbolType::AnyExcept(_) => &[],
+            SymbolType::Literal(_) => &[],
+            SymbolType::LiteralSet(_) => &[],
+            SymbolType::CharSet(_) => &[],
+            SymbolType::EndOfInput => &[],
+            SymbolType::Balanced { .. } => &[],
+            SymbolType::NestedComment { .. } => &[],
+            SymbolType::Lookbehind { symbol, .. } => {
+                std::slice::from_ref(symbol)
+            }
+            SymbolType::And(s) => std::slice::from_ref(s),
+            SymbolType::Not(s) => std::slice::from_ref(s),
+            SymbolType::RestOfLine => &[],
+            SymbolType::Take(_) => &[],
+        }
+    }
+
+    /// The grammar file and line of the rule this symbol was built for, if
+    /// it was built from BNF.
+    pub fn source_location(&self) -> Option<(&str, usize)> {
+        self.properties
+            .source_location
+            .as_ref()
+            .map(|(file, line)| (file.as_str(), *line))
+    }
+
+    /// The `#` or `///` comment lines right above the rule this symbol was
+    /// built for, without the comment markers, if it was built from BNF.
+    pub fn doc(&self) -> Option<&str> {
+        self.properties.doc.as_deref()
+    }
+
     pub fn add_ident(&mut self, ident: &SymbolIdent) {
         match &mut self.symbol_type {
             SymbolType::Sequence(s) => s.push(*ident),
@@ -31,17 +132,47 @@ impl Symbol {
             SymbolType::Optional(_) => panic!(),
             SymbolType::OneOrMore(_) => panic!(),
             SymbolType::ZeroOrMore(_) => panic!(),
+            SymbolType::Repeat { .. } => panic!(),
             SymbolType::Terminal(_) => panic!(),
             SymbolType::AnyExcept(_) => panic!(),
+            SymbolType::Literal(_) => panic!(),
+            SymbolType::LiteralSet(_) => panic!(),
+            SymbolType::CharSet(_) => panic!(),
+            SymbolType::EndOfInput => panic!(),
+            SymbolType::Balanced { .. } => panic!(),
+            SymbolType::NestedComment { .. } => panic!(),
+            SymbolType::Lookbehind { .. } => panic!(),
+            SymbolType::And(_) => panic!(),
+            SymbolType::Not(_) => panic!(),
+            SymbolType::RestOfLine => panic!(),
+            SymbolType::Take(_) => panic!(),
         };
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct SymbolProperties {
-    node_name: Option<String>,
-    ignore: bool,
-    raw: bool,
+    pub(crate) node_name: Option<String>,
+    pub(crate) ignore: bool,
+    pub(crate) raw: bool,
+    /// The grammar file and line this symbol's rule was defined at.
+    pub(crate) source_location: Option<(String, usize)>,
+    /// Whether results are cached per input position, see
+    /// [`SymbolProperties::memoized`].
+    pub(crate) memoize: bool,
+    /// Reported instead of a generic syntax error when this symbol fails
+    /// where the parse failed.
+    pub(crate) error_message: Option<String>,
+    /// Capacity reserved for the children of a sequence or repetition, see
+    /// [`SymbolProperties::with_expected_children`].
+    pub(crate) expected_children: Option<usize>,
+    /// Whether nodes are marked as lists, see [`SymbolProperties::as_list`].
+    pub(crate) is_list: bool,
+    /// Whether nodes leave out surrounding whitespace, see
+    /// [`SymbolProperties::tight`].
+    pub(crate) tight: bool,
+    /// The comment above the rule in the grammar, see [`Symbol::doc`].
+    pub(crate) doc: Option<String>,
 }
 
 impl SymbolProperties {
@@ -50,8 +181,59 @@ impl SymbolProperties {
             node_name: Some(node_name.to_owned()),
             raw,
             ignore,
+            source_location: None,
+            memoize: false,
+            error_message: None,
+            expected_children: None,
+            is_list: false,
+            tight: false,
+            doc: None,
         })
     }
+
+    /// Caches the result of the symbol per input position for the duration
+    /// of a parse, so backtracking never evaluates it twice at the same
+    /// place. Worth it for expensive rules that are retried often.
+    pub fn memoized(mut self) -> Self {
+        self.memoize = true;
+        self
+    }
+
+    /// Reports `message` when the parse fails while this symbol is being
+    /// matched, instead of a plain syntax error.
+    pub fn with_error_message(mut self, message: &str) -> Self {
+        self.error_message = Some(message.to_owned());
+        self
+    }
+
+    /// Marks the nodes of the symbol as lists of same-typed children, to be
+    /// read with [`Node::as_list`].
+    pub fn as_list(mut self) -> Self {
+        self.is_list = true;
+        self
+    }
+
+    /// Makes the nodes of a sequence span only its elements between leading
+    /// and trailing `$WHITESPACE`, optional or not, so raw nodes hold the
+    /// bare token. The whitespace is still matched.
+    pub fn tight(mut self) -> Self {
+        self.tight = true;
+        self
+    }
+
+    /// Describes the rule for documentation, as a `#` comment above it in
+    /// BNF does.
+    pub fn with_doc(mut self, doc: &str) -> Self {
+        self.doc = Some(doc.to_owned());
+        self
+    }
+
+    /// Reserves room for `count` children up front when a sequence or
+    /// repetition is matched, saving reallocations for long repetitions.
+    pub fn with_expected_children(mut self, count: usize) -> Self {
+        self.expected_children = Some(count);
+        self
+    }
 }
 
 impl Default for SymbolProperties {
@@ -60,18 +242,56 @@ impl Default for SymbolProperties {
             node_name: None,
             raw: false,
             ignore: true,
+            source_location: None,
+            memoize: false,
+            error_message: None,
+            expected_children: None,
+            is_list: false,
+            tight: false,
+            doc: None,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub node_type: String,
     pub loc: Range,
     pub data: NodeData,
+    /// Whether the rule is marked as a list, see [`Node::as_list`].
+    pub is_list: bool,
+    /// The raw text converted by [`Parser::parse_as`], see [`Node::value`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub value: Option<TypedValue>,
 }
 
-#[derive(Debug)]
+/// A value converted from the raw text of a node, see [`Parser::parse_as`].
+/// It follows from the text, so nodes compare equal whatever it holds.
+#[derive(Clone)]
+pub struct TypedValue(Arc<dyn Any + Send + Sync>);
+
+impl Debug for TypedValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("TypedValue").finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for TypedValue {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for TypedValue {}
+
+/// Serializes as `{"children": [...]}` or `{"raw": "..."}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum NodeData {
     Children(Vec<Node>),
     Raw(String),
@@ -83,19 +303,141 @@ impl Node {
             node_type: node_type.to_owned(),
             loc,
             data,
+            is_list: false,
+            value: None,
+        }
+    }
+
+    /// The number of chars the node spans.
+    pub fn len(&self) -> usize {
+        self.loc.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loc.is_empty()
+    }
+
+    /// The children of a node whose rule is marked as a list, see
+    /// [`SymbolProperties::as_list`].
+    pub fn as_list(&self) -> Option<&[Node]> {
+        match &self.data {
+            NodeData::Children(c) if self.is_list => Some(c),
+            _ => None,
+        }
+    }
+
+    /// The first direct child of type `node_type`, e.g. a `label:expr`
+    /// capture.
+    pub fn field(&self, node_type: &str) -> Option<&Node> {
+        self.iter_children().find(|n| n.node_type == node_type)
+    }
+
+    /// The value the raw text was converted to while parsing, see
+    /// [`Parser::parse_as`]. `None` if the rule has no conversion to `T`.
+    pub fn value<T: Any>(&self) -> Option<&T> {
+        self.value.as_ref()?.0.downcast_ref()
+    }
+
+    /// The direct children, none for a raw node.
+    pub fn iter_children(&self) -> std::slice::Iter<'_, Node> {
+        match &self.data {
+            NodeData::Children(c) => c.iter(),
+            NodeData::Raw(_) => [].iter(),
+        }
+    }
+
+    /// The tree as an S-expression, `(TYPE child ...)` for nodes with
+    /// children and `(TYPE "raw")` for raw nodes. Quotes and backslashes in
+    /// raw text are escaped with a backslash.
+    pub fn to_sexpr(&self) -> String {
+        let mut sexpr = Vec::new();
+        self.write_to(&mut sexpr, OutputFormat::Sexpr)
+            .expect("Writing to a Vec does not fail");
+        String::from_utf8(sexpr).expect("The tree is valid UTF-8")
+    }
+
+    /// The innermost node of the tree whose range contains `pos`, see
+    /// [`Range::contains`].
+    pub fn node_at(&self, pos: Position) -> Option<&Node> {
+        self.path_to(pos).pop()
+    }
+
+    /// The nodes whose range contains `pos`, from this one down to the
+    /// innermost, e.g. to expand a selection outwards. Empty if `pos` lies
+    /// outside of this node.
+    pub fn path_to(&self, pos: Position) -> Vec<&Node> {
+        let mut path = Vec::new();
+        let mut next = Some(self).filter(|node| node.loc.contains(pos));
+        while let Some(node) = next {
+            path.push(node);
+            next = node.iter_children().find(|child| child.loc.contains(pos));
+        }
+        path
+    }
+
+    /// This node and everything below it, depth first in pre-order.
+    pub fn descendants(&self) -> impl Iterator<Item = &Node> {
+        Descendants { stack: vec![self] }
+    }
+}
+
+struct Descendants<'a> {
+    /// Nodes still to visit, the next one last.
+    stack: Vec<&'a Node>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a Node;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.iter_children().rev());
+        Some(node)
+    }
+}
+
+/// Walks a tree depth first, starting with `visitor.visit_node(&root)`.
+///
+/// By default every node is entered, its children are visited and it is
+/// left again, so implementing [`Visitor::enter`] and [`Visitor::leave`]
+/// is enough to keep track of the path to the current node. Overriding
+/// [`Visitor::visit_node`] without calling [`Visitor::walk`] skips the
+/// children.
+pub trait Visitor<'n> {
+    /// Visits `node`, by default with [`Visitor::walk`].
+    fn visit_node(&mut self, node: &'n Node) {
+        self.walk(node);
+    }
+
+    /// Enters `node`, visits its children and leaves it.
+    fn walk(&mut self, node: &'n Node) {
+        self.enter(node);
+        for child in node.iter_children() {
+            self.visit_node(child);
         }
+        self.leave(node);
     }
+
+    /// Called before the children of `node` are visited.
+    fn enter(&mut self, _node: &'n Node) {}
+
+    /// Called after the children of `node` were visited.
+    fn leave(&mut self, _node: &'n Node) {}
 }
 
+#[derive(Clone, Debug)]
 pub enum AdvanceResult {
     NewNode(Node),
     Ok(Vec<Node>),
     Err,
+    /// The parse fails as a whole, without trying other alternatives.
+    Abort(ParseError),
 }
 
 pub enum DataResult {
     Data(Vec<Node>),
     Err,
+    Abort(ParseError),
 }
 
 fn join_and_wrap(strings: Vec<String>, sep: &str) -> String {
@@ -108,10 +450,44 @@ fn join_and_wrap(strings: Vec<String>, sep: &str) -> String {
     joined
 }
 
+/// The bounds of a repetition as written in BNF: `<min,max>`, `<n>` if both
+/// are `n`, or `<min,>` without an upper bound.
+pub(crate) fn bounds(min: usize, max: Option<usize>) -> String {
+    match max {
+        Some(max) if max == min => format!("<{}>", min),
+        Some(max) => format!("<{},{}>", min, max),
+        None => format!("<{},>", min),
+    }
+}
+
 impl Symbol {
+    /// The rule body in BNF-like notation, followed by where the rule was
+    /// defined if it came from a grammar file.
     pub fn repr(&self, p: &Parser) -> String {
+        match &self.properties.source_location {
+            Some((file, line)) => {
+                format!("{}  @ {}:{}", self.repr_body(p), file, line)
+            }
+            None => self.repr_body(p),
+        }
+    }
+
+    fn repr_body(&self, p: &Parser) -> String {
         match &self.symbol_type {
             SymbolType::AnyExcept(_) => "ANY".to_owned(),
+            SymbolType::EndOfInput => "EOF".to_owned(),
+            SymbolType::RestOfLine => "$EOL_TEXT".to_owned(),
+            SymbolType::Take(n) => format!(".{{{}}}", n),
+            SymbolType::Balanced { open, close } => format!(
+                "'{}' ... '{}'",
+                open.escape_debug(),
+                close.escape_debug()
+            ),
+            SymbolType::NestedComment { open, close } => format!(
+                "'{}' ... '{}'",
+                open.escape_debug(),
+                close.escape_debug()
+            ),
             SymbolType::Sequence(s) => {
                 let reprs = s
                     .iter()
@@ -120,7 +496,7 @@ impl Symbol {
                         if let Some(name) = &sym.properties.node_name {
                             name.clone()
                         } else {
-                            sym.repr(p)
+                            sym.repr_body(p)
                         }
                     })
                     .collect::<Vec<String>>();
@@ -134,7 +510,7 @@ impl Symbol {
                         if let Some(name) = &sym.properties.node_name {
                             name.clone()
                         } else {
-                            sym.repr(p)
+                            sym.repr_body(p)
                         }
                     })
                     .collect::<Vec<String>>();
@@ -148,35 +524,165 @@ impl Symbol {
                     if let Some(name) = &asd.properties.node_name {
                         name.clone()
                     } else {
-                        p.get_symbol(id).repr(p)
+                        p.get_symbol(id).repr_body(p)
                     }
                 )
             }
             SymbolType::OneOrMore(id) => {
-                format!("{{ {} }}+", p.get_symbol(id).repr(p))
+                format!("{{ {} }}+", p.get_symbol(id).repr_body(p))
             }
             SymbolType::ZeroOrMore(id) => {
-                format!("{{ {} }}", p.get_symbol(id).repr(p))
+                format!("{{ {} }}", p.get_symbol(id).repr_body(p))
+            }
+            SymbolType::Repeat { symbol, min, max } => format!(
+                "{{ {} }}{}",
+                p.get_symbol(symbol).repr_body(p),
+                bounds(*min, *max)
+            ),
+            SymbolType::Lookbehind { symbol, .. } => {
+                format!("<= {}", p.get_symbol(symbol).repr_body(p))
+            }
+            SymbolType::And(id) => {
+                format!("&{}", p.get_symbol(id).repr_body(p))
+            }
+            SymbolType::Not(id) => {
+                format!("!{}", p.get_symbol(id).repr_body(p))
             }
             SymbolType::Terminal(c) => format!("\'{}\'", c.escape_debug()),
+            SymbolType::Literal(s) => join_and_wrap(
+                s.chars()
+                    .map(|c| format!("\'{}\'", c.escape_debug()))
+                    .collect(),
+                " ",
+            ),
+            SymbolType::LiteralSet(trie) => join_and_wrap(
+                trie.words()
+                    .iter()
+                    .map(|s| {
+                        join_and_wrap(
+                            s.chars()
+                                .map(|c| format!("\'{}\'", c.escape_debug()))
+                                .collect(),
+                            " ",
+                        )
+                    })
+                    .collect(),
+                " | ",
+            ),
+            SymbolType::CharSet(ranges) => join_and_wrap(
+                ranges
+                    .iter()
+                    .map(|(from, to)| {
+                        if from == to {
+                            format!("\'{}\'", from.escape_debug())
+                        } else {
+                            format!(
+                                "\'{}\'..\'{}\'",
+                                from.escape_debug(),
+                                to.escape_debug()
+                            )
+                        }
+                    })
+                    .collect(),
+                " | ",
+            ),
         }
     }
 }
 
 impl Symbol {
-    fn name(&self) -> String {
+    pub(crate) fn name(&self) -> &str {
         if let Some(name) = &self.properties.node_name {
-            name.to_owned()
+            name
         } else {
             match self.symbol_type {
-                SymbolType::Sequence(_) => "Sequence".to_owned(),
-                SymbolType::OneOf(_) => "OneOf".to_owned(),
-                SymbolType::Optional(_) => "Optional".to_owned(),
-                SymbolType::OneOrMore(_) => "OneOrMore".to_owned(),
-                SymbolType::ZeroOrMore(_) => "ZeroOrMore".to_owned(),
-                SymbolType::Terminal(_) => "Terminal".to_owned(),
-                SymbolType::AnyExcept(_) => "AnyExcept".to_owned(),
+                SymbolType::Sequence(_) => "Sequence",
+                SymbolType::OneOf(_) => "OneOf",
+                SymbolType::Optional(_) => "Optional",
+                SymbolType::OneOrMore(_) => "OneOrMore",
+                SymbolType::ZeroOrMore(_) => "ZeroOrMore",
+                SymbolType::Repeat { .. } => "Repeat",
+                SymbolType::Terminal(_) => "Terminal",
+                SymbolType::AnyExcept(_) => "AnyExcept",
+                SymbolType::Literal(_) => "Literal",
+                SymbolType::LiteralSet(_) => "LiteralSet",
+                SymbolType::CharSet(_) => "CharSet",
+                SymbolType::EndOfInput => "EndOfInput",
+                SymbolType::Balanced { .. } => "Balanced",
+                SymbolType::NestedComment { .. } => "NestedComment",
+                SymbolType::Lookbehind { .. } => "Lookbehind",
+                SymbolType::And(_) => "And",
+                SymbolType::Not(_) => "Not",
+                SymbolType::RestOfLine => "RestOfLine",
+                SymbolType::Take(_) => "Take",
+            }
+        }
+    }
+
+    /// Whether this is a `$WHITESPACE` symbol as built from BNF.
+    pub(crate) fn is_whitespace(&self) -> bool {
+        self.properties.ignore
+            && self.properties.node_name.as_deref() == Some("WHITESPACE")
+    }
+
+    /// The chars that start a match of this leaf symbol. Char ranges are
+    /// only listed if they hold at most [`MAX_EXPECTED_CHARS`] chars.
+    pub(crate) fn expected_chars(&self) -> Vec<char> {
+        let mut chars = Vec::new();
+        self.push_expected_chars(&mut chars);
+        chars
+    }
+
+    /// Appends [`Symbol::expected_chars`] to `chars`.
+    fn push_expected_chars(&self, chars: &mut Vec<char>) {
+        let mut small = |ranges: &[(char, char)]| {
+            let count: u32 = ranges
+                .iter()
+                .map(|(from, to)| *to as u32 - *from as u32 + 1)
+                .sum();
+            if count as usize <= MAX_EXPECTED_CHARS {
+                chars.extend(ranges.iter().flat_map(|(from, to)| *from..=*to));
+            }
+        };
+        match &self.symbol_type {
+            SymbolType::Terminal(c) => chars.push(*c),
+            SymbolType::Literal(s) => chars.extend(s.chars().take(1)),
+            SymbolType::LiteralSet(trie) => chars
+                .extend(trie.words().iter().filter_map(|s| s.chars().next())),
+            SymbolType::CharSet(ranges) => small(ranges),
+            SymbolType::AnyExcept(ranges) => small(&included_ranges(ranges)),
+            SymbolType::Balanced { open, .. } => chars.push(*open),
+            SymbolType::NestedComment { open, .. } => {
+                chars.extend(open.chars().take(1))
+            }
+            _ => {}
+        }
+    }
+
+    /// A node of this symbol.
+    pub(crate) fn node(&self, loc: Range, data: NodeData) -> Node {
+        let mut node = Node::new(self.name(), loc, data);
+        node.is_list = self.properties.is_list;
+        node
+    }
+
+    /// The data of a node of this symbol. `raw` gives the chars it matched,
+    /// only asked for if the node keeps them.
+    pub(crate) fn node_data<'c>(
+        &self,
+        raw: impl FnOnce() -> &'c [char],
+        children: Vec<Node>,
+    ) -> NodeData {
+        match self.symbol_type {
+            SymbolType::Balanced { .. } => {
+                let raw = raw();
+                NodeData::Raw(raw[1..raw.len() - 1].iter().collect())
             }
+            SymbolType::NestedComment { .. }
+            | SymbolType::RestOfLine
+            | SymbolType::Take(_) => NodeData::Raw(raw().iter().collect()),
+            _ if self.properties.raw => NodeData::Raw(raw().iter().collect()),
+            _ => NodeData::Children(children),
         }
     }
 
@@ -184,21 +690,44 @@ impl Symbol {
         &self,
         stream: &mut CharStream,
         p: &Parser,
+        ctx: &mut ParseContext,
     ) -> AdvanceResult {
         let start_pos = stream.get_pos();
+        let farthest = ctx.farthest;
+        let capacity = self.properties.expected_children.unwrap_or(0);
+        let mut inner = None;
         let data_result: DataResult = match &self.symbol_type {
             SymbolType::Sequence(s) => {
-                Symbol::try_advance_sequence(s, stream, p)
+                if self.properties.tight {
+                    inner = Some(Range::new(start_pos, start_pos));
+                }
+                Symbol::try_advance_sequence(
+                    s,
+                    capacity,
+                    stream,
+                    p,
+                    ctx,
+                    inner.as_mut(),
+                )
+            }
+            SymbolType::OneOf(s) => {
+                Symbol::try_advance_one_of(s, stream, p, ctx)
             }
-            SymbolType::OneOf(s) => Symbol::try_advance_one_of(s, stream, p),
             SymbolType::Optional(s) => {
-                Symbol::try_advance_optional(s, stream, p)
+                Symbol::try_advance_optional(s, stream, p, ctx)
             }
+            SymbolType::And(s) => Symbol::try_advance_and(s, stream, p, ctx),
+            SymbolType::Not(s) => Symbol::try_advance_not(s, stream, p, ctx),
             SymbolType::OneOrMore(s) => {
-                Symbol::try_advance_one_or_more(s, stream, p)
+                Symbol::try_advance_one_or_more(s, capacity, stream, p, ctx)
             }
             SymbolType::ZeroOrMore(s) => {
-                Symbol::try_advance_zero_or_more(s, stream, p)
+                Symbol::try_advance_zero_or_more(s, capacity, stream, p, ctx)
+            }
+            SymbolType::Repeat { symbol, min, max } => {
+                Symbol::try_advance_repeat(
+                    symbol, *min, *max, capacity, stream, p, ctx,
+                )
             }
             SymbolType::Terminal(c) => {
                 Symbol::try_advance_terminal(c, stream, p)
@@ -206,39 +735,121 @@ impl Symbol {
             SymbolType::AnyExcept(c) => {
                 Symbol::try_advance_any_except(c, stream, p)
             }
+            SymbolType::Literal(s) => {
+                if eat(s, stream) {
+                    DataResult::Data(vec![])
+                } else {
+                    // Fail where the literal stops matching, as its chars
+                    // one by one would.
+                    for c in s.chars() {
+                        if stream.peek() != Some(&c) {
+                            ctx.failed(stream.get_pos(), None, None);
+                            ctx.expect(stream.get_pos(), |chars| chars.push(c));
+                            break;
+                        }
+                        stream.next();
+                    }
+                    stream.set_pos(start_pos);
+                    DataResult::Err
+                }
+            }
+            SymbolType::LiteralSet(trie) => {
+                if trie.advance(stream) {
+                    DataResult::Data(vec![])
+                } else {
+                    trie.matches(stream);
+                    ctx.failed(stream.get_pos(), None, None);
+                    stream.set_pos(start_pos);
+                    DataResult::Err
+                }
+            }
+            SymbolType::CharSet(ranges) => match stream.peek() {
+                Some(c) if in_ranges(ranges, *c) => {
+                    stream.next();
+                    DataResult::Data(vec![])
+                }
+                _ => DataResult::Err,
+            },
+            SymbolType::EndOfInput => {
+                Symbol::try_advance_end_of_input(stream, p)
+            }
+            SymbolType::Balanced { open, close } => {
+                if match_balanced(*open, *close, stream) {
+                    DataResult::Data(vec![])
+                } else {
+                    DataResult::Err
+                }
+            }
+            SymbolType::NestedComment { open, close } => {
+                if match_nested_comment(open, close, stream) {
+                    DataResult::Data(vec![])
+                } else {
+                    DataResult::Err
+                }
+            }
+            SymbolType::Lookbehind { symbol, length } => {
+                if p.matches_behind(symbol, *length, stream) {
+                    DataResult::Data(vec![])
+                } else {
+                    DataResult::Err
+                }
+            }
+            SymbolType::RestOfLine => {
+                match_rest_of_line(stream);
+                DataResult::Data(vec![])
+            }
+            SymbolType::Take(n) => {
+                if match_take(*n, stream) {
+                    DataResult::Data(vec![])
+                } else {
+                    DataResult::Err
+                }
+            }
         };
-        return match data_result {
+        match data_result {
             DataResult::Data(node) => {
                 // Got New Node. If the current symbol is ignored in AST,
                 // Only Forward the Data contained in that node.
-                if self.properties.ignore {
+                if self.properties.ignore && !ctx.concrete {
                     AdvanceResult::Ok(node)
                 } else {
-                    let (raw, range) = stream.since_pos(start_pos);
-                    let data = if self.properties.raw {
-                        NodeData::Raw(raw.iter().collect())
-                    } else {
-                        NodeData::Children(node)
-                    };
-                    AdvanceResult::NewNode(Node::new(&self.name(), range, data))
+                    ctx.nodes += 1;
+                    if p.max_nodes.is_some_and(|max| ctx.nodes > max) {
+                        return AdvanceResult::Abort(ParseError::TooManyNodes);
+                    }
+                    let range = inner.unwrap_or_else(|| {
+                        Range::new(start_pos, stream.get_pos())
+                    });
+                    let data = self.node_data(|| stream.slice(&range), node);
+                    let mut node = self.node(range, data);
+                    match p.run_hooks(&mut node) {
+                        Ok(()) => AdvanceResult::NewNode(node),
+                        Err(e) => AdvanceResult::Abort(e),
+                    }
                 }
             }
             DataResult::Err => {
+                let message = self.properties.error_message.as_deref();
+                ctx.failed(start_pos, farthest, message);
+                ctx.expect(start_pos, |chars| self.push_expected_chars(chars));
                 stream.set_pos(start_pos);
                 AdvanceResult::Err
             }
-        };
+            DataResult::Abort(e) => AdvanceResult::Abort(e),
+        }
     }
 
     fn try_advance_one_or_more(
         symbol: &SymbolIdent,
+        capacity: usize,
         stream: &mut CharStream,
         p: &Parser,
+        ctx: &mut ParseContext,
     ) -> DataResult {
         let mut once = false;
-        let mut data = Vec::new();
+        let mut data = Vec::with_capacity(capacity);
         loop {
-            match p.get_symbol(symbol).try_advance(stream, p) {
+            match p.advance(symbol, stream, ctx) {
                 AdvanceResult::NewNode(node) => {
                     if !once {
                         once = true;
@@ -258,22 +869,53 @@ impl Symbol {
                         DataResult::Err
                     };
                 }
+                AdvanceResult::Abort(e) => return DataResult::Abort(e),
             }
         }
     }
 
     fn try_advance_zero_or_more(
         symbol: &SymbolIdent,
+        capacity: usize,
         stream: &mut CharStream,
         p: &Parser,
+        ctx: &mut ParseContext,
     ) -> DataResult {
-        let mut data = Vec::new();
+        let mut data = Vec::with_capacity(capacity);
         loop {
-            match p.get_symbol(symbol).try_advance(stream, p) {
+            match p.advance(symbol, stream, ctx) {
                 AdvanceResult::NewNode(node) => data.push(node),
                 AdvanceResult::Ok(mut nodes) => data.append(&mut nodes),
                 AdvanceResult::Err => return DataResult::Data(data),
+                AdvanceResult::Abort(e) => return DataResult::Abort(e),
+            }
+        }
+    }
+
+    fn try_advance_repeat(
+        symbol: &SymbolIdent,
+        min: usize,
+        max: Option<usize>,
+        capacity: usize,
+        stream: &mut CharStream,
+        p: &Parser,
+        ctx: &mut ParseContext,
+    ) -> DataResult {
+        let mut data = Vec::with_capacity(capacity);
+        let mut count = 0;
+        while max.is_none_or(|max| count < max) {
+            match p.advance(symbol, stream, ctx) {
+                AdvanceResult::NewNode(node) => data.push(node),
+                AdvanceResult::Ok(mut nodes) => data.append(&mut nodes),
+                AdvanceResult::Err => break,
+                AdvanceResult::Abort(e) => return DataResult::Abort(e),
             }
+            count += 1;
+        }
+        if count < min {
+            DataResult::Err
+        } else {
+            DataResult::Data(data)
         }
     }
 
@@ -281,26 +923,76 @@ impl Symbol {
         symbol: &SymbolIdent,
         stream: &mut CharStream,
         p: &Parser,
+        ctx: &mut ParseContext,
     ) -> DataResult {
-        return match p.get_symbol(symbol).try_advance(stream, p) {
+        match p.advance(symbol, stream, ctx) {
             AdvanceResult::NewNode(node) => DataResult::Data(vec![node]),
             AdvanceResult::Ok(nodes) => DataResult::Data(nodes),
             AdvanceResult::Err => DataResult::Data(vec![]),
-        };
+            AdvanceResult::Abort(e) => DataResult::Abort(e),
+        }
+    }
+
+    /// Matches `symbol` and goes back to where it started, so that nothing is
+    /// consumed and its nodes are dropped.
+    fn try_advance_and(
+        symbol: &SymbolIdent,
+        stream: &mut CharStream,
+        p: &Parser,
+        ctx: &mut ParseContext,
+    ) -> DataResult {
+        let start = stream.get_pos();
+        match p.advance(symbol, stream, ctx) {
+            AdvanceResult::NewNode(_) | AdvanceResult::Ok(_) => {
+                stream.set_pos(start);
+                DataResult::Data(vec![])
+            }
+            AdvanceResult::Err => DataResult::Err,
+            AdvanceResult::Abort(e) => DataResult::Abort(e),
+        }
+    }
+
+    /// Matches nothing if `symbol` fails, and fails if it matches. Whatever
+    /// `symbol` reports is dropped: its failures let the parse go on, and
+    /// its match is reported as the failure of the `Not`.
+    fn try_advance_not(
+        symbol: &SymbolIdent,
+        stream: &mut CharStream,
+        p: &Parser,
+        ctx: &mut ParseContext,
+    ) -> DataResult {
+        let start = stream.get_pos();
+        let farthest = ctx.farthest;
+        let message = ctx.message.take();
+        let expected = ctx.expected.take();
+        let element = ctx.element;
+        let result = p.advance(symbol, stream, ctx);
+        stream.set_pos(start);
+        ctx.farthest = farthest;
+        ctx.message = message;
+        ctx.expected = expected;
+        ctx.element = element;
+        match result {
+            AdvanceResult::NewNode(_) | AdvanceResult::Ok(_) => DataResult::Err,
+            AdvanceResult::Err => DataResult::Data(vec![]),
+            AdvanceResult::Abort(e) => DataResult::Abort(e),
+        }
     }
 
     fn try_advance_one_of(
         symbols: &[SymbolIdent],
         stream: &mut CharStream,
         p: &Parser,
+        ctx: &mut ParseContext,
     ) -> DataResult {
         for symbol in symbols {
-            match p.get_symbol(symbol).try_advance(stream, p) {
+            match p.advance(symbol, stream, ctx) {
                 AdvanceResult::NewNode(node) => {
                     return DataResult::Data(vec![node])
                 }
                 AdvanceResult::Ok(nodes) => return DataResult::Data(nodes),
                 AdvanceResult::Err => continue,
+                AdvanceResult::Abort(e) => return DataResult::Abort(e),
             }
         }
         DataResult::Err
@@ -308,17 +1000,35 @@ impl Symbol {
 
     fn try_advance_sequence(
         symbols: &[SymbolIdent],
+        capacity: usize,
         stream: &mut CharStream,
         p: &Parser,
+        ctx: &mut ParseContext,
+        mut inner: Option<&mut Range>,
     ) -> DataResult {
-        let mut data = Vec::new();
-        for symbol in symbols {
-            let symbol = p.get_symbol(symbol);
-            match symbol.try_advance(stream, p) {
+        let mut data = Vec::with_capacity(capacity);
+        let mut tokens = 0;
+        for (index, symbol) in symbols.iter().enumerate() {
+            let start = stream.get_pos();
+            match p.advance(symbol, stream, ctx) {
                 AdvanceResult::Ok(mut nodes) => data.append(&mut nodes),
-                AdvanceResult::Err => return DataResult::Err,
+                AdvanceResult::Err => {
+                    ctx.failed_element(start, index, *symbol);
+                    return DataResult::Err;
+                }
                 AdvanceResult::NewNode(node) => data.push(node),
+                AdvanceResult::Abort(e) => return DataResult::Abort(e),
             };
+            // Narrow the span to the elements that are not whitespace.
+            if let Some(inner) = inner.as_deref_mut() {
+                if !p.only_whitespace(symbol) {
+                    if tokens == 0 {
+                        inner.0 = start;
+                    }
+                    inner.1 = stream.get_pos();
+                    tokens += 1;
+                }
+            }
         }
         DataResult::Data(data)
     }
@@ -341,12 +1051,12 @@ impl Symbol {
     }
 
     fn try_advance_any_except(
-        chars: &[char],
+        ranges: &[(char, char)],
         stream: &mut CharStream,
         _p: &Parser,
     ) -> DataResult {
         if let Some(next_char) = stream.peek() {
-            if !chars.contains(next_char) {
+            if !in_ranges(ranges, *next_char) {
                 stream.next();
                 DataResult::Data(vec![])
             } else {
@@ -356,15 +1066,79 @@ impl Symbol {
             DataResult::Err
         }
     }
+
+    fn try_advance_end_of_input(
+        stream: &mut CharStream,
+        _p: &Parser,
+    ) -> DataResult {
+        match stream.peek() {
+            Some(_) => DataResult::Err,
+            None => DataResult::Data(vec![]),
+        }
+    }
 }
-#[derive(Debug)]
+/// Checks a node right after it is built, see [`Parser::on_complete`].
+pub type CompleteHook = Box<dyn Fn(&Node) -> Result<(), String> + Send + Sync>;
+
+/// A [`CompleteHook`] as stored, shared by the copies of a grammar.
+type SharedHook = Arc<dyn Fn(&Node) -> Result<(), String> + Send + Sync>;
+
+/// Converts the raw text of a node, see [`Parser::parse_as`].
+type Converter = Arc<dyn Fn(&str) -> Result<TypedValue, String> + Send + Sync>;
+
 pub struct Parser {
-    symbol_registry: Vec<Symbol>,
-    root_node: Option<SymbolIdent>,
+    pub(crate) symbol_registry: Vec<Symbol>,
+    pub(crate) root_node: Option<SymbolIdent>,
+    backtracking_threshold: Option<usize>,
+    /// Whether a leading `#!` line is skipped, see
+    /// [`Parser::with_shebang_skip`].
+    skip_shebang: bool,
+    /// Upper bound on the nodes built per parse, see
+    /// [`Parser::with_max_nodes`].
+    pub(crate) max_nodes: Option<usize>,
+    /// Upper bound on how deeply symbols nest while parsing, see
+    /// [`Parser::with_max_depth`].
+    pub(crate) max_depth: Option<usize>,
+    hooks: HashMap<String, Vec<SharedHook>>,
+    /// The conversion of the raw text of each rule, see
+    /// [`Parser::parse_as`].
+    converters: HashMap<String, Converter>,
+    /// Stamped on every [`SymbolIdent`] this parser hands out.
+    pub(crate) tag: u32,
+}
+
+impl Debug for Parser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut hooked: Vec<&String> = self.hooks.keys().collect();
+        hooked.sort();
+        let mut converted: Vec<&String> = self.converters.keys().collect();
+        converted.sort();
+        f.debug_struct("Parser")
+            .field("symbol_registry", &self.symbol_registry)
+            .field("root_node", &self.root_node)
+            .field("backtracking_threshold", &self.backtracking_threshold)
+            .field("skip_shebang", &self.skip_shebang)
+            .field("max_nodes", &self.max_nodes)
+            .field("max_depth", &self.max_depth)
+            .field("hooks", &hooked)
+            .field("converters", &converted)
+            .finish()
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct SymbolIdent(pub usize);
+/// Index of a symbol in the registry of the parser that created it. The
+/// ident also carries the tag of that parser, so that using it with another
+/// parser is caught in debug builds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SymbolIdent(pub usize, u32);
+
+/// Source of unique [`Parser`] tags.
+static NEXT_TAG: AtomicU32 = AtomicU32::new(0);
+
+/// A tag no other [`Parser`] carries.
+pub(crate) fn new_tag() -> u32 {
+    NEXT_TAG.fetch_add(1, Ordering::Relaxed)
+}
 
 impl Default for Parser {
     fn default() -> Self {
@@ -377,32 +1151,143 @@ impl Parser {
         Parser {
             symbol_registry: Vec::new(),
             root_node: None,
+            backtracking_threshold: None,
+            skip_shebang: false,
+            max_nodes: None,
+            max_depth: None,
+            hooks: HashMap::new(),
+            converters: HashMap::new(),
+            tag: new_tag(),
         }
     }
 
-    pub fn from_bnf(bnf: &str) -> Self {
+    /// Builds a parser from a grammar. Fails with [`GrammarError::Syntax`]
+    /// if `bnf` does not parse, otherwise with the error of
+    /// [`ParserBuilder::build`].
+    pub fn from_bnf(bnf: &str) -> Result<Self, GrammarError> {
+        Parser::from_bnf_source("<bnf>", bnf)
+    }
+
+    /// Like [`Parser::from_bnf`], recording `source` as the file every rule
+    /// comes from. The symbols [`Parser::get_symbol`] returns report it, with
+    /// the line of their rule, as their `source_location`.
+    pub fn from_bnf_source(
+        source: &str,
+        bnf: &str,
+    ) -> Result<Self, GrammarError> {
+        let root_node = Parser::parse_bnf_to_ast(bnf)?;
+        ParserBuilder::new().with_source(source).build(&root_node)
+    }
+
+    /// Like [`Parser::from_bnf`], with every terminal matching regardless of
+    /// case, see [`ParserBuilder::case_insensitive`].
+    pub fn from_bnf_ci(bnf: &str) -> Result<Self, GrammarError> {
+        let root_node = Parser::parse_bnf_to_ast(bnf)?;
+        ParserBuilder::new().case_insensitive().build(&root_node)
+    }
+
+    /// Parses a grammar without building a parser from it. The returned tree
+    /// has a `ROOT` node with one `STATEMENT` child per rule.
+    pub fn parse_bnf_to_ast(bnf: &str) -> Result<Node, GrammarError> {
         let mut stream = CharStream::from(bnf);
-        let bnf_parser = bnf_parser();
-        let root_node = match bnf_parser.parse(&mut stream) {
-            ParseResult::Ok(n) => n,
-            ParseResult::Err(_) => panic!("BNF Parse failed"),
-        };
-        let parser_builder = ParserBuilder::new();
-        parser_builder.build(&root_node)
+        bnf_parser()
+            .parse(&mut stream)
+            .map_err(GrammarError::Syntax)
+    }
+
+    /// The first symbol emitting nodes of type `name`.
+    pub fn find_symbol(&self, name: &str) -> Option<SymbolIdent> {
+        self.symbol_registry
+            .iter()
+            .position(|s| s.properties.node_name.as_deref() == Some(name))
+            .map(|idx| self.ident(idx))
+    }
+
+    /// Puts `prefix` in front of the name of every symbol that shows up in
+    /// the tree, e.g. `json.` turns `VALUE` into `json.VALUE`, so trees of
+    /// several grammars can be told apart. Hooks and conversions move along
+    /// with the names.
+    ///
+    /// Ignored symbols keep their names, as they only appear in the concrete
+    /// syntax tree. So does `$WHITESPACE`, which is recognized by name.
+    pub fn prefix_node_types(&mut self, prefix: &str) {
+        for symbol in &mut self.symbol_registry {
+            let properties = &mut symbol.properties;
+            if properties.ignore {
+                continue;
+            }
+            if let Some(name) = &mut properties.node_name {
+                if let Some(hooks) = self.hooks.remove(name.as_str()) {
+                    self.hooks.insert(format!("{}{}", prefix, name), hooks);
+                }
+                if let Some(convert) = self.converters.remove(name.as_str()) {
+                    self.converters
+                        .insert(format!("{}{}", prefix, name), convert);
+                }
+                name.insert_str(0, prefix);
+            }
+        }
+    }
+
+    /// A copy of the grammar sharing the tag of `self`, so idents stay
+    /// valid. Hooks and conversions are shared with `self`.
+    pub(crate) fn clone_grammar(&self) -> Parser {
+        Parser {
+            symbol_registry: self.symbol_registry.clone(),
+            root_node: self.root_node,
+            backtracking_threshold: self.backtracking_threshold,
+            skip_shebang: self.skip_shebang,
+            max_nodes: self.max_nodes,
+            max_depth: self.max_depth,
+            hooks: self.hooks.clone(),
+            converters: self.converters.clone(),
+            tag: self.tag,
+        }
+    }
+
+    /// Number of symbols in the registry. Every symbol lives as long as the
+    /// parser, so this bounds its memory use.
+    pub fn symbol_count(&self) -> usize {
+        self.symbol_registry.len()
+    }
+
+    /// Whether `ident` matches nothing but whitespace: a `$WHITESPACE`, or
+    /// one made optional or repeated. Tight sequences leave these out.
+    pub(crate) fn only_whitespace(&self, ident: &SymbolIdent) -> bool {
+        let mut symbol = self.get_symbol(ident);
+        while !symbol.is_whitespace() {
+            match &symbol.symbol_type {
+                SymbolType::Optional(s)
+                | SymbolType::ZeroOrMore(s)
+                | SymbolType::OneOrMore(s)
+                | SymbolType::Repeat { symbol: s, .. } => {
+                    symbol = self.get_symbol(s)
+                }
+                _ => return false,
+            }
+        }
+        true
     }
 
     pub fn get_symbol(&self, idx: &SymbolIdent) -> &Symbol {
+        debug_assert_eq!(self.tag, idx.1, "SymbolIdent of another parser");
         &self.symbol_registry[idx.0]
     }
 
     pub fn get_symbol_mut(&mut self, idx: &SymbolIdent) -> &mut Symbol {
+        debug_assert_eq!(self.tag, idx.1, "SymbolIdent of another parser");
         &mut self.symbol_registry[idx.0]
     }
 
+    /// The ident of the symbol at `idx` in the registry.
+    pub(crate) fn ident(&self, idx: usize) -> SymbolIdent {
+        SymbolIdent(idx, self.tag)
+    }
+
     pub fn insert_symbol(&mut self, symbol: Symbol) -> SymbolIdent {
         let idx = self.symbol_registry.len();
         self.symbol_registry.push(symbol);
-        SymbolIdent(idx)
+        self.ident(idx)
     }
     pub fn root_node(&mut self, root: &SymbolIdent) {
         // if self.get_symbol(root).properties.ignore {
@@ -411,36 +1296,590 @@ impl Parser {
         self.root_node = Some(*root);
     }
 
-    pub fn parse(&self, stream: &mut CharStream) -> ParseResult {
-        match self
-            .get_symbol(&self.root_node.expect("No root node defined"))
-            .try_advance(stream, self)
+    pub fn has_root(&self) -> bool {
+        self.root_node.is_some()
+    }
+
+    /// Runs `hook` on every `rule_name` node as soon as it is built. If the
+    /// hook returns an error the whole parse fails with
+    /// [`ParseError::Rejected`], without trying other alternatives.
+    pub fn on_complete(&mut self, rule_name: &str, hook: CompleteHook) {
+        self.hooks
+            .entry(rule_name.to_owned())
+            .or_default()
+            .push(Arc::from(hook));
+    }
+
+    /// Converts the raw text of every `rule_name` node to `T` as soon as the
+    /// node is built, failing the parse with [`ParseError::Rejected`] at the
+    /// node if it does not convert. Read the value with [`Node::value`].
+    /// Replaces an earlier conversion of the rule.
+    pub fn parse_as<T>(&mut self, rule_name: &str)
+    where
+        T: FromStr + Send + Sync + 'static,
+        T::Err: Display,
+    {
+        self.converters.insert(
+            rule_name.to_owned(),
+            Arc::new(|raw| match raw.parse::<T>() {
+                Ok(value) => Ok(TypedValue(Arc::new(value))),
+                Err(e) => Err(format!(
+                    "Cannot convert {:?} to {}: {}",
+                    raw,
+                    std::any::type_name::<T>(),
+                    e
+                )),
+            }),
+        );
+    }
+
+    /// Drops the hooks and the conversion of `rule_name`.
+    pub(crate) fn remove_hooks(&mut self, rule_name: &str) {
+        self.hooks.remove(rule_name);
+        self.converters.remove(rule_name);
+    }
+
+    /// Converts the raw text of `node` as [`Parser::parse_as`] asked, then
+    /// runs the hooks [`Parser::on_complete`] added.
+    pub(crate) fn run_hooks(&self, node: &mut Node) -> Result<(), ParseError> {
+        if let (Some(convert), NodeData::Raw(raw)) =
+            (self.converters.get(&node.node_type), &node.data)
         {
-            AdvanceResult::Ok(_) => {
-                panic!("Root node is set to be ignored");
-            }
-            AdvanceResult::Err => ParseResult::Err(stream.get_pos()),
-            AdvanceResult::NewNode(n) => {
-                return match stream.peek() {
-                    Some(_) => ParseResult::Err(stream.get_pos()),
-                    None => ParseResult::Ok(n),
+            match convert(raw) {
+                Ok(value) => node.value = Some(value),
+                Err(message) => {
+                    return Err(ParseError::Rejected {
+                        message,
+                        loc: node.loc.clone(),
+                    })
                 }
             }
         }
+        let hooks = match self.hooks.get(&node.node_type) {
+            Some(hooks) => hooks,
+            None => return Ok(()),
+        };
+        for hook in hooks {
+            hook(node).map_err(|message| ParseError::Rejected {
+                message,
+                loc: node.loc.clone(),
+            })?;
+        }
+        Ok(())
     }
-}
 
-impl Parser {
-    pub fn terminal(
-        &mut self,
-        char: char,
-        props: Option<SymbolProperties>,
-    ) -> SymbolIdent {
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+    pub fn parse(&self, stream: &mut CharStream) -> ParseResult {
+        self.parse_in(self.root_node, stream, &mut ParseContext::new(self))
+    }
+
+    /// Parses the whole input as the first of the named `rules` that matches
+    /// it, for grammars with several entry points, and returns the name of
+    /// that rule with the tree. If none matches, fails with the error of
+    /// the rule that got farthest, or [`ParseError::NoRoot`] if `rules` is
+    /// empty.
+    ///
+    /// Panics if a rule does not exist.
+    pub fn parse_any_of(
+        &self,
+        rules: &[&str],
+        stream: &mut CharStream,
+    ) -> Result<(String, Node), ParseError> {
+        let start = stream.get_pos();
+        let mut farthest: Option<ParseError> = None;
+        for rule in rules {
+            let ident = self
+                .find_symbol(rule)
+                .unwrap_or_else(|| panic!("Unknown rule {}", rule));
+            let mut ctx = ParseContext::new(self);
+            let err = match self.parse_in(Some(ident), stream, &mut ctx) {
+                Ok(node) => return Ok((rule.to_string(), node)),
+                Err(err @ ParseError::Syntax { .. }) => err,
+                Err(err) => return Err(err),
+            };
+            stream.set_pos(start);
+            let pos = |err: &ParseError| match err {
+                ParseError::Syntax { pos, .. } => pos.index(),
+                _ => 0,
+            };
+            if farthest.as_ref().is_none_or(|f| pos(&err) > pos(f)) {
+                farthest = Some(err);
+            }
+        }
+        Err(farthest.unwrap_or(ParseError::NoRoot))
+    }
+
+    /// Parses into the concrete syntax tree: every symbol that matched is a
+    /// node, including ignored ones, which [`Parser::parse`] flattens away.
+    /// Unnamed symbols are named after their kind, e.g. `Terminal`.
+    pub fn parse_cst(&self, stream: &mut CharStream) -> ParseResult {
+        let mut ctx = ParseContext::new(self);
+        ctx.concrete = true;
+        self.parse_in(self.root_node, stream, &mut ctx)
+    }
+
+    /// Like [`Parser::parse`], but succeeds without consuming the whole
+    /// input. The stream is left right after the matched prefix.
+    pub fn parse_partial(&self, stream: &mut CharStream) -> ParseResult {
+        self.parse_partial_in(
+            self.root_node,
+            stream,
+            &mut ParseContext::new(self),
+        )
+    }
+
+    /// The number of chars `ident` matches if it is always the same, such as
+    /// for chars, literals and sequences or alternatives of those.
+    pub fn fixed_length(&self, ident: &SymbolIdent) -> Option<usize> {
+        self.fixed_length_of(ident, &mut vec![false; self.symbol_count()])
+    }
+
+    fn fixed_length_of(
+        &self,
+        ident: &SymbolIdent,
+        visiting: &mut Vec<bool>,
+    ) -> Option<usize> {
+        if std::mem::replace(&mut visiting[ident.0], true) {
+            return None;
+        }
+        let length = match &self.get_symbol(ident).symbol_type {
+            SymbolType::Terminal(_)
+            | SymbolType::AnyExcept(_)
+            | SymbolType::CharSet(_) => Some(1),
+            SymbolType::Literal(s) => Some(s.chars().count()),
+            SymbolType::LiteralSet(trie) => {
+                let lengths: Vec<usize> =
+                    trie.words().iter().map(|s| s.chars().count()).collect();
+                match lengths.split_first() {
+                    Some((first, rest)) if rest.iter().all(|l| l == first) => {
+                        Some(*first)
+                    }
+                    _ => None,
+                }
+            }
+            SymbolType::Take(n) => Some(*n),
+            SymbolType::EndOfInput
+            | SymbolType::Lookbehind { .. }
+            | SymbolType::And(_)
+            | SymbolType::Not(_) => Some(0),
+            SymbolType::Sequence(s) => {
+                s.iter().map(|id| self.fixed_length_of(id, visiting)).sum()
+            }
+            SymbolType::Repeat {
+                symbol,
+                min,
+                max: Some(max),
+            } if min == max => {
+                Some(self.fixed_length_of(symbol, visiting)? * min)
+            }
+            SymbolType::OneOf(s) => {
+                let lengths = s
+                    .iter()
+                    .map(|id| self.fixed_length_of(id, visiting))
+                    .collect::<Option<Vec<usize>>>()?;
+                match lengths.split_first() {
+                    Some((first, rest)) if rest.iter().all(|l| l == first) => {
+                        Some(*first)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        visiting[ident.0] = false;
+        length
+    }
+
+    /// Whether `ident`, which always matches `length` chars, matches the
+    /// input right before the position of `stream`.
+    pub(crate) fn matches_behind(
+        &self,
+        ident: &SymbolIdent,
+        length: usize,
+        stream: &CharStream,
+    ) -> bool {
+        let end = stream.get_pos();
+        let start = match stream.pos_before(length) {
+            Some(start) => start,
+            None => return false,
+        };
+        let mut scratch = stream.clone_fresh();
+        scratch.set_pos(start);
+        let matched = matches!(
+            self.advance(ident, &mut scratch, &mut ParseContext::new(self)),
+            AdvanceResult::NewNode(_) | AdvanceResult::Ok(_)
+        );
+        matched && scratch.get_pos().index() == end.index()
+    }
+
+    /// Like [`Parser::parse`] for input that arrives in chunks. If the parse
+    /// fails after running into the end of the input, it fails with
+    /// [`ParseError::Incomplete`] and rewinds the stream, so that it can be
+    /// parsed again once [`CharStream::push_str`] added more.
+    pub fn parse_streaming(&self, stream: &mut CharStream) -> ParseResult {
+        let start = stream.get_pos();
+        stream.clear_hit_end();
+        match self.parse(stream) {
+            Err(ParseError::Syntax { .. }) if stream.hit_end() => {
+                stream.set_pos(start);
+                Err(ParseError::Incomplete)
+            }
+            result => result,
+        }
+    }
+
+    /// Whether `ident` matches at the current position of `stream`. The
+    /// stream is left untouched, the match runs on a copy of it.
+    pub fn matches_at(&self, ident: &SymbolIdent, stream: &CharStream) -> bool {
+        let mut scratch = stream.clone_fresh();
+        scratch.set_pos(stream.get_pos());
+        matches!(
+            self.advance(ident, &mut scratch, &mut ParseContext::new(self)),
+            AdvanceResult::NewNode(_) | AdvanceResult::Ok(_)
+        )
+    }
+
+    /// Counts how often each rule is attempted during a parse, see
+    /// [`Parser::parse_with_warnings`].
+    pub fn with_backtracking_warnings(mut self, threshold: usize) -> Self {
+        self.backtracking_threshold = Some(threshold);
+        self
+    }
+
+    /// Skips a line starting with `#!`, up to and including its `\n`, where
+    /// a parse begins, as script files start with one. Node positions still
+    /// count from the start of the input, so the content after a shebang
+    /// starts on line 2.
+    pub fn with_shebang_skip(mut self) -> Self {
+        self.skip_shebang = true;
+        self
+    }
+
+    /// Aborts a parse with [`ParseError::TooManyNodes`] once it has built
+    /// more than `max` nodes, bounding the size of the tree on untrusted
+    /// input. Nodes dropped again by backtracking count as well.
+    pub fn with_max_nodes(mut self, max: usize) -> Self {
+        self.max_nodes = Some(max);
+        self
+    }
+
+    /// Aborts a parse with [`ParseError::TooDeep`] once more than `max`
+    /// symbols are being matched inside each other. Each level of nesting
+    /// in the input takes several symbols, e.g. about five per array in
+    /// JSON. Parsing recurses per symbol, so this keeps deeply nested
+    /// untrusted input from overflowing the stack.
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Advances `stream` over a leading shebang line if enabled, see
+    /// [`Parser::with_shebang_skip`].
+    pub(crate) fn skip_shebang(&self, stream: &mut CharStream) {
+        if self.skip_shebang && eat("#!", stream) {
+            match_rest_of_line(stream);
+            stream.next();
+        }
+    }
+
+    /// Parses like [`Parser::parse`] and additionally reports the named rules
+    /// that were attempted more than `threshold` times the input length,
+    /// most attempted first. A symptom of catastrophic backtracking.
+    ///
+    /// Only reports anything after
+    /// [`Parser::with_backtracking_warnings`].
+    pub fn parse_with_warnings(
+        &self,
+        stream: &mut CharStream,
+    ) -> (ParseResult, Vec<(String, usize)>) {
+        let mut ctx = ParseContext::new(self);
+        let result = self.parse_in(self.root_node, stream, &mut ctx);
+        let warnings = match (self.backtracking_threshold, ctx.attempts) {
+            (Some(threshold), Some(attempts)) => {
+                let limit = threshold * stream.len().max(1);
+                let mut warnings: Vec<(String, usize)> = self
+                    .symbol_registry
+                    .iter()
+                    .zip(attempts)
+                    .filter(|(symbol, count)| {
+                        symbol.properties.node_name.is_some() && *count > limit
+                    })
+                    .map(|(symbol, count)| (symbol.name().to_owned(), count))
+                    .collect();
+                warnings.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+                warnings
+            }
+            _ => Vec::new(),
+        };
+        (result, warnings)
+    }
+
+    fn parse_in(
+        &self,
+        root: Option<SymbolIdent>,
+        stream: &mut CharStream,
+        ctx: &mut ParseContext,
+    ) -> ParseResult {
+        match self.parse_partial_in(root, stream, ctx) {
+            ParseResult::Ok(n) => match stream.peek() {
+                Some(_) => ParseResult::Err(ParseError::Syntax {
+                    pos: ctx.farthest_from(stream.get_pos()),
+                    message: None,
+                    element: None,
+                    expected: ctx.expected_chars(),
+                }),
+                None => ParseResult::Ok(n),
+            },
+            err => err,
+        }
+    }
+
+    fn parse_partial_in(
+        &self,
+        root: Option<SymbolIdent>,
+        stream: &mut CharStream,
+        ctx: &mut ParseContext,
+    ) -> ParseResult {
+        let root = root.ok_or(ParseError::NoRoot)?;
+        if root.0 >= self.symbol_registry.len() {
+            return ParseResult::Err(ParseError::InvalidRoot(root));
+        }
+        self.skip_shebang(stream);
+        let result = self.advance(&root, stream, ctx);
+        if let Some(err) = stream.take_error() {
+            return ParseResult::Err(err);
+        }
+        match result {
+            AdvanceResult::Ok(_) => {
+                panic!("Root node is set to be ignored");
+            }
+            AdvanceResult::Err => match ctx.error_message() {
+                Some((pos, message)) => ParseResult::Err(ParseError::Syntax {
+                    pos,
+                    message: Some(message),
+                    element: self.failed_element(ctx),
+                    expected: ctx.expected_chars(),
+                }),
+                None => ParseResult::Err(ParseError::Syntax {
+                    pos: ctx.farthest_from(stream.get_pos()),
+                    message: None,
+                    element: self.failed_element(ctx),
+                    expected: ctx.expected_chars(),
+                }),
+            },
+            AdvanceResult::NewNode(n) => ParseResult::Ok(n),
+            AdvanceResult::Abort(e) => ParseResult::Err(e),
+        }
+    }
+
+    fn advance(
+        &self,
+        ident: &SymbolIdent,
+        stream: &mut CharStream,
+        ctx: &mut ParseContext,
+    ) -> AdvanceResult {
+        if let Some(attempts) = &mut ctx.attempts {
+            attempts[ident.0] += 1;
+        }
+        if self.max_depth.is_some_and(|max| ctx.depth >= max) {
+            return AdvanceResult::Abort(ParseError::TooDeep);
+        }
+        ctx.depth += 1;
+        let symbol = self.get_symbol(ident);
+        let named = symbol.properties.node_name.is_some();
+        if named {
+            ctx.rules.push(*ident);
+        }
+        let key = (ident.0, stream.get_pos().index());
+        let result = if !symbol.properties.memoize {
+            symbol.try_advance(stream, self, ctx)
+        } else if let Some((result, end)) = ctx.memo.get(&key) {
+            stream.set_pos(*end);
+            result.clone()
+        } else {
+            let result = symbol.try_advance(stream, self, ctx);
+            ctx.memo.insert(key, (result.clone(), stream.get_pos()));
+            result
+        };
+        if named {
+            ctx.rules.pop();
+        }
+        ctx.depth -= 1;
+        result
+    }
+
+    /// Describes the sequence element recorded by
+    /// [`ParseContext::failed_element`].
+    fn failed_element(&self, ctx: &ParseContext) -> Option<Box<FailedElement>> {
+        let (pos, rule, index, ident) = ctx.element?;
+        let symbol = self.get_symbol(&ident);
+        Some(Box::new(FailedElement {
+            pos,
+            rule: rule.map(|rule| self.get_symbol(&rule).name().to_owned()),
+            index,
+            expected: match &symbol.properties.node_name {
+                Some(name) => name.clone(),
+                None => symbol.repr_body(self),
+            },
+        }))
+    }
+}
+
+/// State of a single parse run.
+#[derive(Debug)]
+struct ParseContext {
+    /// How often each symbol was attempted, if counting is enabled.
+    attempts: Option<Vec<usize>>,
+    /// Results of memoized symbols by symbol and start index, with the
+    /// position they ended at.
+    memo: HashMap<(usize, usize), (AdvanceResult, Position)>,
+    /// Whether ignored symbols produce nodes too.
+    concrete: bool,
+    /// How many nodes were built so far.
+    nodes: usize,
+    /// How many symbols are being matched inside each other.
+    depth: usize,
+    /// The farthest position any symbol failed to match at.
+    farthest: Option<Position>,
+    /// A custom error message of a symbol that failed while the farthest
+    /// failure was reached, with the index of that failure.
+    message: Option<(usize, String)>,
+    /// The named symbols being matched, innermost last.
+    rules: Vec<SymbolIdent>,
+    /// The chars that would have let a leaf symbol match at the farthest
+    /// place one failed, with the index of that place.
+    expected: Option<(usize, Vec<char>)>,
+    /// The farthest failing sequence element: where it started, the
+    /// innermost named symbol, its index in the sequence and the element.
+    element: Option<(Position, Option<SymbolIdent>, usize, SymbolIdent)>,
+}
+
+impl ParseContext {
+    fn new(p: &Parser) -> Self {
+        ParseContext {
+            attempts: p
+                .backtracking_threshold
+                .map(|_| vec![0; p.symbol_registry.len()]),
+            memo: HashMap::new(),
+            concrete: false,
+            nodes: 0,
+            depth: 0,
+            farthest: None,
+            message: None,
+            rules: Vec::new(),
+            expected: None,
+            element: None,
+        }
+    }
+
+    /// Records that element `index` of a sequence, starting at `start`,
+    /// failed to match. Of elements failing at the same place the outermost
+    /// one is kept, as it is recorded last.
+    fn failed_element(
+        &mut self,
+        start: Position,
+        index: usize,
+        element: SymbolIdent,
+    ) {
+        let recorded = self.element.map(|(pos, ..)| pos.index());
+        if recorded.is_none_or(|recorded| start.index() >= recorded) {
+            let rule = self.rules.last().copied();
+            self.element = Some((start, rule, index, element));
+        }
+    }
+
+    /// Records that a symbol starting at `start` failed to match, with
+    /// `before` the farthest failure when it was attempted.
+    ///
+    /// A symbol with an error message claims the farthest failure if it
+    /// failed there itself or reached it during its attempt, not if an
+    /// alternative tried before it did. The innermost such symbol wins.
+    fn failed(
+        &mut self,
+        start: Position,
+        before: Option<Position>,
+        error_message: Option<&str>,
+    ) {
+        if self.farthest.is_none_or(|f| start.index() > f.index()) {
+            self.farthest = Some(start);
+        }
+        let farthest = self.farthest.map_or(0, |f| f.index());
+        if let Some(message) = error_message {
+            let claimed = self.message.as_ref().map(|(idx, _)| *idx);
+            let reached = farthest == start.index()
+                || before.is_none_or(|b| farthest > b.index());
+            if reached && claimed != Some(farthest) {
+                self.message = Some((farthest, message.to_owned()));
+            }
+        }
+    }
+
+    /// The farthest position any symbol failed at, or `pos` if that is
+    /// farther.
+    fn farthest_from(&self, pos: Position) -> Position {
+        match self.farthest {
+            Some(farthest) if farthest.index() > pos.index() => farthest,
+            _ => pos,
+        }
+    }
+
+    /// Records that a leaf symbol starting at `start` failed, with `push`
+    /// adding the chars it would have matched. Runs `push` only where the
+    /// chars are still of interest, and reuses the buffer, as this happens
+    /// on every failed char.
+    fn expect(&mut self, start: Position, push: impl FnOnce(&mut Vec<char>)) {
+        let idx = start.index();
+        match &mut self.expected {
+            Some((at, _)) if *at > idx => {}
+            Some((at, chars)) if *at == idx => push(chars),
+            Some((at, chars)) => {
+                let old = chars.len();
+                push(chars);
+                if chars.len() > old {
+                    chars.drain(..old);
+                    *at = idx;
+                }
+            }
+            None => {
+                let mut chars = Vec::new();
+                push(&mut chars);
+                if !chars.is_empty() {
+                    self.expected = Some((idx, chars));
+                }
+            }
+        }
+    }
+
+    /// The chars recorded by [`ParseContext::expect`], sorted.
+    fn expected_chars(&self) -> Vec<char> {
+        let mut chars = self
+            .expected
+            .as_ref()
+            .map_or(Vec::new(), |(_, chars)| chars.clone());
+        chars.sort();
+        chars.dedup();
+        chars
+    }
+
+    /// The custom error message for the farthest failure, if any, with the
+    /// position of that failure.
+    fn error_message(&self) -> Option<(Position, String)> {
+        let farthest = self.farthest?;
+        match &self.message {
+            Some((idx, message)) if *idx == farthest.index() => {
+                Some((farthest, message.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Parser {
+    pub fn terminal(
+        &mut self,
+        char: char,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
 
         self.insert_symbol(Symbol {
             symbol_type: SymbolType::Terminal(char),
@@ -448,32 +1887,198 @@ impl Parser {
         })
     }
 
+    /// Matches one char from `start` to `end`, both included.
+    pub fn range(
+        &mut self,
+        start: char,
+        end: char,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        assert!(start <= end, "Invalid range {:?}..{:?}", start, end);
+        self.one_of_ranges(&[(start, end)], props)
+    }
+
+    /// Matches any char inside one of the inclusive `ranges`.
+    pub fn one_of_ranges(
+        &mut self,
+        ranges: &[(char, char)],
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::CharSet(merge_ranges(ranges)),
+            properties,
+        })
+    }
+
     pub fn any_except(
         &mut self,
         chars: &[char],
         props: Option<SymbolProperties>,
     ) -> SymbolIdent {
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+        let ranges: Vec<(char, char)> =
+            chars.iter().map(|c| (*c, *c)).collect();
+        self.any_except_ranges(&ranges, props)
+    }
 
+    /// Matches any char that is not inside one of the inclusive `ranges`.
+    pub fn any_except_ranges(
+        &mut self,
+        ranges: &[(char, char)],
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
         self.insert_symbol(Symbol {
-            symbol_type: SymbolType::AnyExcept(chars.to_vec()),
+            symbol_type: SymbolType::AnyExcept(merge_ranges(ranges)),
             properties,
         })
     }
+
+    pub fn end_of_input(
+        &mut self,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::EndOfInput,
+            properties,
+        })
+    }
+
+    /// Always matches, consuming nothing, and leaves an empty `name` node at
+    /// the current position, to mark places in the tree.
+    pub fn marker(&mut self, name: &str) -> SymbolIdent {
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::Sequence(Vec::new()),
+            properties: SymbolProperties::new(name, false, false).unwrap(),
+        })
+    }
+
+    /// Matches nothing, but only where the input right before matches
+    /// `symbol`, e.g. a keyword that is one only after a digit.
+    ///
+    /// Panics unless `symbol` always matches the same number of chars, see
+    /// [`Parser::fixed_length`].
+    pub fn lookbehind(
+        &mut self,
+        symbol: &SymbolIdent,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let length = self.fixed_length(symbol).unwrap_or_else(|| {
+            panic!("Lookbehind of {} is not of fixed length", symbol.0)
+        });
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::Lookbehind {
+                symbol: *symbol,
+                length,
+            },
+            properties,
+        })
+    }
+
+    /// Matches nothing, but only where `symbol` matches, e.g. to require
+    /// what follows without consuming it. Nodes of `symbol` are dropped.
+    pub fn and(
+        &mut self,
+        symbol: &SymbolIdent,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::And(*symbol),
+            properties,
+        })
+    }
+
+    /// Matches nothing, but only where `symbol` does not match, e.g. for
+    /// any char but a closing quote.
+    pub fn not(
+        &mut self,
+        symbol: &SymbolIdent,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::Not(*symbol),
+            properties,
+        })
+    }
+
+    /// Matches the rest of the current line, up to but not including the
+    /// next `\n` or the end of the input, and possibly nothing. Unless
+    /// ignored, the node holds the matched text raw.
+    pub fn rest_of_line(
+        &mut self,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::RestOfLine,
+            properties,
+        })
+    }
+
+    /// Matches exactly `n` chars of any kind, for fixed-width fields. Fails
+    /// if fewer remain. Unless ignored, the node holds the chars raw.
+    pub fn take(
+        &mut self,
+        n: usize,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::Take(n),
+            properties,
+        })
+    }
+
+    /// Matches `open` up to its matching `close`, counting nested pairs.
+    /// Unless ignored, the node holds the text between the delimiters raw.
+    /// Input that ends before the pair is closed does not match.
+    pub fn balanced(
+        &mut self,
+        open: char,
+        close: char,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::Balanced { open, close },
+            properties,
+        })
+    }
+
+    /// Matches a block comment from `open` to its matching `close`, such as
+    /// `/* a /* b */ c */`, consuming nested comments along with it. The
+    /// symbol is ignored by default, so it can be dropped into sequences
+    /// wherever comments may appear. Unless ignored, the node holds the
+    /// whole comment raw. An unterminated comment does not match.
+    pub fn nested_comment(
+        &mut self,
+        open: &str,
+        close: &str,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        if open.is_empty() || close.is_empty() {
+            panic!("Comment delimiters must not be empty");
+        }
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::NestedComment {
+                open: open.to_owned(),
+                close: close.to_owned(),
+            },
+            properties,
+        })
+    }
+
     pub fn one_of(
         &mut self,
         symbols: Vec<&SymbolIdent>,
         props: Option<SymbolProperties>,
     ) -> SymbolIdent {
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+        let properties = props.unwrap_or_default();
         self.insert_symbol(Symbol {
             symbol_type: SymbolType::OneOf(
                 symbols.into_iter().copied().collect(),
@@ -482,6 +2087,20 @@ impl Parser {
         })
     }
 
+    /// Like [`Parser::one_of`], but the alternatives are tried by ascending
+    /// `priority` instead of by their order in `symbols`. Alternatives with
+    /// the same priority keep their order.
+    pub fn one_of_prioritized(
+        &mut self,
+        symbols: Vec<(u32, &SymbolIdent)>,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let mut symbols = symbols;
+        symbols.sort_by_key(|(priority, _)| *priority);
+        let symbols = symbols.into_iter().map(|(_, ident)| ident).collect();
+        self.one_of(symbols, props)
+    }
+
     pub fn add_ident(
         &mut self,
         ident: &SymbolIdent,
@@ -496,11 +2115,7 @@ impl Parser {
         symbols: Vec<&SymbolIdent>,
         props: Option<SymbolProperties>,
     ) -> SymbolIdent {
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+        let properties = props.unwrap_or_default();
         self.insert_symbol(Symbol {
             symbol_type: SymbolType::Sequence(
                 symbols.into_iter().copied().collect(),
@@ -513,11 +2128,7 @@ impl Parser {
         symbol: &SymbolIdent,
         props: Option<SymbolProperties>,
     ) -> SymbolIdent {
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+        let properties = props.unwrap_or_default();
         self.insert_symbol(Symbol {
             symbol_type: SymbolType::Optional(*symbol),
             properties,
@@ -528,11 +2139,7 @@ impl Parser {
         symbol: &SymbolIdent,
         props: Option<SymbolProperties>,
     ) -> SymbolIdent {
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+        let properties = props.unwrap_or_default();
 
         self.insert_symbol(Symbol {
             symbol_type: SymbolType::OneOrMore(*symbol),
@@ -544,27 +2151,85 @@ impl Parser {
         symbol: &SymbolIdent,
         props: Option<SymbolProperties>,
     ) -> SymbolIdent {
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+        let properties = props.unwrap_or_default();
 
         self.insert_symbol(Symbol {
             symbol_type: SymbolType::ZeroOrMore(*symbol),
             properties,
         })
     }
+    /// A possibly empty list of `item`s separated by `separator`, e.g.
+    /// `a,b,c`. `trailing` controls a separator after the last item.
+    pub fn separated(
+        &mut self,
+        item: &SymbolIdent,
+        separator: &SymbolIdent,
+        trailing: TrailingMode,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        self.separated_at_least(item, separator, 0, trailing, props)
+    }
+
+    /// Like [`Parser::separated`], but with at least `min` items, e.g.
+    /// `a,b` and longer for `min` 2.
+    pub fn separated_at_least(
+        &mut self,
+        item: &SymbolIdent,
+        separator: &SymbolIdent,
+        min: usize,
+        trailing: TrailingMode,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        let rest_item = self.sequence(vec![separator, item], None);
+        let rest = self.zero_or_more(&rest_item, None);
+        let trailing_separator = match trailing {
+            TrailingMode::Never => None,
+            TrailingMode::Allowed => Some(self.optional(separator, None)),
+            TrailingMode::RequiredIfNonEmpty => Some(*separator),
+        };
+        let mut elements = vec![item];
+        for _ in 1..min {
+            elements.push(&rest_item);
+        }
+        elements.push(&rest);
+        elements.extend(trailing_separator.as_ref());
+        if min == 0 {
+            let non_empty = self.sequence(elements, None);
+            self.optional(&non_empty, props)
+        } else {
+            self.sequence(elements, props)
+        }
+    }
+
+    /// Matches `symbol` at least `min` and at most `max` times, as often as
+    /// possible, or without an upper bound if `max` is `None`.
+    pub fn repeat(
+        &mut self,
+        symbol: &SymbolIdent,
+        min: usize,
+        max: Option<usize>,
+        props: Option<SymbolProperties>,
+    ) -> SymbolIdent {
+        if let Some(max) = max {
+            assert!(min <= max, "Invalid repetition {}..{}", min, max);
+        }
+        let properties = props.unwrap_or_default();
+        self.insert_symbol(Symbol {
+            symbol_type: SymbolType::Repeat {
+                symbol: *symbol,
+                min,
+                max,
+            },
+            properties,
+        })
+    }
+
     pub fn sequence_chars(
         &mut self,
         string: &str,
         props: Option<SymbolProperties>,
     ) -> SymbolIdent {
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+        let properties = props.unwrap_or_default();
         let chars = string.chars();
         let symbol_type = SymbolType::Sequence(
             chars.map(|c| self.terminal(c, None)).collect(),
@@ -580,11 +2245,7 @@ impl Parser {
         props: Option<SymbolProperties>,
     ) -> SymbolIdent {
         let chars = string.chars();
-        let properties = if let Some(p) = props {
-            p
-        } else {
-            Default::default()
-        };
+        let properties = props.unwrap_or_default();
         let symbol_type =
             SymbolType::OneOf(chars.map(|c| self.terminal(c, None)).collect());
         self.insert_symbol(Symbol {
@@ -594,12 +2255,252 @@ impl Parser {
     }
 }
 
-#[derive(Debug)]
-pub enum ParseResult {
-    Ok(Node),
-    Err(Position),
+/// Advances `stream` over an `open` ... `close` pair, including nested
+/// pairs. Returns false if the stream does not start with `open` or ends
+/// before the pair is closed.
+pub(crate) fn match_balanced(
+    open: char,
+    close: char,
+    stream: &mut CharStream,
+) -> bool {
+    if stream.peek() != Some(&open) {
+        return false;
+    }
+    stream.next();
+    let mut depth = 1;
+    for c in stream.by_ref() {
+        if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return true;
+            }
+        } else if c == open {
+            depth += 1;
+        }
+    }
+    false
+}
+
+/// Advances `stream` over an `open` ... `close` comment, including nested
+/// comments. Returns false if the stream does not start with `open` or ends
+/// before the comment is closed.
+pub(crate) fn match_nested_comment(
+    open: &str,
+    close: &str,
+    stream: &mut CharStream,
+) -> bool {
+    if !eat(open, stream) {
+        return false;
+    }
+    let mut depth = 1;
+    while depth > 0 {
+        if eat(close, stream) {
+            depth -= 1;
+        } else if eat(open, stream) {
+            depth += 1;
+        } else if stream.next().is_none() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Advances `stream` up to the next `\n` or the end of the input.
+pub(crate) fn match_rest_of_line(stream: &mut CharStream) {
+    while matches!(stream.peek(), Some(c) if *c != '\n') {
+        stream.next();
+    }
 }
 
+/// Advances `stream` over the next `n` chars if there are that many.
+pub(crate) fn match_take(n: usize, stream: &mut CharStream) -> bool {
+    let start = stream.get_pos();
+    for _ in 0..n {
+        if stream.next().is_none() {
+            stream.set_pos(start);
+            return false;
+        }
+    }
+    true
+}
+
+/// Advances `stream` over `s` if it starts with it.
+pub(crate) fn eat(s: &str, stream: &mut CharStream) -> bool {
+    let start = stream.get_pos();
+    for c in s.chars() {
+        if stream.next() != Some(c) {
+            stream.set_pos(start);
+            return false;
+        }
+    }
+    true
+}
+
+/// The previous Unicode scalar value, skipping the surrogate gap.
+pub(crate) fn char_before(c: char) -> Option<char> {
+    match c {
+        '\0' => None,
+        '\u{e000}' => Some('\u{d7ff}'),
+        c => char::from_u32(c as u32 - 1),
+    }
+}
+
+/// The next Unicode scalar value, skipping the surrogate gap.
+pub(crate) fn char_after(c: char) -> Option<char> {
+    match c {
+        char::MAX => None,
+        '\u{d7ff}' => Some('\u{e000}'),
+        c => char::from_u32(c as u32 + 1),
+    }
+}
+
+/// Whether `c` lies in one of the sorted, non-overlapping `ranges`.
+#[inline]
+pub(crate) fn in_ranges(ranges: &[(char, char)], c: char) -> bool {
+    let idx = ranges.partition_point(|(_, end)| *end < c);
+    ranges.get(idx).is_some_and(|(start, _)| *start <= c)
+}
+
+/// Sorts the ranges and merges overlapping or adjacent ones.
+pub(crate) fn merge_ranges(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut sorted: Vec<(char, char)> =
+        ranges.iter().copied().filter(|(s, e)| s <= e).collect();
+    sorted.sort();
+    let mut merged: Vec<(char, char)> = Vec::with_capacity(sorted.len());
+    for (start, end) in sorted {
+        match merged.last_mut() {
+            Some((_, last_end)) if start as u32 <= *last_end as u32 + 1 => {
+                *last_end = (*last_end).max(end);
+            }
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// How [`Parser::separated`] treats a separator after the last item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingMode {
+    /// `a,b` but not `a,b,`.
+    Never,
+    /// Both `a,b` and `a,b,`.
+    Allowed,
+    /// `a,b,` but not `a,b`. An empty list has no separator at all.
+    RequiredIfNonEmpty,
+}
+
+pub type ParseResult = Result<Node, ParseError>;
+
+/// The most chars a char range may hold to be listed in
+/// [`ParseError::Syntax`] as expected.
+pub(crate) const MAX_EXPECTED_CHARS: usize = 64;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// No root symbol was set with [`Parser::root_node`].
+    NoRoot,
+    /// The root symbol does not belong to this parser.
+    InvalidRoot(SymbolIdent),
+    /// The input does not match the grammar. `pos` is the farthest place
+    /// any symbol failed at, also in alternatives that were abandoned.
+    /// `message` is the error message of the rule that failed, if it has
+    /// one, `element` the farthest sequence element that failed and
+    /// `expected` the chars that would have matched there, sorted.
+    Syntax {
+        pos: Position,
+        message: Option<String>,
+        element: Option<Box<FailedElement>>,
+        expected: Vec<char>,
+    },
+    /// A hook registered with [`Parser::on_complete`] rejected a node.
+    Rejected { message: String, loc: Range },
+    /// The input ended before the grammar could decide, see
+    /// [`Parser::parse_streaming`].
+    Incomplete,
+    /// The tree grew beyond the limit set with [`Parser::with_max_nodes`].
+    TooManyNodes,
+    /// The input nests deeper than the limit set with
+    /// [`Parser::with_max_depth`].
+    TooDeep,
+    /// The parser went back to a position that a stream from
+    /// [`CharStream::from_reader`] had already dropped from its buffer.
+    Evicted(Position),
+    /// The reader of a stream from [`CharStream::from_reader`] failed.
+    Read(String),
+}
+
+/// A sequence element that failed to match, see [`ParseError::Syntax`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailedElement {
+    /// Where the element was expected.
+    pub pos: Position,
+    /// The innermost named rule the sequence belongs to.
+    pub rule: Option<String>,
+    /// Index of the element in the sequence.
+    pub index: usize,
+    /// The element, by name or in BNF-like notation.
+    pub expected: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::NoRoot => write!(f, "No root node defined"),
+            ParseError::InvalidRoot(ident) => {
+                write!(f, "Root node {} is not a known symbol", ident.0)
+            }
+            ParseError::Syntax {
+                pos,
+                message: Some(message),
+                ..
+            } => write!(f, "{} at {}", message, pos),
+            ParseError::Syntax {
+                element: Some(element),
+                ..
+            } => match &element.rule {
+                Some(rule) => write!(
+                    f,
+                    "Expected {} in {} at {}",
+                    element.expected, rule, element.pos
+                ),
+                None => {
+                    write!(
+                        f,
+                        "Expected {} at {}",
+                        element.expected, element.pos
+                    )
+                }
+            },
+            ParseError::Syntax { pos, expected, .. }
+                if !expected.is_empty() =>
+            {
+                let chars: Vec<String> = expected
+                    .iter()
+                    .map(|c| format!("'{}'", c.escape_debug()))
+                    .collect();
+                write!(f, "Expected one of {} at {}", chars.join(", "), pos)
+            }
+            ParseError::Syntax { pos, .. } => {
+                write!(f, "Syntax error at {}", pos)
+            }
+            ParseError::Rejected { message, loc } => {
+                write!(f, "{} at {}", message, loc)
+            }
+            ParseError::Incomplete => write!(f, "Unexpected end of input"),
+            ParseError::TooManyNodes => write!(f, "Too many nodes"),
+            ParseError::TooDeep => write!(f, "Input nested too deeply"),
+            ParseError::Evicted(pos) => {
+                write!(f, "Position {} is no longer buffered", pos)
+            }
+            ParseError::Read(message) => {
+                write!(f, "Failed to read input: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -629,6 +2530,24 @@ mod tests {
         assert!(matches!(parser.parse(&mut stream), ParseResult::Err(_)));
     }
 
+    #[test]
+    fn no_root() {
+        let mut parser = Parser::new();
+        parser.terminal('a', None);
+
+        let mut stream = CharStream::from("a");
+
+        assert!(!parser.has_root());
+        assert_eq!(Err(ParseError::NoRoot), parser.parse(&mut stream));
+
+        parser.root_node(&parser.ident(1));
+        assert!(parser.has_root());
+        assert_eq!(
+            Err(ParseError::InvalidRoot(parser.ident(1))),
+            parser.parse(&mut stream)
+        );
+    }
+
     #[test]
     fn literal_rewind() {
         let mut parser = Parser::new();
@@ -694,6 +2613,628 @@ mod tests {
         assert!(matches!(parser.parse(&mut stream3), ParseResult::Err(_)));
     }
 
+    #[test]
+    fn any_except_ranges() {
+        let mut parser = Parser::new();
+        let symbol = parser.any_except_ranges(
+            &[('\u{0}', '\u{1f}'), ('"', '"'), ('\\', '\\'), ('!', '"')],
+            SymbolProperties::new("", true, false),
+        );
+        parser.root_node(&symbol);
+
+        for ok in ["a", "#", "[", "ä"] {
+            let mut stream = CharStream::from(ok);
+            assert!(matches!(parser.parse(&mut stream), ParseResult::Ok(_)));
+        }
+        for err in ["\n", "\u{1f}", "!", "\"", "\\"] {
+            let mut stream = CharStream::from(err);
+            assert!(matches!(parser.parse(&mut stream), ParseResult::Err(_)));
+        }
+    }
+
+    #[test]
+    fn merged_ranges() {
+        assert_eq!(
+            vec![('a', 'f'), ('x', 'x')],
+            merge_ranges(&[('x', 'x'), ('d', 'f'), ('a', 'c'), ('b', 'd')])
+        );
+    }
+
+    #[test]
+    fn backtracking_warnings() {
+        // S = ( 'a' S 'b' ) | ( 'a' S 'c' ) | 'a', exponential on "a..ac"
+        let mut parser = Parser::new();
+        let a = parser.terminal('a', None);
+        let b = parser.terminal('b', None);
+        let c = parser.terminal('c', None);
+        let s = parser.one_of(vec![], SymbolProperties::new("S", false, true));
+        let with_b = parser.sequence(vec![&a, &s, &b], None);
+        let with_c = parser.sequence(vec![&a, &s, &c], None);
+        parser.add_ident(&s, &with_b);
+        parser.add_ident(&s, &with_c);
+        parser.add_ident(&s, &a);
+        let root = parser
+            .sequence(vec![&s], SymbolProperties::new("ROOT", false, false));
+        parser.root_node(&root);
+        let parser = parser.with_backtracking_warnings(10);
+
+        let (_, quiet) =
+            parser.parse_with_warnings(&mut CharStream::from("aab"));
+        let mut stream = CharStream::from("aaaaaaaaaaaac");
+        let (_, warnings) = parser.parse_with_warnings(&mut stream);
+
+        assert!(quiet.is_empty());
+        assert_eq!(1, warnings.len());
+        assert_eq!("S", warnings[0].0);
+        assert!(warnings[0].1 > 10 * 13);
+    }
+
+    #[test]
+    fn separated() {
+        let accepted = |trailing: TrailingMode| {
+            let mut parser = Parser::new();
+            let item = parser.terminal('a', None);
+            let comma = parser.terminal(',', None);
+            let symbol = parser.separated(
+                &item,
+                &comma,
+                trailing,
+                SymbolProperties::new("LIST", false, false),
+            );
+            parser.root_node(&symbol);
+            ["", ",", "a", "a,", "a,a", "a,a,", "a,,"]
+                .into_iter()
+                .filter(|input| {
+                    parser.parse(&mut CharStream::from(input)).is_ok()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["", "a", "a,a"], accepted(TrailingMode::Never));
+        assert_eq!(
+            vec!["", "a", "a,", "a,a", "a,a,"],
+            accepted(TrailingMode::Allowed)
+        );
+        assert_eq!(
+            vec!["", "a,", "a,a,"],
+            accepted(TrailingMode::RequiredIfNonEmpty)
+        );
+    }
+
+    #[test]
+    fn separated_at_least() {
+        let accepted = |min: usize, trailing: TrailingMode| {
+            let mut parser = Parser::new();
+            let item = parser.terminal('a', None);
+            let comma = parser.terminal(',', None);
+            let symbol = parser.separated_at_least(
+                &item,
+                &comma,
+                min,
+                trailing,
+                SymbolProperties::new("LIST", false, false),
+            );
+            parser.root_node(&symbol);
+            ["", ",", "a", "a,", "a,a", "a,a,", "a,a,a", "a,,a"]
+                .into_iter()
+                .filter(|input| {
+                    parser.parse(&mut CharStream::from(input)).is_ok()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(vec!["a", "a,a", "a,a,a"], accepted(1, TrailingMode::Never));
+        assert_eq!(vec!["a,a", "a,a,a"], accepted(2, TrailingMode::Never));
+        assert_eq!(
+            vec!["a,a", "a,a,", "a,a,a"],
+            accepted(2, TrailingMode::Allowed)
+        );
+        assert_eq!(vec!["a,a,"], accepted(2, TrailingMode::RequiredIfNonEmpty));
+    }
+
+    #[test]
+    fn one_of_prioritized() {
+        fn winner(keyword_first: bool) -> String {
+            let mut parser = Parser::new();
+            let keyword = parser.sequence_chars(
+                "if",
+                SymbolProperties::new("KEYWORD", true, false),
+            );
+            let i = parser.terminal('i', None);
+            let f = parser.terminal('f', None);
+            let letter = parser.one_of(vec![&i, &f], None);
+            let ident = parser.one_or_more(
+                &letter,
+                SymbolProperties::new("IDENT", true, false),
+            );
+            let alternatives = if keyword_first {
+                vec![(1, &keyword), (2, &ident)]
+            } else {
+                vec![(2, &ident), (1, &keyword)]
+            };
+            let word = parser.one_of_prioritized(
+                alternatives,
+                SymbolProperties::new("WORD", false, false),
+            );
+            parser.root_node(&word);
+            let node = parser.parse(&mut CharStream::from("if")).unwrap();
+            match node.data {
+                NodeData::Children(c) => c[0].node_type.clone(),
+                NodeData::Raw(_) => panic!("WORD is not raw"),
+            }
+        }
+
+        assert_eq!("KEYWORD", winner(true));
+        assert_eq!("KEYWORD", winner(false));
+    }
+
+    #[test]
+    fn balanced() {
+        let mut parser = Parser::new();
+        let balanced = parser.balanced(
+            '(',
+            ')',
+            SymbolProperties::new("SPAN", false, false),
+        );
+        parser.root_node(&balanced);
+
+        let node = parser.parse(&mut CharStream::from("(a(b)c)")).unwrap();
+        assert_eq!(NodeData::Raw("a(b)c".to_owned()), node.data);
+        assert_eq!(0..7, node.loc.byte_span());
+        assert!(parser.parse(&mut CharStream::from("(a(b")).is_err());
+        assert!(parser.parse(&mut CharStream::from("(a)b)")).is_err());
+    }
+
+    #[test]
+    fn nested_comment() {
+        let mut parser = Parser::new();
+        let comment = parser.nested_comment(
+            "/*",
+            "*/",
+            SymbolProperties::new("COMMENT", false, false),
+        );
+        parser.root_node(&comment);
+        let parse = |input: &str| parser.parse(&mut CharStream::from(input));
+
+        let flat = parse("/* a */").unwrap();
+        assert_eq!(NodeData::Raw("/* a */".to_owned()), flat.data);
+        let single = parse("/* a /* b */ c */").unwrap();
+        assert_eq!(0..17, single.loc.byte_span());
+        assert!(parse("/* a /* b /* c */ */ d */").is_ok());
+        assert!(parse("/* a /* b */ c").is_err());
+        assert!(parse("/* a */ b */").is_err());
+    }
+
+    #[test]
+    fn rest_of_line() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = LINE { U+A LINE };\nLINE = $EOL_TEXT;",
+        )
+        .unwrap();
+        let node = parser
+            .parse(&mut CharStream::from("key = value\n\nlast"))
+            .unwrap();
+
+        let lines: Vec<&NodeData> =
+            node.iter_children().map(|line| &line.data).collect();
+        assert_eq!(
+            vec![
+                &NodeData::Raw("key = value".to_owned()),
+                &NodeData::Raw(String::new()),
+                &NodeData::Raw("last".to_owned()),
+            ],
+            lines
+        );
+        assert!(parser.parse(&mut CharStream::from("a\n")).is_ok());
+    }
+
+    #[test]
+    fn take() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = ID NAME;\nID = .{5};\nNAME = $EOL_TEXT;",
+        )
+        .unwrap();
+        let node = parser.parse(&mut CharStream::from("0\n042Alice")).unwrap();
+        assert_eq!(
+            NodeData::Raw("0\n042".to_owned()),
+            node.field("ID").unwrap().data
+        );
+        assert_eq!(
+            Some(5),
+            parser.fixed_length(&parser.find_symbol("ID").unwrap())
+        );
+
+        let mut parser = Parser::new();
+        let five = parser.take(5, SymbolProperties::new("FIVE", false, false));
+        parser.root_node(&five);
+        assert!(parser.parse(&mut CharStream::from("12345")).is_ok());
+        assert!(parser.parse(&mut CharStream::from("123")).is_err());
+    }
+
+    #[test]
+    fn nested_comment_skipped() {
+        let mut parser = Parser::new();
+        let comment = parser.nested_comment("(*", "*)", None);
+        let a = parser.terminal('a', None);
+        let b = parser.terminal('b', None);
+        let root = parser.sequence(
+            vec![&a, &comment, &b],
+            SymbolProperties::new("ROOT", true, false),
+        );
+        parser.root_node(&root);
+
+        let node = parser.parse(&mut CharStream::from("a(*(**)*)b")).unwrap();
+        assert_eq!(NodeData::Raw("a(*(**)*)b".to_owned()), node.data);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "SymbolIdent of another parser")]
+    fn ident_of_other_parser() {
+        let mut parser = Parser::new();
+        parser.terminal('a', None);
+        let mut other = Parser::new();
+        let b = other.terminal('b', None);
+
+        parser.get_symbol(&b);
+    }
+
+    #[test]
+    fn marker() {
+        let mut parser = Parser::new();
+        let a = parser.terminal('a', SymbolProperties::new("A", true, false));
+        let marker = parser.marker("MARK");
+        let root = parser.sequence(
+            vec![&a, &a, &marker, &a],
+            SymbolProperties::new("ROOT", false, false),
+        );
+        parser.root_node(&root);
+
+        let node = parser.parse(&mut CharStream::from("aaa")).unwrap();
+        let mark = node.field("MARK").unwrap();
+        assert_eq!(2, mark.loc.0.index());
+        assert_eq!(mark.loc.0, mark.loc.1);
+        assert_eq!(NodeData::Children(vec![]), mark.data);
+        assert!(parser.lint().is_empty());
+    }
+
+    #[test]
+    fn repeat() {
+        let mut parser = Parser::new();
+        let digit = parser.range('0', '9', None);
+        let root = parser.repeat(
+            &digit,
+            2,
+            Some(4),
+            SymbolProperties::new("NUMBER", true, false),
+        );
+        parser.root_node(&root);
+
+        for (input, ok) in [("1", false), ("12", true), ("1234", true)] {
+            assert_eq!(ok, parser.parse(&mut CharStream::from(input)).is_ok());
+        }
+        assert!(parser.parse(&mut CharStream::from("12345")).is_err());
+    }
+
+    #[test]
+    fn range() {
+        let mut parser = Parser::new();
+        let digit = parser.range('0', '9', None);
+        let root = parser
+            .one_or_more(&digit, SymbolProperties::new("NUMBER", true, false));
+        parser.root_node(&root);
+
+        assert_eq!("'0'..'9'", parser.get_symbol(&digit).repr(&parser));
+        assert!(parser.parse(&mut CharStream::from("09")).is_ok());
+        assert!(parser.parse(&mut CharStream::from("0a")).is_err());
+        assert_eq!(
+            "NUMBER(root,raw) = '0'..'9' { '0'..'9' };\n",
+            parser.to_bnf().unwrap()
+        );
+    }
+
+    #[test]
+    fn expected_chars() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = VALUE { ',' VALUE };\n\
+             VALUE = ( '[' ']' ) | ( '{' '}' ) | DIGIT;\nDIGIT(raw) = '0'..'9';",
+        ).unwrap();
+        let expected =
+            |input: &str| match parser.parse(&mut CharStream::from(input)) {
+                Err(ParseError::Syntax { expected, .. }) => expected,
+                other => panic!("Unexpected result {:?}", other),
+            };
+
+        assert_eq!(
+            "0123456789[{".chars().collect::<Vec<_>>(),
+            expected("[],x")
+        );
+        assert_eq!(vec![']'], expected("[,"));
+        assert_eq!(vec![','], expected("1 "));
+        let err = parser.parse(&mut CharStream::from("1 ")).unwrap_err();
+        assert_eq!("Expected one of ',' at 1:2", err.to_string());
+    }
+
+    #[test]
+    fn parse_any_of() {
+        let parser = Parser::from_bnf(
+            "INPUT(root) = STMT | EXPR;\n\
+             STMT = 'l' 'e' 't' ' ' NAME ' ' '=' ' ' EXPR;\n\
+             EXPR = NAME | NUMBER;\n\
+             NAME(raw) = 'a'..'z' { 'a'..'z' };\n\
+             NUMBER(raw) = '0'..'9' { '0'..'9' };",
+        )
+        .unwrap();
+        let parse = |input: &str| {
+            parser.parse_any_of(&["STMT", "EXPR"], &mut CharStream::from(input))
+        };
+
+        let (rule, node) = parse("let x = 42").unwrap();
+        assert_eq!("STMT", rule);
+        assert_eq!(
+            r#"(STMT (NAME "x") (EXPR (NUMBER "42")))"#,
+            node.to_sexpr()
+        );
+        let (rule, node) = parse("let").unwrap();
+        assert_eq!("EXPR", rule);
+        assert_eq!(r#"(EXPR (NAME "let"))"#, node.to_sexpr());
+        match parse("let x = ?") {
+            Err(ParseError::Syntax { pos, .. }) => assert_eq!(8, pos.index()),
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert_eq!(
+            Err(ParseError::NoRoot),
+            parser.parse_any_of(&[], &mut CharStream::from("1"))
+        );
+    }
+
+    #[test]
+    fn max_nodes() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = { ITEM };\nITEM = A | B;\nA(raw) = 'a';\nB(raw) = 'b';",
+        ).unwrap()
+        .with_max_nodes(20);
+
+        let node = parser.parse(&mut CharStream::from("abababab")).unwrap();
+        assert_eq!(8, node.iter_children().count());
+        assert_eq!(
+            Err(ParseError::TooManyNodes),
+            parser.parse(&mut CharStream::from("ababababab"))
+        );
+        assert!(parser.parse(&mut CharStream::from("ab")).is_ok());
+    }
+
+    #[test]
+    fn visitor() {
+        /// Collects the nodes of one type, with how deeply they are nested.
+        struct Collect<'n> {
+            node_type: &'static str,
+            depth: usize,
+            nodes: Vec<(usize, &'n Node)>,
+        }
+
+        impl<'n> Visitor<'n> for Collect<'n> {
+            fn enter(&mut self, node: &'n Node) {
+                if node.node_type == self.node_type {
+                    self.nodes.push((self.depth, node));
+                }
+                self.depth += 1;
+            }
+
+            fn leave(&mut self, _node: &'n Node) {
+                self.depth -= 1;
+            }
+        }
+
+        let parser = crate::examples::json::json_parser();
+        let input = r#"[1, {"a": [2]}, 3]"#;
+        let tree = parser.parse(&mut CharStream::from(input)).unwrap();
+
+        let mut numbers = Collect {
+            node_type: "NUMBER",
+            depth: 0,
+            nodes: Vec::new(),
+        };
+        numbers.visit_node(&tree);
+        let found: Vec<(usize, String)> = numbers
+            .nodes
+            .iter()
+            .map(|(depth, node)| (*depth, node.to_sexpr()))
+            .collect();
+        assert_eq!(
+            vec![
+                (2, "(NUMBER \"1\")".to_owned()),
+                (5, "(NUMBER \"2\")".to_owned()),
+                (2, "(NUMBER \"3\")".to_owned()),
+            ],
+            found
+        );
+        assert_eq!(0, numbers.depth);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let parser = crate::examples::json::json_parser();
+        let tree = parser.parse(&mut CharStream::from("[true]")).unwrap();
+
+        let json = serde_json::to_value(&tree).unwrap();
+        assert_eq!(
+            serde_json::json!({"line": 1, "col": 2, "index": 1, "byte": 1}),
+            json["data"]["children"][0]["data"]["children"][0]["loc"]["start"]
+        );
+        assert_eq!(
+            serde_json::json!({"raw": "true"}),
+            json["data"]["children"][0]["data"]["children"][0]["data"]
+        );
+        assert_eq!(Ok(tree), serde_json::from_value(json).map_err(|_| ()));
+    }
+
+    #[test]
+    fn max_depth() {
+        let parser = crate::examples::json::json_parser().with_max_depth(100);
+        let nested =
+            |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        assert!(parser
+            .parse(&mut CharStream::from(nested(10).as_str()))
+            .is_ok());
+        assert_eq!(
+            Err(ParseError::TooDeep),
+            parser.parse(&mut CharStream::from(nested(1000).as_str()))
+        );
+    }
+
+    #[test]
+    fn parse_streaming() {
+        let parser = crate::examples::json::json_parser();
+        let mut stream = CharStream::from(r#"{"a": [1, "#);
+
+        assert_eq!(
+            Err(ParseError::Incomplete),
+            parser.parse_streaming(&mut stream)
+        );
+        stream.push_str(r#"2], "b": null}"#);
+        let node = parser.parse_streaming(&mut stream).unwrap();
+        assert_eq!(
+            2,
+            node.field("OBJECT").map_or(0, |o| match &o.data {
+                NodeData::Children(c) => c.len(),
+                NodeData::Raw(_) => 0,
+            })
+        );
+
+        let mut stream = CharStream::from(r#"{"a" 1"#);
+        assert!(matches!(
+            parser.parse_streaming(&mut stream),
+            Err(ParseError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn lookbehind() {
+        let mut parser = Parser::new();
+        let digit = parser.one_of_chars("0123456789", None);
+        let after_digit = parser.lookbehind(&digit, None);
+        let x = parser.terminal('x', None);
+        let marked = parser.sequence(
+            vec![&after_digit, &x],
+            SymbolProperties::new("MARKED", true, false),
+        );
+        let any =
+            parser.any_except(&[], SymbolProperties::new("CHAR", true, false));
+        let item = parser.one_of(vec![&marked, &any], None);
+        let items = parser
+            .zero_or_more(&item, SymbolProperties::new("ITEMS", false, false));
+        parser.root_node(&items);
+
+        let tree = parser.parse(&mut CharStream::from("x1x\nx2xx")).unwrap();
+        let marked: Vec<usize> = tree
+            .iter_children()
+            .filter(|n| n.node_type == "MARKED")
+            .map(|n| n.loc.0.index())
+            .collect();
+        assert_eq!(vec![2, 6], marked);
+        assert_eq!(8, tree.iter_children().count());
+    }
+
+    #[test]
+    #[should_panic(expected = "not of fixed length")]
+    fn lookbehind_variable_length() {
+        let mut parser = Parser::new();
+        let a = parser.terminal('a', None);
+        let many = parser.one_or_more(&a, None);
+        parser.lookbehind(&many, None);
+    }
+
+    #[test]
+    fn failed_element() {
+        let parser = crate::examples::json::json_parser();
+
+        match parser.parse(&mut CharStream::from("[1, 2")) {
+            Err(err @ ParseError::Syntax { .. }) => {
+                let ParseError::Syntax {
+                    element: Some(element),
+                    ..
+                } = &err
+                else {
+                    panic!("No element in {:?}", err);
+                };
+                assert_eq!(Some("ARRAY"), element.rule.as_deref());
+                assert_eq!(3, element.index);
+                assert_eq!("']'", element.expected);
+                assert_eq!(5, element.pos.index());
+                assert_eq!("Expected ']' in ARRAY at 1:6", err.to_string());
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn error_message_of_alternative() {
+        let parser = Parser::from_bnf(
+            "ROOT(root) = KEYWORD | NAME;\n\
+             KEYWORD = 'l' 'e' 't' '!';\n\
+             NAME(error=\"expected a name\") = 'x' 'y';",
+        )
+        .unwrap();
+        let message =
+            |input: &str| match parser.parse(&mut CharStream::from(input)) {
+                Err(ParseError::Syntax { pos, message, .. }) => {
+                    (pos.index(), message)
+                }
+                other => panic!("Unexpected result {:?}", other),
+            };
+
+        assert_eq!((3, None), message("let?"));
+        assert_eq!((1, Some("expected a name".to_owned())), message("xz"));
+        assert_eq!((0, Some("expected a name".to_owned())), message("?"));
+    }
+
+    #[test]
+    fn matches_at() {
+        let parser = crate::examples::json::json_parser();
+        let number = parser.find_symbol("NUMBER").unwrap();
+        let mut stream = CharStream::from("[1, a]");
+
+        stream.next();
+        let pos = stream.get_pos();
+        assert!(parser.matches_at(&number, &stream));
+        assert_eq!(pos, stream.get_pos());
+        stream.next();
+        stream.next();
+        let pos = stream.get_pos();
+        assert!(!parser.matches_at(&number, &stream));
+        assert_eq!(pos, stream.get_pos());
+    }
+
+    #[test]
+    fn expected_children() {
+        let trees: Vec<Node> = [None, Some(1), Some(100)]
+            .into_iter()
+            .map(|expected| {
+                let mut parser = Parser::new();
+                let a = parser
+                    .terminal('a', SymbolProperties::new("A", true, false));
+                let props = SymbolProperties::new("AS", false, false);
+                let many = parser.zero_or_more(
+                    &a,
+                    match expected {
+                        Some(count) => {
+                            props.map(|p| p.with_expected_children(count))
+                        }
+                        None => props,
+                    },
+                );
+                parser.root_node(&many);
+                parser.parse(&mut CharStream::from("aaaa")).unwrap()
+            })
+            .collect();
+
+        assert_eq!(trees[0], trees[1]);
+        assert_eq!(trees[0], trees[2]);
+    }
+
     #[test]
     fn one_or_more() {
         let mut parser = Parser::new();
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use crate::input::Range;
+use crate::parse::Node;
+use crate::parse::NodeData;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`.
+    Right,
+}
+
+/// Binding strength of operator nodes, by node type, for
+/// [`Node::resolve_precedence`]. Higher precedences bind tighter.
+#[derive(Clone, Debug, Default)]
+pub struct PrecedenceTable {
+    operators: HashMap<String, (u32, Associativity)>,
+}
+
+impl PrecedenceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the operator nodes of type `node_type`.
+    pub fn operator(
+        mut self,
+        node_type: &str,
+        precedence: u32,
+        associativity: Associativity,
+    ) -> Self {
+        self.operators
+            .insert(node_type.to_owned(), (precedence, associativity));
+        self
+    }
+}
+
+impl Node {
+    /// The binary operation tree for a flat list of children alternating
+    /// between operands and operators, as parsed by `NUMBER { OP NUMBER }`.
+    ///
+    /// Children whose type is in `table` are operators, all others are
+    /// operands. Each operation becomes a node of the operator's type with
+    /// the two operands as children, spanning both. A single operand is
+    /// returned as it is.
+    ///
+    /// Panics if the children do not alternate, starting and ending with an
+    /// operand.
+    pub fn resolve_precedence(&self, table: &PrecedenceTable) -> Node {
+        let children: Vec<&Node> = self.iter_children().collect();
+        let mut pos = 0;
+        let tree = climb(&children, &mut pos, 0, table);
+        if pos != children.len() {
+            panic!("Operator list of {} does not alternate", self.node_type);
+        }
+        tree
+    }
+}
+
+/// Folds the operations starting at `pos` whose operators have at least
+/// precedence `min`.
+fn climb(
+    children: &[&Node],
+    pos: &mut usize,
+    min: u32,
+    table: &PrecedenceTable,
+) -> Node {
+    let mut lhs = match children.get(*pos) {
+        Some(operand) if !table.operators.contains_key(&operand.node_type) => {
+            (*operand).clone()
+        }
+        _ => panic!("Expected an operand at child {}", pos),
+    };
+    *pos += 1;
+    while let Some(operator) = children.get(*pos) {
+        let (precedence, associativity) =
+            match table.operators.get(&operator.node_type) {
+                Some(entry) => *entry,
+                None => break,
+            };
+        if precedence < min {
+            break;
+        }
+        *pos += 1;
+        let next_min = match associativity {
+            Associativity::Left => precedence + 1,
+            Associativity::Right => precedence,
+        };
+        let rhs = climb(children, pos, next_min, table);
+        let loc = Range::new(lhs.loc.0, rhs.loc.1);
+        lhs = Node::new(
+            &operator.node_type,
+            loc,
+            NodeData::Children(vec![lhs, rhs]),
+        );
+    }
+    lhs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::CharStream;
+    use crate::parse::Parser;
+
+    fn arithmetic() -> PrecedenceTable {
+        PrecedenceTable::new()
+            .operator("ADD", 1, Associativity::Left)
+            .operator("SUB", 1, Associativity::Left)
+            .operator("MUL", 2, Associativity::Left)
+            .operator("POW", 3, Associativity::Right)
+    }
+
+    #[test]
+    fn arithmetic_tree() {
+        let parser = Parser::from_bnf(
+            "EXPR(root) = NUMBER { ( ADD | SUB | MUL | POW ) NUMBER };\n\
+             NUMBER(raw) = '0'..'9';\nADD(raw) = '+';\nSUB(raw) = '-';\n\
+             MUL(raw) = U+2A;\nPOW(raw) = '^';",
+        )
+        .unwrap();
+        let resolve = |input: &str| {
+            parser
+                .parse(&mut CharStream::from(input))
+                .unwrap()
+                .resolve_precedence(&arithmetic())
+        };
+
+        let tree = resolve("1+2*3-4");
+        assert_eq!(
+            r#"(SUB (ADD (NUMBER "1") (MUL (NUMBER "2") (NUMBER "3"))) (NUMBER "4"))"#,
+            tree.to_sexpr()
+        );
+        assert_eq!(0..7, tree.loc.byte_span());
+        assert_eq!(
+            r#"(POW (NUMBER "2") (POW (NUMBER "3") (NUMBER "2")))"#,
+            resolve("2^3^2").to_sexpr()
+        );
+        assert_eq!(r#"(NUMBER "7")"#, resolve("7").to_sexpr());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected an operand at child 2")]
+    fn not_alternating() {
+        let parser = Parser::from_bnf(
+            "EXPR(root) = NUMBER { ADD };\nNUMBER(raw) = '0'..'9';\n\
+             ADD(raw) = '+';",
+        )
+        .unwrap();
+        let node = parser.parse(&mut CharStream::from("1+")).unwrap();
+        node.resolve_precedence(&arithmetic());
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use crate::parse::Parser;
+use crate::parse::SymbolIdent;
+
+/// The shape of the trees a grammar produces: for every node type, the node
+/// types that can appear as its direct children.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GrammarSchema {
+    rules: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl GrammarSchema {
+    pub fn children(&self, node_type: &str) -> Option<&BTreeSet<String>> {
+        self.rules.get(node_type)
+    }
+
+    pub fn node_types(&self) -> impl Iterator<Item = &String> {
+        self.rules.keys()
+    }
+}
+
+impl Parser {
+    /// Computes the [`GrammarSchema`] of this parser. Ignored rules are
+    /// transparent: the nodes they keep show up as children of the nearest
+    /// emitted ancestor. Raw rules have no children.
+    pub fn schema(&self) -> GrammarSchema {
+        let mut schema = GrammarSchema::default();
+        for symbol in &self.symbol_registry {
+            if symbol.properties.ignore {
+                continue;
+            }
+            let children =
+                schema.rules.entry(symbol.name().to_owned()).or_default();
+            if symbol.properties.raw {
+                continue;
+            }
+            let mut visited = BTreeSet::new();
+            for id in symbol.sub_symbols() {
+                self.collect_visible(id, &mut visited, children);
+            }
+        }
+        schema
+    }
+
+    fn collect_visible(
+        &self,
+        ident: &SymbolIdent,
+        visited: &mut BTreeSet<usize>,
+        children: &mut BTreeSet<String>,
+    ) {
+        let symbol = self.get_symbol(ident);
+        if !symbol.properties.ignore {
+            children.insert(symbol.name().to_owned());
+        } else if visited.insert(ident.0) {
+            for id in symbol.sub_symbols() {
+                self.collect_visible(id, visited, children);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::examples::json::json_parser;
+
+    #[test]
+    fn json_schema() {
+        let schema = json_parser().schema();
+
+        let object = schema.children("OBJECT").unwrap();
+        assert!(object.contains("PROPERTY"));
+        assert_eq!(1, object.len());
+
+        let array = schema.children("ARRAY").unwrap();
+        for node_type in ["STRING", "NUMBER", "OBJECT", "ARRAY", "NULL"] {
+            assert!(array.contains(node_type));
+        }
+        assert!(schema.children("STRING").unwrap().is_empty());
+        assert!(schema.children("VALUE").is_none());
+    }
+}
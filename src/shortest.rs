@@ -0,0 +1,109 @@
+use crate::parse::in_ranges;
+use crate::parse::Parser;
+use crate::parse::SymbolIdent;
+use crate::parse::SymbolType;
+
+impl Parser {
+    /// The shortest input `ident` matches, the lexicographically smallest one
+    /// among equally short ones. `None` if the symbol can never match.
+    ///
+    /// Where any char is allowed the first printable ASCII char that is not
+    /// excluded is picked.
+    pub fn shortest_match(&self, ident: &SymbolIdent) -> Option<String> {
+        let mut shortest: Vec<Option<String>> =
+            vec![None; self.symbol_registry.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (idx, symbol) in self.symbol_registry.iter().enumerate() {
+                let candidate = match &symbol.symbol_type {
+                    SymbolType::Sequence(s) => s
+                        .iter()
+                        .map(|id| shortest[id.0].as_deref())
+                        .collect::<Option<String>>(),
+                    SymbolType::OneOf(s) => s
+                        .iter()
+                        .filter_map(|id| shortest[id.0].clone())
+                        .min_by(|a, b| order(a).cmp(&order(b))),
+                    SymbolType::Optional(_) => Some(String::new()),
+                    SymbolType::ZeroOrMore(_) => Some(String::new()),
+                    SymbolType::OneOrMore(id) => shortest[id.0].clone(),
+                    SymbolType::Repeat { min: 0, .. } => Some(String::new()),
+                    SymbolType::Repeat { symbol, min, .. } => {
+                        shortest[symbol.0].as_ref().map(|s| s.repeat(*min))
+                    }
+                    SymbolType::Terminal(c) => Some(c.to_string()),
+                    SymbolType::AnyExcept(ranges) => (' '..=char::MAX)
+                        .find(|c| !in_ranges(ranges, *c))
+                        .map(String::from),
+                    SymbolType::Literal(s) => Some(s.clone()),
+                    SymbolType::LiteralSet(trie) => trie
+                        .words()
+                        .iter()
+                        .min_by(|a, b| order(a).cmp(&order(b)))
+                        .cloned(),
+                    SymbolType::Lookbehind { .. } => Some(String::new()),
+                    SymbolType::And(_) => Some(String::new()),
+                    SymbolType::Not(_) => Some(String::new()),
+                    SymbolType::CharSet(ranges) => ranges
+                        .iter()
+                        .find(|(_, to)| *to >= ' ')
+                        .map(|(from, _)| (*from).max(' '))
+                        .or(ranges.first().map(|(from, _)| *from))
+                        .map(String::from),
+                    SymbolType::EndOfInput => Some(String::new()),
+                    SymbolType::RestOfLine => Some(String::new()),
+                    SymbolType::Take(n) => Some(" ".repeat(*n)),
+                    SymbolType::Balanced { open, close } => {
+                        Some(format!("{}{}", open, close))
+                    }
+                    SymbolType::NestedComment { open, close } => {
+                        Some(format!("{}{}", open, close))
+                    }
+                };
+                let better = match (&candidate, &shortest[idx]) {
+                    (Some(new), Some(old)) => order(new) < order(old),
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                };
+                if better {
+                    shortest[idx] = candidate;
+                    changed = true;
+                }
+            }
+        }
+        shortest.swap_remove(ident.0)
+    }
+}
+
+fn order(s: &str) -> (usize, &str) {
+    (s.chars().count(), s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+
+    #[test]
+    fn json_value() {
+        let parser = json_parser();
+        let shortest =
+            |name| parser.shortest_match(&parser.find_symbol(name).unwrap());
+
+        assert_eq!(Some("0".to_owned()), shortest("VALUE"));
+        assert_eq!(Some("\"\"".to_owned()), shortest("STRING"));
+        assert_eq!(Some("[]".to_owned()), shortest("ARRAY"));
+        assert_eq!(Some("true".to_owned()), shortest("TRUE"));
+    }
+
+    #[test]
+    fn never_matching() {
+        let mut parser = Parser::new();
+        let a = parser.terminal('a', None);
+        let nothing = parser.one_of(vec![], None);
+        let symbol = parser.sequence(vec![&a, &nothing], None);
+
+        assert_eq!(None, parser.shortest_match(&symbol));
+    }
+}
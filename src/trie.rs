@@ -0,0 +1,120 @@
+use crate::input::CharStream;
+use crate::input::Position;
+
+/// Alternatives of literals merged into a prefix tree, so that a shared
+/// prefix is scanned once, see [`crate::Parser::compact`].
+#[derive(Clone, Debug)]
+pub struct LiteralTrie {
+    /// The literals in the order they are tried.
+    words: Vec<String>,
+    /// The root is at index 0.
+    nodes: Vec<TrieNode>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct TrieNode {
+    /// Sorted by char.
+    edges: Vec<(char, usize)>,
+    /// The first literal ending here.
+    word: Option<usize>,
+}
+
+impl LiteralTrie {
+    pub(crate) fn new(words: Vec<String>) -> Self {
+        let mut nodes = vec![TrieNode::default()];
+        for (idx, word) in words.iter().enumerate() {
+            let mut node = 0;
+            for c in word.chars() {
+                node = match nodes[node].edges.binary_search_by_key(&c, |e| e.0)
+                {
+                    Ok(edge) => nodes[node].edges[edge].1,
+                    Err(edge) => {
+                        nodes.push(TrieNode::default());
+                        let next = nodes.len() - 1;
+                        nodes[node].edges.insert(edge, (c, next));
+                        next
+                    }
+                };
+            }
+            nodes[node].word.get_or_insert(idx);
+        }
+        LiteralTrie { words, nodes }
+    }
+
+    pub(crate) fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    /// Every literal that matches at the current position of `stream`, by
+    /// index, with the position after it. The stream is left at the end of
+    /// the longest path followed.
+    pub(crate) fn matches(
+        &self,
+        stream: &mut CharStream,
+    ) -> Vec<(usize, Position)> {
+        let mut matches = Vec::new();
+        let mut node = &self.nodes[0];
+        loop {
+            if let Some(word) = node.word {
+                matches.push((word, stream.get_pos()));
+            }
+            let next = match stream.peek() {
+                Some(c) => node.edges.binary_search_by_key(c, |e| e.0),
+                None => break,
+            };
+            match next {
+                Ok(edge) => {
+                    node = &self.nodes[node.edges[edge].1];
+                    stream.next();
+                }
+                Err(_) => break,
+            }
+        }
+        matches
+    }
+
+    /// Advances `stream` over the first literal that matches, as an ordered
+    /// choice between them would.
+    pub(crate) fn advance(&self, stream: &mut CharStream) -> bool {
+        let start = stream.get_pos();
+        match self.matches(stream).into_iter().min_by_key(|m| m.0) {
+            Some((_, end)) => {
+                stream.set_pos(end);
+                true
+            }
+            None => {
+                stream.set_pos(start);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordered_choice() {
+        let trie = LiteralTrie::new(vec![
+            "letrec".to_owned(),
+            "let".to_owned(),
+            "length".to_owned(),
+            "let".to_owned(),
+        ]);
+        let advance = |input: &str| {
+            let mut stream = CharStream::from(input);
+            trie.advance(&mut stream).then(|| stream.get_pos().index())
+        };
+
+        assert_eq!(Some(6), advance("letrec x"));
+        assert_eq!(Some(3), advance("letre"));
+        assert_eq!(Some(6), advance("length"));
+        assert_eq!(None, advance("leng"));
+        assert_eq!(None, advance(""));
+        let mut stream = CharStream::from("letrec");
+        let ends: Vec<usize> =
+            trie.matches(&mut stream).iter().map(|m| m.0).collect();
+        assert_eq!(vec![1, 0], ends);
+    }
+}
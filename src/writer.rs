@@ -0,0 +1,172 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::io;
+use std::io::Write;
+
+use crate::input::CharStream;
+use crate::parse::Node;
+use crate::parse::NodeData;
+use crate::parse::ParseError;
+use crate::parse::Parser;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// `{"type": "TYPE", "children": [...]}` for nodes with children and
+    /// `{"type": "TYPE", "raw": "..."}` for raw nodes.
+    Json,
+    /// As [`Node::to_sexpr`].
+    Sexpr,
+}
+
+#[derive(Debug)]
+pub enum WriteError {
+    /// The input does not parse.
+    Parse(ParseError),
+    /// Writing the tree failed.
+    Io(io::Error),
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Parse(err) => write!(f, "{}", err),
+            WriteError::Io(err) => write!(f, "Failed to write tree: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+impl Parser {
+    /// Parses like [`Parser::parse`] and writes the tree to `w` in `format`,
+    /// node by node, without building the text in memory first.
+    ///
+    /// This is not a streaming parse and holds as much memory as
+    /// [`Parser::parse`]: backtracking may drop any node until the parse is
+    /// complete, so the whole tree is built before anything is written.
+    /// Nothing is written if the parse fails.
+    pub fn parse_to_writer(
+        &self,
+        stream: &mut CharStream,
+        w: &mut impl Write,
+        format: OutputFormat,
+    ) -> Result<(), WriteError> {
+        let root = self.parse(stream).map_err(WriteError::Parse)?;
+        root.write_to(w, format).map_err(WriteError::Io)
+    }
+}
+
+impl Node {
+    /// Writes the tree to `w` in `format`.
+    pub fn write_to(
+        &self,
+        w: &mut impl Write,
+        format: OutputFormat,
+    ) -> io::Result<()> {
+        match format {
+            OutputFormat::Json => self.write_json(w),
+            OutputFormat::Sexpr => self.write_sexpr(w),
+        }
+    }
+
+    fn write_json(&self, w: &mut impl Write) -> io::Result<()> {
+        write!(w, "{{\"type\":")?;
+        write_json_string(w, &self.node_type)?;
+        match &self.data {
+            NodeData::Children(c) => {
+                write!(w, ",\"children\":[")?;
+                for (idx, child) in c.iter().enumerate() {
+                    if idx > 0 {
+                        write!(w, ",")?;
+                    }
+                    child.write_json(w)?;
+                }
+                write!(w, "]}}")
+            }
+            NodeData::Raw(raw) => {
+                write!(w, ",\"raw\":")?;
+                write_json_string(w, raw)?;
+                write!(w, "}}")
+            }
+        }
+    }
+
+    fn write_sexpr(&self, w: &mut impl Write) -> io::Result<()> {
+        write!(w, "({}", self.node_type)?;
+        match &self.data {
+            NodeData::Children(c) => {
+                for child in c {
+                    write!(w, " ")?;
+                    child.write_sexpr(w)?;
+                }
+            }
+            NodeData::Raw(raw) => {
+                write!(w, " \"")?;
+                for c in raw.chars() {
+                    if c == '"' || c == '\\' {
+                        write!(w, "\\")?;
+                    }
+                    write!(w, "{}", c)?;
+                }
+                write!(w, "\"")?;
+            }
+        }
+        write!(w, ")")
+    }
+}
+
+fn write_json_string(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write!(w, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(w, "\\\"")?,
+            '\\' => write!(w, "\\\\")?,
+            '\n' => write!(w, "\\n")?,
+            c if (c as u32) < 0x20 => write!(w, "\\u{:04x}", c as u32)?,
+            c => write!(w, "{}", c)?,
+        }
+    }
+    write!(w, "\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::examples::json::json_parser;
+
+    #[test]
+    fn large_array() {
+        let parser = json_parser();
+        let items: Vec<String> = (0..2000).map(|i| i.to_string()).collect();
+        let input = format!("[{}, \"a\\\"\\n\"]", items.join(", "));
+
+        let mut out = Vec::new();
+        parser
+            .parse_to_writer(
+                &mut CharStream::from(input.as_str()),
+                &mut out,
+                OutputFormat::Json,
+            )
+            .unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.starts_with(
+            r#"{"type":"ROOT","children":[{"type":"ARRAY","children":[{"type":"NUMBER","raw":"0"},"#
+        ));
+        assert!(json.ends_with(r#"{"type":"STRING","raw":"\"a\\\"\\n\""}]}]}"#));
+        assert!(parser.parse(&mut CharStream::from(json.as_str())).is_ok());
+    }
+
+    #[test]
+    fn syntax_error() {
+        let parser = json_parser();
+
+        let mut out = Vec::new();
+        let result = parser.parse_to_writer(
+            &mut CharStream::from("[1,"),
+            &mut out,
+            OutputFormat::Sexpr,
+        );
+        assert!(matches!(result, Err(WriteError::Parse(_))));
+        assert!(out.is_empty());
+    }
+}